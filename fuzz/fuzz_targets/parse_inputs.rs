@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// 输入按 0x00 分成三段：contig FASTA、突变 CSV、基因位置 CSV。缺少的段按空输入处理
+fuzz_target!(|data: &[u8]| {
+    let mut parts = data.splitn(3, |&byte| byte == 0);
+    let contigs = parts.next().unwrap_or_default();
+    let mutations = parts.next().unwrap_or_default();
+    let positions = parts.next().unwrap_or_default();
+    let _ = z10::gene_snv_replace_bytes(contigs, mutations, positions);
+});
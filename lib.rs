@@ -0,0 +1,8729 @@
+//! z10: apply SNVs and indels to contigs and extract the mutated gene sequences.
+//!
+//! The command-line tool is a thin wrapper around [`run_cli`]. Other Rust code can read inputs with
+//! [`read_contigs`], [`read_mutations`] and [`read_gene_positions`], or build [`Mutation`] and [`GeneInfo`]
+//! values in memory, and pass them to [`gene_snv_replace`] without touching the filesystem.
+
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+use rayon::ThreadPoolBuilder;
+use rayon::prelude::*;
+use clap::{App, AppSettings, Arg, SubCommand};
+use tracing::{field, info_span};
+use tracing_subscriber::fmt::format::FmtSpan;
+
+// --lang en：运行中的消息、警告和错误使用英文（默认中文）
+static ENGLISH_MESSAGES: AtomicBool = AtomicBool::new(false);
+
+// 按 --lang 选择消息：中文和英文模板使用相同的参数（英文可用 {0}、{1} 调整顺序），返回格式化后的 String
+macro_rules! tr {
+    ($zh:literal, $en:literal $(, $arg:expr)* $(,)?) => {
+        if $crate::ENGLISH_MESSAGES.load(std::sync::atomic::Ordering::Relaxed) {
+            format!($en $(, $arg)*)
+        } else {
+            format!($zh $(, $arg)*)
+        }
+    };
+}
+
+// 未给出 --lang 时根据 LC_ALL、LC_MESSAGES、LANG（取第一个非空的）选择：zh 开头、未设置或 C/POSIX 时使用中文，其他使用英文
+fn english_from_locale() -> bool {
+    let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty());
+    match locale {
+        Some(locale) => {
+            let language = locale.split(['_', '.', '@']).next().unwrap_or_default();
+            !(language.starts_with("zh") || language == "C" || language == "POSIX")
+        }
+        None => false,
+    }
+}
+
+/// A gene interval on a contig. Positions are 1-based and inclusive.
+#[derive(Debug, Clone)]
+pub struct GeneInfo {
+    pub contig_id: String,
+    pub start_position: usize,
+    pub end_position: usize,
+    pub gene_id: String,
+    pub strand: Strand,
+    /// The gene_id from the positions file when the gene was renamed (duplicate ids, variant sets); `None` otherwise.
+    pub source_id: Option<String>,
+}
+
+/// The strand a gene is annotated on; `Unknown` when the positions file gives none.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Strand {
+    Plus,
+    Minus,
+    Unknown,
+}
+
+impl Strand {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "+" => Some(Strand::Plus),
+            "-" => Some(Strand::Minus),
+            "" | "." => Some(Strand::Unknown),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Strand::Plus => "+",
+            Strand::Minus => "-",
+            Strand::Unknown => ".",
+        }
+    }
+}
+
+/// Contig id -> sequence, one byte per base, as returned by [`read_contigs`].
+pub type Contigs = HashMap<String, Vec<u8>>;
+
+/// A substitution at a 1-based contig position, or an indel when `indel` is set.
+#[derive(Debug, Clone)]
+pub struct Mutation {
+    pub contig_id: String,
+    pub position: usize,
+    pub new_base: char,
+    pub mutation_id: Option<String>, // 可选的突变ID（如 VCF 的 ID 列），缺失时输出为 "."
+    pub heterozygous: bool,          // 可选的第5列基因型为杂合；--het-as-ambiguity 时写成参考和替换碱基的 IUPAC 简并碱基
+    pub expected_ref: Option<char>,  // 可选的第6列：期望的参考碱基，应用前与 contig 上的碱基核对
+    pub indel: Option<Box<Indel>>,   // 插入或缺失；此时 new_base 不使用
+}
+
+/// An insertion or deletion: the reference bases starting at the mutation position are replaced by `alt`
+/// (VCF style, usually sharing the first anchor base).
+#[derive(Debug, Clone)]
+pub struct Indel {
+    pub reference: String,
+    pub alt: String,
+}
+
+/// How input files are parsed. The default matches the command line without options:
+/// 1-based, inclusive gene intervals, plain integer coordinates and an error on multi-allelic VCF records.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ParseOptions {
+    /// Gene start and end are 0-based and half-open, as in BED (`--coord-base 0`). Mutation positions are not affected.
+    pub zero_based_genes: bool,
+    /// Accept coordinates with digit group separators such as `1,234,567` or `1_234` (`--lenient-numbers`).
+    pub lenient_numbers: bool,
+    /// Apply only the first ALT of a multi-allelic VCF record instead of failing (`--vcf-multiallelic first`).
+    pub vcf_first_alt: bool,
+}
+
+impl Mutation {
+    fn id_or_dot(&self) -> &str {
+        self.mutation_id.as_deref().unwrap_or(".")
+    }
+}
+
+// 实际被应用的突变（经过过滤和范围检查之后），用于输出 applied VCF 等报告；插入/缺失也在其中（mutation.indel）
+#[derive(Debug, Clone)]
+struct AppliedMutation {
+    mutation: Mutation,
+    ref_base: char, // 插入/缺失时为参考序列的第一个碱基
+    #[cfg_attr(not(feature = "sqlite"), allow(dead_code))]
+    gene_id: String, // 覆盖该位点的第一个基因
+    edited_position: usize, // 应用了该 contig 上的全部插入/缺失之后的位置（没有插入/缺失时与 position 相同）
+}
+
+impl AppliedMutation {
+    fn is_indel(&self) -> bool {
+        self.mutation.indel.is_some()
+    }
+
+    // ref/alt 等位基因：替换为单个碱基，插入/缺失为参考和替换序列
+    fn alleles(&self) -> (String, String) {
+        match self.mutation.indel.as_deref() {
+            Some(indel) => (indel.reference.clone(), indel.alt.clone()),
+            None => (self.ref_base.to_string(), self.mutation.new_base.to_string()),
+        }
+    }
+}
+
+// 读入后没有被应用的突变及原因（reason 为 --sqlite 和 --report-parquet 中的 reason 列）
+#[derive(Debug, Clone)]
+#[cfg_attr(not(any(feature = "sqlite", feature = "arrow", feature = "python")), allow(dead_code))]
+struct RejectedMutation {
+    mutation: Mutation,
+    reason: &'static str,
+}
+
+#[cfg(any(feature = "sqlite", feature = "arrow"))]
+impl RejectedMutation {
+    // ref/alt 等位基因：没有应用时不读 contig，ref 只有输入给出时才有（第6列的期望碱基或插入/缺失的参考序列）
+    fn alleles(&self) -> (Option<String>, String) {
+        match self.mutation.indel.as_deref() {
+            Some(indel) => (Some(indel.reference.clone()), indel.alt.clone()),
+            None => (self.mutation.expected_ref.map(String::from), self.mutation.new_base.to_string()),
+        }
+    }
+}
+
+// gene_snv_replace_files 的处理结果
+struct ReplaceResult {
+    mutated_genes: HashMap<String, String>,
+    applied_mutations: Vec<AppliedMutation>,
+    #[cfg_attr(not(any(feature = "sqlite", feature = "arrow")), allow(dead_code))]
+    rejected_mutations: Vec<RejectedMutation>,
+    timings: Vec<(&'static str, Duration)>,
+    work_items: usize,             // 可并行的工作项数（有基因的 contig 数）
+    average_parallelism: f64,      // 工作线程总忙碌时间 / 并行阶段墙钟时间
+    mutation_inputs: Vec<MutationInputSummary>,
+    #[cfg_attr(not(feature = "sqlite"), allow(dead_code))]
+    gene_summaries: Vec<GeneSummary>,
+    mutation_stats: MutationStats,
+}
+
+// 突变的去向统计：读取的突变按（坐标换算和过滤之后的）位置归类，用于核对坐标文件是否对得上。
+// 有 --variant-set 时各集合共享的突变在每个集合中各计一次
+#[derive(Debug, Clone, Default)]
+struct MutationStats {
+    read: usize,
+    in_genes: usize,       // 落在某个基因区间内
+    applied: usize,        // 实际写入基因序列的（同一位点重复的、非 ASCII 的、--only-strand 之外的不计）
+    intergenic: usize,     // 在有基因的 contig 上，但不在任何基因区间内
+    no_gene_contig: usize, // 所在 contig 上没有基因（不读取该 contig，因此不检查它是否存在）
+    missing_contig: usize, // 所在 contig 上有基因，但 FASTA 中没有该 contig
+    out_of_range: usize,   // 位置为 0 或超出 contig 长度
+    filtered: usize,       // 应用前被跳过的（参考碱基不一致、坐标换算失败、--exclude-boundary、N 含量过高的 contig 等）
+}
+
+impl MutationStats {
+    fn to_json(&self) -> String {
+        format!(
+            "{{\"mutations_read\":{},\"in_gene_windows\":{},\"applied\":{},\"intergenic\":{},\"contig_without_genes\":{},\"missing_contig\":{},\"out_of_range\":{},\"filtered\":{}}}",
+            self.read,
+            self.in_genes,
+            self.applied,
+            self.intergenic,
+            self.no_gene_contig,
+            self.missing_contig,
+            self.out_of_range,
+            self.filtered
+        )
+    }
+}
+
+// 每个输出基因的摘要信息，供各类报告使用
+#[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "sqlite"), allow(dead_code))]
+struct GeneSummary {
+    gene: GeneInfo,
+    length: usize,
+    mutations_applied: usize,
+    codons_affected: usize,
+    hamming: usize, // 与参考序列不同的位置数（替换后碱基与参考相同的突变不计，不区分大小写）
+}
+
+// 每个突变输入文件的摘要：内容的 BLAKE3 摘要、大小、记录数，以及是否因内容重复而被跳过
+#[derive(Debug, Clone)]
+struct MutationInputSummary {
+    path: String,
+    content_digest: blake3::Hash,
+    size: u64,
+    records: usize,
+    duplicate_of: Option<String>,
+}
+
+// 在读取的同时计算内容的 BLAKE3 摘要（用于重复文件检测、突变索引和来源信息）和字节数，使大文件只需读一遍
+struct HashingReader<R> {
+    inner: R,
+    digest: blake3::Hasher,
+    bytes_read: u64,
+}
+
+impl<R: Read> HashingReader<R> {
+    fn new(inner: R) -> Self {
+        HashingReader { inner, digest: blake3::Hasher::new(), bytes_read: 0 }
+    }
+
+    // 把已读内容的大小和 BLAKE3 记入本次运行的输入摘要
+    fn record_input(&self, path: &str) {
+        let digest = InputDigest {
+            path: path.to_string(),
+            size: self.bytes_read,
+            blake3: self.digest.finalize().to_hex().to_string(),
+        };
+        if let Some(cli_run) = CLI_RUN.lock().unwrap().as_mut() {
+            cli_run.inputs.retain(|recorded| recorded.path != path);
+            cli_run.inputs.push(digest);
+        }
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.digest.update(&buf[..n]);
+        self.bytes_read += n as u64;
+        Ok(n)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct InputDigest {
+    path: String,
+    size: u64,
+    blake3: String,
+}
+
+// 构建时可通过环境变量 Z10_GIT_DESCRIBE 传入 git describe 的结果
+const GIT_DESCRIBE: Option<&str> = option_env!("Z10_GIT_DESCRIBE");
+
+// 在作用域结束时把经过的时间累加到共享计数器中，保证提前 return 时也会计入
+struct BusyTimer<'a> {
+    start: Instant,
+    total_nanos: &'a AtomicU64,
+}
+
+impl Drop for BusyTimer<'_> {
+    fn drop(&mut self) {
+        self.total_nanos.fetch_add(self.start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+// gene_snv_replace_files 的可选行为，由命令行参数填充
+#[derive(Debug, Default)]
+struct ReplaceOptions {
+    // 每个 contig 的坐标偏移量，在匹配突变之前加到突变位置上
+    contig_offsets: HashMap<String, i64>,
+    // 预先构建的突变索引：读取时跳过解析，写出时供后续运行复用
+    index_in: Option<String>,
+    index_out: Option<String>,
+    // 每个 contig 的 N 含量报告路径，以及 N 比例上限（超过则排除该 contig）
+    n_report: Option<String>,
+    max_n_fraction: Option<f64>,
+    // 从 SQLite 数据库读取基因位置：(数据库路径, 查询语句)，设置时忽略基因位置文件
+    positions_sqlite: Option<(String, String)>,
+    remote: RemoteOptions,
+    // 内存软上限（字节），超过时提前以明确的错误退出而不是被 OOM 杀死
+    max_memory: Option<usize>,
+    // 主输出：每种格式及其路径
+    outputs: Vec<(OutputFormat, String)>,
+    // 只对该链上的基因应用突变
+    only_strand: Option<Strand>,
+    // 输出前把非 ACGT 碱基替换为参考碱基
+    collapse_ambiguity: bool,
+    // 写出时逐条检查输出 FASTA 的一致性，失败时不生成输出文件
+    check_output: bool,
+    // 同一 gene_id 出现多次时保留哪条记录
+    dedup_genes: DedupGenes,
+    // 输入有问题时报错退出；ignore_missing_contigs 时基因引用了不存在的 contig 仍只给出警告
+    strict: bool,
+    ignore_missing_contigs: bool,
+    // 未输出的基因及原因（TSV）
+    dropped_genes: Option<String>,
+    // 单个 contig 上应用突变的方式
+    strategy: Strategy,
+    // 覆盖度轨道（bedGraph）及最低覆盖度，低于该值的位置在输出中屏蔽为 N
+    coverage: Option<(String, f64)>,
+    // 参考序列中的 '-' 为比对空位：不计入坐标，也不会被突变
+    gap_aware: bool,
+    // 杂合突变写为参考和替换碱基的 IUPAC 简并碱基
+    het_as_ambiguity: bool,
+    // 主输出每 N 条记录刷新一次（直接写最终路径）；None 时只在最后刷新
+    flush_every: Option<usize>,
+    // --liftover：注释所在组装到参考组装的 chain；liftover_mutations 时突变也需要换算
+    liftover: Option<Liftover>,
+    liftover_mutations: bool,
+    // 不应用落在基因首末碱基上的突变
+    exclude_boundary: bool,
+    // --no-revcomp-minus：主输出中负链基因也按基因组正链方向输出（默认写为反向互补，按基因方向；其他报告总是按基因组方向）
+    forward_minus: bool,
+    // fastq 中被改变位置的 Phred 质量
+    mutated_quality: u8,
+    // FASTA/masked-diff 主输出每行的字符数，0 为不折行
+    wrap: usize,
+    // 主输出中记录的顺序
+    sort_by: SortBy,
+    // --order-by-score：gene_id -> 分数，主输出按分数从高到低排列
+    gene_scores: Option<HashMap<String, f64>>,
+    // 写出后回读主输出，核对记录数和校验和
+    verify_output: bool,
+    // json/jsonl 主输出中写入来源信息（--no-provenance 时关闭）
+    provenance: bool,
+    // --variant-set：(集合名, 突变文件)，每个集合与位置参数的突变合并后各应用一次，基因ID记为 {gene}__{set}
+    variant_sets: Vec<(String, String)>,
+    // 输入文件的解析方式（--coord-base 等）
+    parse: ParseOptions,
+}
+
+// 远程 http(s) 输入的选项（需要 remote feature）
+#[derive(Debug, Default)]
+#[cfg_attr(not(feature = "remote"), allow(dead_code))]
+struct RemoteOptions {
+    // FASTA 的 .fai 索引（本地路径或远程地址），提供时只读取需要的 contig；远程 FASTA 按字节范围下载
+    contigs_fai: Option<String>,
+    // URL -> 期望的 SHA-256（十六进制），下载后校验
+    expect_sha256: HashMap<String, String>,
+    // 网络请求失败后的重试次数，每次重试前的等待时间翻倍
+    retries: u32,
+    retry_backoff: Duration,
+}
+
+// 运行进度计数器，供 --metrics-file 定期导出
+struct RunCounters {
+    genes_processed: AtomicU64,
+    mutations_applied: AtomicU64,
+    rejects_missing_contig: AtomicU64,
+    rejects_out_of_range: AtomicU64,
+    rejects_non_ascii: AtomicU64,
+    invalid_records: AtomicU64, // 无法解析而被跳过的基因位置和突变记录
+    bytes_written: AtomicU64,
+}
+
+// 命令行的计数器；库的入口每次调用各自创建一组计数器传给 replace_contigs，并发的调用互不影响
+static COUNTERS: RunCounters = RunCounters::new();
+
+impl RunCounters {
+    const fn new() -> RunCounters {
+        RunCounters {
+            genes_processed: AtomicU64::new(0),
+            mutations_applied: AtomicU64::new(0),
+            rejects_missing_contig: AtomicU64::new(0),
+            rejects_out_of_range: AtomicU64::new(0),
+            rejects_non_ascii: AtomicU64::new(0),
+            invalid_records: AtomicU64::new(0),
+            bytes_written: AtomicU64::new(0),
+        }
+    }
+
+    #[cfg_attr(not(feature = "python"), allow(dead_code))]
+    fn named(&self) -> [(&'static str, &AtomicU64); 7] {
+        [
+            ("genes_processed", &self.genes_processed),
+            ("mutations_applied", &self.mutations_applied),
+            ("rejects_missing_contig", &self.rejects_missing_contig),
+            ("rejects_out_of_range", &self.rejects_out_of_range),
+            ("rejects_non_ascii", &self.rejects_non_ascii),
+            ("invalid_records", &self.invalid_records),
+            ("bytes_written", &self.bytes_written),
+        ]
+    }
+}
+
+// 表示一条输入记录无法解析而被跳过的警告类别，计入 COUNTERS.invalid_records
+const INVALID_RECORD_KINDS: [&str; 2] = ["invalid_gene_record", "invalid_mutation"];
+
+// --events 的 JSON Lines 事件：各线程把序列化好的行发到通道，由单个写线程依次写出，行之间不会交错。
+// 事件格式变化时递增 EVENTS_VERSION（每行的 "v" 字段）
+const EVENTS_VERSION: u32 = 1;
+static EVENT_SENDER: Mutex<Option<mpsc::Sender<String>>> = Mutex::new(None);
+
+struct EventEmitter {
+    handle: thread::JoinHandle<io::Result<()>>,
+}
+
+impl EventEmitter {
+    // path 为 "-" 时写到标准错误
+    fn start(path: &str) -> io::Result<EventEmitter> {
+        let mut writer: Box<dyn Write + Send> = match path {
+            "-" => Box::new(io::stderr()),
+            _ => Box::new(BufWriter::new(File::create(path)?)),
+        };
+        let (sender, receiver) = mpsc::channel::<String>();
+        *EVENT_SENDER.lock().unwrap() = Some(sender);
+        let handle = thread::spawn(move || {
+            while let Ok(line) = receiver.recv() {
+                writeln!(writer, "{}", line)?;
+                for line in receiver.try_iter() {
+                    writeln!(writer, "{}", line)?;
+                }
+                // 通道暂时为空时刷新，消费者可以实时看到进度
+                writer.flush()?;
+            }
+            writer.flush()
+        });
+        Ok(EventEmitter { handle })
+    }
+
+    // 关闭通道并等待写线程写完剩余事件
+    fn finish(self) -> io::Result<()> {
+        EVENT_SENDER.lock().unwrap().take();
+        self.handle.join().unwrap_or_else(|_| Err(io::Error::other("event writer thread panicked")))
+    }
+}
+
+// 发出一个事件；fields 的值必须是已编码的 JSON，只有启用 --events 时才会调用 fields
+fn emit_event(event: &str, fields: impl FnOnce() -> Vec<(&'static str, String)>) {
+    let sender = EVENT_SENDER.lock().unwrap();
+    if let Some(sender) = sender.as_ref() {
+        let mut line = format!("{{\"v\":{},\"event\":{},\"ts\":{}", EVENTS_VERSION, json_string(event), unix_timestamp());
+        for (key, value) in fields() {
+            line.push_str(&format!(",{}:{}", json_string(key), value));
+        }
+        line.push('}');
+        let _ = sender.send(line);
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+// 命令行一次运行的状态，由 run_cli 安装。库的入口不安装它：库的调用不写 --warnings-json、不记录输入摘要，
+// 也不计入 COUNTERS.invalid_records
+struct CliRun {
+    // --warnings-json 的输出：每个警告一行 JSON 对象，按行刷新，运行中途失败时已写出的警告仍然完整
+    warnings: Option<io::LineWriter<File>>,
+    // --verbose：汇总过的警告在标准错误上也逐项输出
+    verbose: bool,
+    // 本次运行读取过的输入文件，同一路径只保留最后一次读取
+    inputs: Vec<InputDigest>,
+}
+
+static CLI_RUN: Mutex<Option<CliRun>> = Mutex::new(None);
+
+// 一条警告：类别（type）、可读的消息，以及相关的 contig、基因和位置（没有时为 null）。
+// 标准错误只输出消息；--warnings-json 写出全部字段
+struct Warning {
+    kind: &'static str,
+    message: String,
+    contig: Option<String>,
+    gene: Option<String>,
+    position: Option<usize>,
+}
+
+impl Warning {
+    fn new(kind: &'static str, message: String) -> Warning {
+        Warning { kind, message, contig: None, gene: None, position: None }
+    }
+
+    fn contig(mut self, contig: &str) -> Warning {
+        self.contig = Some(contig.to_string());
+        self
+    }
+
+    fn gene(mut self, gene: &str) -> Warning {
+        self.gene = Some(gene.to_string());
+        self
+    }
+
+    fn position(mut self, position: usize) -> Warning {
+        self.position = Some(position);
+        self
+    }
+
+    fn json_fields(&self) -> Vec<(&'static str, String)> {
+        let optional = |value: Option<&str>| value.map(json_string).unwrap_or_else(|| "null".to_string());
+        vec![
+            ("type", json_string(self.kind)),
+            ("message", json_string(&self.message)),
+            ("contig", optional(self.contig.as_deref())),
+            ("gene", optional(self.gene.as_deref())),
+            ("position", self.position.map(|position| position.to_string()).unwrap_or_else(|| "null".to_string())),
+        ]
+    }
+
+    // 输出到标准错误，作为 warning 事件发出，并写入 --warnings-json
+    fn emit(self) {
+        eprintln!("{}", self.message);
+        emit_event("warning", || self.json_fields());
+        self.record();
+    }
+
+    // 只写入 --warnings-json，用于标准错误上已经汇总过的逐项警告
+    fn record(&self) {
+        let mut cli_run = CLI_RUN.lock().unwrap();
+        let Some(cli_run) = cli_run.as_mut() else {
+            return;
+        };
+        if INVALID_RECORD_KINDS.contains(&self.kind) {
+            COUNTERS.invalid_records.fetch_add(1, Ordering::Relaxed);
+        }
+        if let Some(sink) = cli_run.warnings.as_mut() {
+            let fields: Vec<String> =
+                self.json_fields().into_iter().map(|(key, value)| format!("{}:{}", json_string(key), value)).collect();
+            // 写警告失败不应中断运行
+            let _ = writeln!(sink, "{{{}}}", fields.join(","));
+        }
+    }
+
+    // 标准错误和事件中输出汇总消息，--warnings-json 中写出逐项的警告（没有逐项警告时写出汇总本身）；
+    // --verbose 时逐项的警告也输出到标准错误
+    fn emit_summary(self, details: impl IntoIterator<Item = Warning>) {
+        eprintln!("{}", self.message);
+        emit_event("warning", || self.json_fields());
+        let verbose = CLI_RUN.lock().unwrap().as_ref().is_some_and(|cli_run| cli_run.verbose);
+        let mut any = false;
+        for detail in details {
+            if verbose {
+                eprintln!("  {}", detail.message);
+            }
+            detail.record();
+            any = true;
+        }
+        if !any {
+            self.record();
+        }
+    }
+}
+
+// 输出没有 contig/基因/位置上下文的警告
+fn warn(kind: &'static str, message: String) {
+    Warning::new(kind, message).emit();
+}
+
+// 记录一个阶段的耗时并发出 stage_finished 事件
+fn record_stage(timings: &mut Vec<(&'static str, Duration)>, stage: &'static str, elapsed: Duration) {
+    timings.push((stage, elapsed));
+    emit_event("stage_finished", || {
+        vec![("stage", json_string(stage)), ("duration", format!("{:.6}", elapsed.as_secs_f64()))]
+    });
+}
+
+// 运行期间请求的最大单次序列分配（字节），在 --timings 中输出
+static LARGEST_ALLOCATION: AtomicUsize = AtomicUsize::new(0);
+
+fn record_allocation(bytes: usize) {
+    LARGEST_ALLOCATION.fetch_max(bytes, Ordering::Relaxed);
+}
+
+fn largest_allocation() -> usize {
+    LARGEST_ALLOCATION.load(Ordering::Relaxed)
+}
+
+/// Error returned by [`gene_snv_replace`]. Converts into [`io::Error`] for callers that use `?` in an
+/// `io::Result` function.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum GeneReplaceError {
+    /// An input CSV could not be read.
+    CsvError(csv::Error),
+    /// An input file could not be read.
+    IoError(io::Error),
+    /// A line of an input file could not be parsed. The message names the file and line.
+    ParseError(String),
+    /// A gene refers to a contig that is not in the FASTA (only an error under `--strict`).
+    MissingContig(String),
+    /// The worker pool could not be created.
+    ThreadPool(rayon::ThreadPoolBuildError),
+}
+
+impl std::error::Error for GeneReplaceError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GeneReplaceError::CsvError(err) => Some(err),
+            GeneReplaceError::IoError(err) => Some(err),
+            GeneReplaceError::ThreadPool(err) => Some(err),
+            GeneReplaceError::ParseError(_) | GeneReplaceError::MissingContig(_) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for GeneReplaceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GeneReplaceError::CsvError(err) => write!(f, "{}", tr!("CSV 错误：{}", "CSV error: {}", err)),
+            GeneReplaceError::IoError(err) => write!(f, "{}", tr!("IO 错误：{}", "IO error: {}", err)),
+            GeneReplaceError::ParseError(message) => write!(f, "{}", tr!("解析错误：{}", "parse error: {}", message)),
+            GeneReplaceError::MissingContig(message) => write!(f, "{}", tr!("缺少 contig：{}", "missing contig: {}", message)),
+            GeneReplaceError::ThreadPool(err) => write!(f, "{}", tr!("线程池错误：{}", "thread pool error: {}", err)),
+        }
+    }
+}
+
+impl From<GeneReplaceError> for io::Error {
+    fn from(error: GeneReplaceError) -> Self {
+        match error {
+            GeneReplaceError::CsvError(csv_err) => io::Error::other(tr!("CSV 错误：{}", "CSV error: {}", csv_err)),
+            GeneReplaceError::IoError(io_err) => io_err,
+            GeneReplaceError::ParseError(message) | GeneReplaceError::MissingContig(message) => {
+                io::Error::new(io::ErrorKind::InvalidData, message)
+            }
+            GeneReplaceError::ThreadPool(err) => io::Error::other(tr!(
+                "线程池创建失败：{}",
+                "failed to create thread pool: {}",
+                err,
+            )),
+        }
+    }
+}
+
+/// Applies `mutations` to `genes` using sequences already in memory and returns the mutated gene
+/// sequences keyed by gene id.
+///
+/// `contigs` maps contig id to sequence, as returned by [`read_contigs`]. Genes on contigs that are not in
+/// `contigs` are skipped. A gene whose interval does not fit on its contig is skipped with a warning. A mutation whose
+/// expected reference (`expected_ref`, or the reference of an indel) differs from the contig is skipped with a warning,
+/// as the CLI does without `--strict`. Repeated gene ids
+/// are resolved as with `--dedup-genes first`: repeats on one contig keep the first record, and an id kept on several
+/// contigs is renamed to `gene_id_contig_start_end`. `num_threads` sets the size of the worker pool; 0 uses the rayon
+/// global pool. The result does not depend on `num_threads`. Genes on [`Strand::Minus`] are returned as the reverse
+/// complement of their mutated contig slice, reading 5'->3' along the gene, as the CLI writes them by default.
+///
+/// Each call keeps its own counters and writes no global state, so concurrent calls do not affect each other.
+pub fn gene_snv_replace(
+    contigs: &Contigs,
+    mutations: &[Mutation],
+    genes: Vec<GeneInfo>,
+    num_threads: usize,
+) -> Result<HashMap<String, String>, GeneReplaceError> {
+    let gene_positions_map = group_unique_genes(genes);
+    let pool = match num_threads {
+        0 => None,
+        num_threads => Some(ThreadPoolBuilder::new().num_threads(num_threads).build().map_err(GeneReplaceError::ThreadPool)?),
+    };
+    let mutations = skip_reference_mismatches(mutations.to_vec(), contigs);
+    let counters = RunCounters::new();
+    let replaced = replace_contigs(contigs, &gene_positions_map, &mutations, None, Strategy::default(), pool.as_ref(), &counters)?;
+    Ok(orient_minus_strand(&replaced.mutated_genes, &replaced.gene_summaries))
+}
+
+/// Parses in-memory inputs in the command-line file formats (FASTA, mutation CSV and gene-position CSV) and applies
+/// the mutations with [`gene_snv_replace`] on the calling thread.
+///
+/// Any byte input either returns sequences or an error; malformed input never panics. The fuzz target in `fuzz/`
+/// drives this function with arbitrary bytes.
+pub fn gene_snv_replace_bytes(
+    contigs_fasta: &[u8],
+    mutations_csv: &[u8],
+    positions_csv: &[u8],
+) -> Result<HashMap<String, String>, GeneReplaceError> {
+    let options = ParseOptions::default();
+    let (contigs, _) = read_contigs_from(contigs_fasta, None).map_err(GeneReplaceError::IoError)?;
+    let mutations = read_mutations_from(mutations_csv, &options).map_err(GeneReplaceError::IoError)?;
+    let (genes, _) = read_gene_positions_from(positions_csv, &options).map_err(GeneReplaceError::IoError)?;
+    gene_snv_replace(&contigs, &mutations, genes, 0)
+}
+
+// 命令行使用的入口：从文件读取输入，按 options 过滤、校验，再调用 replace_contigs。
+// 写主输出之前填写 run_info 的结束时间和输入摘要
+fn gene_snv_replace_files(
+    contigs_file: &str,
+    mutations_files: &[&str],
+    gene_positions_file: &str,
+    num_threads: usize,
+    options: &ReplaceOptions,
+    run_info: &mut RunInfo,
+) -> io::Result<ReplaceResult> {
+    let mut timings: Vec<(&'static str, Duration)> = Vec::new();
+
+    // 读取基因位置信息
+    let stage_start = Instant::now();
+    let stage_span = info_span!("stage", stage = "read_gene_positions").entered();
+    let LoadedGenes { mut gene_positions_map, input_order, unlifted_genes, invalid_interval_genes } =
+        load_genes(gene_positions_file, options)?;
+    record_stage(&mut timings, "read_gene_positions", stage_start.elapsed());
+    stage_span.exit();
+
+    // 读取 contigs 和 mutations；远程 FASTA 提供 .fai 索引时只按范围请求基因所在的 contig
+    let stage_start = Instant::now();
+    let stage_span = info_span!("stage", stage = "read_contigs").entered();
+    let wanted: HashSet<&str> = gene_positions_map.keys().map(|c| c.as_str()).collect();
+    let sequence_source = open_sequence_source(contigs_file, options)?;
+    let (mut contigs, n_counts) = sequence_source.read_contigs(Some(&wanted), options.max_memory)?;
+    if let Some(n_report_file) = &options.n_report {
+        write_n_content_report(n_report_file, &contigs, &n_counts, options.max_n_fraction)?;
+    }
+    // 排除 N 含量过高的 contig，它们上的基因也一并跳过
+    let excluded_contigs = match options.max_n_fraction {
+        Some(max_n_fraction) => exclude_high_n_contigs(&mut contigs, &n_counts, max_n_fraction),
+        None => BTreeSet::new(),
+    };
+    // 校验：在任何序列处理之前统计基因引用了但 FASTA 中没有的 contig，以及反过来没有被引用的 contig
+    let missing_contigs: BTreeSet<String> = gene_positions_map
+        .keys()
+        .filter(|contig_id| !contigs.contains_key(*contig_id) && !excluded_contigs.contains(*contig_id))
+        .cloned()
+        .collect();
+    report_missing_contigs(&gene_positions_map, &contigs, &missing_contigs);
+    // 区间不在 contig 范围内的基因：--strict 时报错，否则由 replace_contigs 逐个警告后跳过
+    let mut out_of_range_genes: Vec<GeneInfo> = gene_positions_map
+        .iter()
+        .filter_map(|(contig_id, genes)| contigs.get(contig_id).map(|sequence| (genes, sequence.len())))
+        .flat_map(|(genes, length)| genes.iter().filter(move |gene_info| !gene_fits_contig(gene_info, length)))
+        .cloned()
+        .collect();
+    out_of_range_genes.sort_by(|a, b| a.gene_id.cmp(&b.gene_id));
+    if let Some(dropped_genes_file) = &options.dropped_genes {
+        write_dropped_genes(
+            dropped_genes_file,
+            &gene_positions_map,
+            &missing_contigs,
+            &excluded_contigs,
+            &unlifted_genes,
+            &invalid_interval_genes,
+            &out_of_range_genes,
+        )?;
+    }
+    if options.strict {
+        check_strict_genes(options, &invalid_interval_genes, &out_of_range_genes, &missing_contigs, &contigs)?;
+    }
+    gene_positions_map.retain(|contig_id, _| !excluded_contigs.contains(contig_id));
+    for (contig_id, sequence) in &contigs {
+        emit_event("contig_loaded", || vec![("contig", json_string(contig_id)), ("length", sequence.len().to_string())]);
+    }
+    record_stage(&mut timings, "read_contigs", stage_start.elapsed());
+    stage_span.exit();
+    let stage_start = Instant::now();
+    let stage_span = info_span!("stage", stage = "read_mutations").entered();
+    let (mut mutation_sets, mutation_inputs) = read_mutation_sets(mutations_files, options)?;
+    let mutations_read: usize = mutation_sets.iter().map(|(_, mutations)| mutations.len()).sum();
+    let mut rejected_mutations = vec![];
+    prepare_mutation_sets(&mut mutation_sets, options, &contigs, &mut gene_positions_map, &mut rejected_mutations)?;
+    record_stage(&mut timings, "read_mutations", stage_start.elapsed());
+    stage_span.exit();
+
+    // 预检：基因文件和突变文件涉及的 contig 集合是否一致
+    report_contig_set_differences(&gene_positions_map, mutation_sets.iter().flat_map(|(_, mutations)| mutations));
+
+    if let Some(limit) = options.max_memory {
+        check_memory_limit(limit, &contigs, &mutation_sets, num_threads.min(gene_positions_map.len()).max(1))?;
+    }
+
+    // 创建线程池
+    let pool = ThreadPoolBuilder::new().num_threads(num_threads).build().map_err(GeneReplaceError::ThreadPool)?;
+
+    // 并行处理突变：每个任务负责一个 contig，只复制一次该 contig 到字节缓冲区并在其上原地修改，
+    // 然后从缓冲区中截取该 contig 上的所有基因，任务结束时释放缓冲区，因此单个 contig 的临时内存约为其长度的 1 倍。
+    // 同时存在的缓冲区不超过活动线程数，峰值约为：全部 contig + min(线程数, contig 数) × 最长 contig + 输出的基因序列
+    // （与 --max-memory 的估算一致）
+    let work_items = gene_positions_map.len();
+    if num_threads > work_items {
+        eprintln!(
+            "{}",
+            tr!(
+                "[info] 线程数 {} 超过可并行的工作项数 {}（每个 contig 一个工作项），多余的线程将处于空闲状态",
+                "[info] {} threads exceed the {} parallel work items (one per contig); the extra threads will be idle",
+                num_threads,
+                work_items,
+            )
+        );
+    }
+    let replace_start = Instant::now();
+    let stage_span = info_span!("stage", stage = "replace").entered();
+    let (replaced, masked_diffs) =
+        replace_mutation_sets(&contigs, &gene_positions_map, &mutation_sets, options, &pool, &excluded_contigs, &mut rejected_mutations)?;
+    let mut mutation_stats = classify_mutations(
+        mutation_sets.iter().flat_map(|(_, mutations)| mutations),
+        &gene_positions_map,
+        &contigs,
+        &excluded_contigs,
+    );
+    mutation_stats.read = mutations_read;
+    mutation_stats.applied = COUNTERS.mutations_applied.load(Ordering::Relaxed) as usize;
+    mutation_stats.filtered = mutations_read
+        - mutation_stats.in_genes
+        - mutation_stats.intergenic
+        - mutation_stats.no_gene_contig
+        - mutation_stats.missing_contig
+        - mutation_stats.out_of_range;
+    let replace_elapsed = replace_start.elapsed();
+    record_stage(&mut timings, "replace", replace_elapsed);
+    stage_span.exit();
+    let average_parallelism = if replace_elapsed.is_zero() {
+        0.0
+    } else {
+        replaced.busy_time.as_secs_f64() / replace_elapsed.as_secs_f64()
+    };
+
+    // 单个分配不应超过最长的 contig（每个 contig 只复制一次）
+    debug_assert!(largest_allocation() <= contigs.values().map(|c| c.len()).max().unwrap_or(0));
+
+    let mut mutated_genes_result = replaced.mutated_genes;
+    mask_output_sequences(&mut mutated_genes_result, &replaced.gene_summaries, &contigs, options)?;
+
+    // 负链基因（除非 --no-revcomp-minus）：主输出使用按基因方向的副本，masked-diff（以及由它得到的 fastq 质量）同样取反向互补，
+    // 返回给调用方、供其他报告使用的序列仍按基因组方向
+    let oriented = (!options.forward_minus).then(|| {
+        let orient = |sequences: &HashMap<String, String>| orient_minus_strand(sequences, &replaced.gene_summaries);
+        (orient(&mutated_genes_result), masked_diffs.as_ref().map(orient))
+    });
+    let (output_genes, output_masked_diffs) = match &oriented {
+        Some((genes, masked)) => (genes, masked.as_ref()),
+        None => (&mutated_genes_result, masked_diffs.as_ref()),
+    };
+
+    // 在这里我们修改代码，将突变后的基因序列写入到文件中（创建新文件）；计算只做一次，每种格式各写一个文件
+    let write_start = Instant::now();
+    let stage_span = info_span!("stage", stage = "write_output").entered();
+    let sorted_summaries = sort_output_summaries(&replaced.gene_summaries, options, &input_order);
+    let output_summaries = sorted_summaries.as_deref().unwrap_or(&replaced.gene_summaries);
+    run_info.finished_at = unix_timestamp();
+    run_info.inputs = CLI_RUN.lock().unwrap().as_ref().map(|cli_run| cli_run.inputs.clone()).unwrap_or_default();
+    let provenance = options.provenance.then_some(&*run_info);
+    write_main_outputs(options, output_genes, output_masked_diffs, output_summaries, provenance)?;
+    record_stage(&mut timings, "write_output", write_start.elapsed());
+    stage_span.exit();
+
+    Ok(ReplaceResult {
+        mutated_genes: mutated_genes_result,
+        applied_mutations: replaced.applied_mutations,
+        rejected_mutations,
+        timings,
+        work_items,
+        average_parallelism,
+        mutation_inputs,
+        gene_summaries: replaced.gene_summaries,
+        mutation_stats,
+    })
+}
+
+// gene_snv_replace_files 读取基因阶段的结果
+struct LoadedGenes {
+    gene_positions_map: HashMap<String, Vec<GeneInfo>>,
+    // --sort-by input：基因在位置文件中的顺序；--variant-set 改名后的基因按 source_id 找到原来的位置
+    input_order: HashMap<String, usize>,
+    // --liftover 无法换算而跳过的基因
+    unlifted_genes: Vec<GeneInfo>,
+    // 区间无效的基因行，已在解析时提示，保留下来供 --dropped-genes 和 --strict 使用
+    invalid_interval_genes: Vec<GeneInfo>,
+}
+
+// 读取基因位置，按 --dedup-genes 去重，按 --liftover 换算坐标，再按 contig 分组
+fn load_genes(gene_positions_file: &str, options: &ReplaceOptions) -> io::Result<LoadedGenes> {
+    let (genes, invalid_interval_genes) = match &options.positions_sqlite {
+        Some((database, query)) => read_gene_positions_sqlite(database, query, &options.parse)?,
+        None => read_gene_positions_checked(gene_positions_file, &options.parse)?,
+    };
+    let (genes, duplicates, split) = dedup_genes(genes, options.dedup_genes);
+    report_dedup_genes(options.dedup_genes, duplicates, split);
+    // --liftover：把基因坐标从注释所在的组装换算到参考组装，无法换算的基因跳过并报告
+    let mut unlifted_genes = vec![];
+    let genes = match &options.liftover {
+        Some(liftover) => {
+            let (lifted, unlifted) = lift_genes(genes, liftover);
+            warn_unlifted_genes(&unlifted);
+            unlifted_genes = unlifted;
+            lifted
+        }
+        None => genes,
+    };
+    let mut input_order: HashMap<String, usize> = HashMap::new();
+    if options.sort_by == SortBy::Input {
+        for (index, gene_info) in genes.iter().enumerate() {
+            input_order.insert(gene_info.gene_id.clone(), index);
+            if let Some(source_id) = &gene_info.source_id {
+                input_order.entry(source_id.clone()).or_insert(index);
+            }
+        }
+    }
+    let gene_positions_map = group_genes_by_contig(genes);
+    if options.only_strand.is_some() {
+        let unknown = gene_positions_map.values().flatten().filter(|gene_info| gene_info.strand == Strand::Unknown).count();
+        if unknown > 0 {
+            warn("unknown_strand", tr!(
+                "警告：{} 个基因没有链信息，--only-strand 下将输出其参考序列",
+                "warning: {} genes have no strand; --only-strand outputs their reference sequence",
+                unknown,
+            ));
+        }
+    }
+    Ok(LoadedGenes { gene_positions_map, input_order, unlifted_genes, invalid_interval_genes })
+}
+
+// 报告 --dedup-genes 改名或去除的记录数
+fn report_dedup_genes(policy: DedupGenes, duplicates: usize, split: usize) {
+    if split > 0 {
+        println!(
+            "{}",
+            tr!(
+                "--dedup-genes：{} 条记录的 gene_id 出现在多个 contig 上，已按位置改名为 gene_id_contig_start_end",
+                "--dedup-genes: {} records share a gene_id across contigs and were renamed to gene_id_contig_start_end",
+                split,
+            )
+        );
+    }
+    if duplicates == 0 {
+        return;
+    }
+    match policy {
+        DedupGenes::First | DedupGenes::Longest => warn(
+            "duplicate_gene_id",
+            tr!(
+                "警告：{} 条在同一 contig 上重复 gene_id 的记录已去除（--dedup-genes locus 可按位置全部输出）",
+                "warning: removed {} records that repeat a gene_id on the same contig (--dedup-genes locus keeps all of them by position)",
+                duplicates,
+            ),
+        ),
+        DedupGenes::None => println!(
+            "{}",
+            tr!(
+                "--dedup-genes：{} 条重复 gene_id 的记录已改名保留",
+                "--dedup-genes: renamed and kept {} records with duplicate gene_id",
+                duplicates,
+            )
+        ),
+        DedupGenes::Locus => {
+            println!(
+                "{}",
+                tr!(
+                    "--dedup-genes：{} 条重复 gene_id 的记录已按位置改名（位置也相同的只保留一条）",
+                    "--dedup-genes: renamed {} records with duplicate gene_id by position (records at the same position are kept once)",
+                    duplicates,
+                )
+            )
+        }
+    }
+}
+
+// --max-n-fraction：从 contigs 中去掉 N 比例超过上限的 contig 并汇总报告，返回被排除的 contig
+fn exclude_high_n_contigs(
+    contigs: &mut Contigs,
+    n_counts: &HashMap<String, usize>,
+    max_n_fraction: f64,
+) -> BTreeSet<String> {
+    let mut excluded_contigs: BTreeSet<String> = BTreeSet::new();
+    contigs.retain(|contig_id, sequence| {
+        let n_count = n_counts.get(contig_id).copied().unwrap_or(0);
+        let keep = n_fraction(n_count, sequence.len()) <= max_n_fraction;
+        if !keep {
+            excluded_contigs.insert(contig_id.clone());
+        }
+        keep
+    });
+    if !excluded_contigs.is_empty() {
+        let message = tr!(
+            "排除 {} 个 N 含量超过 {:.2}% 的 contig",
+            "excluded {} contigs with more than {:.2}% N",
+            excluded_contigs.len(),
+            max_n_fraction * 100.0,
+        );
+        Warning::new("high_n_content", message).emit_summary(excluded_contigs.iter().map(|contig_id| {
+            Warning::new("high_n_content", tr!(
+                "排除 N 含量超过 {:.2}% 的 contig {}",
+                "excluded contig {1} with more than {0:.2}% N",
+                max_n_fraction * 100.0,
+                contig_id,
+            ))
+                .contig(contig_id)
+        }));
+    }
+    excluded_contigs
+}
+
+// --strict：区间无效的基因行、区间超出 contig 的基因和（没有 --ignore-missing-contigs 时）缺失的 contig 都是错误
+fn check_strict_genes(
+    options: &ReplaceOptions,
+    invalid_interval_genes: &[GeneInfo],
+    out_of_range_genes: &[GeneInfo],
+    missing_contigs: &BTreeSet<String>,
+    contigs: &Contigs,
+) -> io::Result<()> {
+    if !invalid_interval_genes.is_empty() {
+        let listed: Vec<String> = invalid_interval_genes
+            .iter()
+            .take(20)
+            .map(|gene_info| {
+                format!("{} ({}:{}-{})", gene_info.gene_id, gene_info.contig_id, gene_info.start_position, gene_info.end_position)
+            })
+            .collect();
+        let more = if invalid_interval_genes.len() > listed.len() { ", ..." } else { "" };
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            tr!(
+                "--strict：{} 个基因行的区间无效（起点为 0 或起点大于终点）：{}{}",
+                "--strict: {} gene rows have an invalid interval (start of 0 or start after end): {}{}",
+                invalid_interval_genes.len(),
+                listed.join(", "),
+                more,
+            ),
+        ));
+    }
+    if !out_of_range_genes.is_empty() {
+        let listed: Vec<String> = out_of_range_genes
+            .iter()
+            .take(20)
+            .map(|gene_info| {
+                format!(
+                    "{} ({}:{}-{}, {})",
+                    gene_info.gene_id,
+                    gene_info.contig_id,
+                    gene_info.start_position,
+                    gene_info.end_position,
+                    contigs[&gene_info.contig_id].len()
+                )
+            })
+            .collect();
+        let more = if out_of_range_genes.len() > listed.len() { ", ..." } else { "" };
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            tr!(
+                "--strict：{} 个基因的区间不在所在 contig 的范围内（基因 (contig:起点-终点, contig 长度)）：{}{}",
+                "--strict: {} genes have intervals outside their contig (gene (contig:start-end, contig length)): {}{}",
+                out_of_range_genes.len(),
+                listed.join(", "),
+                more,
+            ),
+        ));
+    }
+    if !options.ignore_missing_contigs && !missing_contigs.is_empty() {
+        return Err(GeneReplaceError::MissingContig(tr!(
+            "--strict：{} 个 contig 被基因引用但不在 FASTA 中（可用 --ignore-missing-contigs 忽略）",
+            "--strict: {} contigs are referenced by genes but missing from the FASTA (use --ignore-missing-contigs to ignore)",
+            missing_contigs.len()
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+// 一组一起应用的突变：(--variant-set 集合名, 突变)；没有 --variant-set 时只有一组，集合名为 None
+type MutationSet<'a> = (Option<&'a str>, Vec<Mutation>);
+
+// 读取突变文件（或 --index-in 的索引）和各 --variant-set，返回突变集合和各输入文件的摘要
+fn read_mutation_sets<'a>(
+    mutations_files: &[&str],
+    options: &'a ReplaceOptions,
+) -> io::Result<(Vec<MutationSet<'a>>, Vec<MutationInputSummary>)> {
+    let loaded_index = match &options.index_in {
+        Some(index_file) => load_mutation_index(index_file, mutations_files, &options.parse)?,
+        None => None,
+    };
+    let (mutations, mut mutation_inputs) = match loaded_index {
+        Some(loaded) => loaded,
+        None => read_mutation_files(mutations_files, &options.parse)?,
+    };
+    if let Some(index_file) = &options.index_out {
+        save_mutation_index(index_file, &mutations, &mutation_inputs, &options.parse)?;
+    }
+    // --variant-set：每个集合单独读取，与上面的突变合并后各应用一次；集合中的突变排在前面，同一位点以集合中的为准
+    let mut mutation_sets: Vec<MutationSet> = vec![];
+    for (set_name, set_file) in &options.variant_sets {
+        let (mut set_mutations, set_inputs) = read_mutation_files(&[set_file.as_str()], &options.parse)?;
+        set_mutations.extend(mutations.iter().cloned());
+        mutation_inputs.extend(set_inputs);
+        mutation_sets.push((Some(set_name.as_str()), set_mutations));
+    }
+    if mutation_sets.is_empty() {
+        mutation_sets.push((None, mutations));
+    }
+    // 插入/缺失改变基因长度，按参考坐标逐位对应的输出和处理无法使用
+    if mutation_sets.iter().flat_map(|(_, mutations)| mutations).any(|mutation| mutation.indel.is_some()) {
+        let mut position_aligned: Vec<&str> = options
+            .outputs
+            .iter()
+            .filter_map(|(format, _)| match format {
+                OutputFormat::MaskedDiff => Some("--format masked-diff"),
+                OutputFormat::Fastq => Some("--format fastq"),
+                _ => None,
+            })
+            .collect();
+        if options.collapse_ambiguity {
+            position_aligned.push("--collapse-ambiguity");
+        }
+        if options.coverage.is_some() {
+            position_aligned.push("--coverage");
+        }
+        if let Some(option) = position_aligned.first() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                tr!(
+                    "突变中含有插入/缺失，不能与按参考坐标逐位对应的 {} 同时使用",
+                    "the mutations include insertions/deletions, which cannot be combined with the position-aligned {}",
+                    option,
+                ),
+            ));
+        }
+    }
+    Ok((mutation_sets, mutation_inputs))
+}
+
+// 应用前的突变预处理，依次为：--liftover-mutations 坐标换算、--contig-offset 偏移、--gap-aware 空位换算、
+// 参考碱基核对、--het-as-ambiguity 和 --exclude-boundary
+// 各步去掉的突变及原因加入 rejected
+fn prepare_mutation_sets(
+    mutation_sets: &mut [MutationSet],
+    options: &ReplaceOptions,
+    contigs: &Contigs,
+    gene_positions_map: &mut HashMap<String, Vec<GeneInfo>>,
+    rejected: &mut Vec<RejectedMutation>,
+) -> io::Result<()> {
+    let mut reject = |mutations: Vec<Mutation>, reason: &'static str| {
+        rejected.extend(mutations.into_iter().map(|mutation| RejectedMutation { mutation, reason }));
+    };
+    if let Some(liftover) = options.liftover.as_ref().filter(|_| options.liftover_mutations) {
+        let mut unlifted = 0;
+        for (_, mutations) in mutation_sets.iter_mut() {
+            let (lifted, failed) = lift_mutations(std::mem::take(mutations), liftover);
+            *mutations = lifted;
+            unlifted += failed.len();
+            reject(failed, "liftover_failed");
+        }
+        warn_unlifted_mutations(unlifted);
+    }
+    if !options.contig_offsets.is_empty() {
+        for (_, mutations) in mutation_sets.iter_mut() {
+            let (kept, dropped) = apply_contig_offsets(std::mem::take(mutations), &options.contig_offsets, |contig_id| {
+                contigs.get(contig_id).map(Vec::len)
+            });
+            *mutations = kept;
+            reject(dropped, "offset_out_of_range");
+        }
+    }
+    if options.gap_aware {
+        let all_mutations = mutation_sets.iter_mut().flat_map(|(_, mutations)| mutations.iter_mut());
+        let gapped = apply_gap_aware(gene_positions_map, all_mutations, contigs);
+        if gapped > 0 {
+            println!(
+                "{}",
+                tr!(
+                    "--gap-aware：{} 个 contig 含有空位 '-'，坐标按不含空位的碱基计数",
+                    "--gap-aware: {} contigs contain gap characters '-'; coordinates count only non-gap bases",
+                    gapped,
+                )
+            );
+        }
+    }
+    let mismatched = drop_reference_mismatches(mutation_sets, contigs, options.strict)?;
+    let mismatches = mismatched.len();
+    reject(mismatched, "ref_mismatch");
+    if mismatches > 0 {
+        println!(
+            "{}",
+            tr!(
+                "参考碱基核对：{} 个突变与 contig 上的碱基不一致，已跳过",
+                "reference check: {} mutations do not match the contig base and were skipped",
+                mismatches,
+            )
+        );
+    }
+    if options.het_as_ambiguity {
+        let converted: usize = mutation_sets.iter_mut().map(|(_, mutations)| apply_het_ambiguity(mutations, contigs)).sum();
+        println!(
+            "{}",
+            tr!(
+                "--het-as-ambiguity：{} 个杂合突变写为 IUPAC 简并碱基",
+                "--het-as-ambiguity: {} heterozygous mutations written as IUPAC ambiguity codes",
+                converted,
+            )
+        );
+    }
+    if options.exclude_boundary {
+        let mut excluded = 0;
+        for (_, mutations) in mutation_sets.iter_mut() {
+            let (kept, dropped) = exclude_boundary_mutations(std::mem::take(mutations), gene_positions_map, options.only_strand);
+            *mutations = kept;
+            excluded += dropped.len();
+            reject(dropped, "gene_boundary");
+        }
+        println!(
+            "{}",
+            tr!(
+                "--exclude-boundary：{} 个落在基因首末碱基上的突变未应用",
+                "--exclude-boundary: {} mutations on the first or last base of a gene were not applied",
+                excluded,
+            )
+        );
+    }
+    Ok(())
+}
+
+// 第6列给出期望的参考碱基时核对（在坐标换算之后）；strict 时不一致是错误，否则警告并跳过该突变。
+// 各 --variant-set 共享的突变只报告一次。返回跳过的突变（共享的突变只返回一次）
+fn drop_reference_mismatches(mutation_sets: &mut [MutationSet], contigs: &Contigs, strict: bool) -> io::Result<Vec<Mutation>> {
+    let mut reported_mismatches: HashSet<(String, usize)> = HashSet::new();
+    let mut mismatches = vec![];
+    for (_, mutations) in mutation_sets.iter_mut() {
+        let (kept, mismatched) = check_reference_alleles(std::mem::take(mutations), contigs);
+        *mutations = kept;
+        for (mutation, found) in mismatched {
+            let expected = expected_reference(&mutation).unwrap_or_default();
+            let message = tr!(
+                "突变 {} 的参考碱基不一致：{}:{} 期望 {}，实际为 {}",
+                "reference base mismatch for mutation {}: {}:{} expected {}, found {}",
+                mutation.id_or_dot(), mutation.contig_id, mutation.position, expected, found
+            );
+            if strict {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, tr!("--strict：{}", "--strict: {}", message)));
+            }
+            if reported_mismatches.insert((mutation.contig_id.clone(), mutation.position)) {
+                Warning::new("ref_mismatch", tr!("警告：{}，已跳过", "warning: {}; skipped", message))
+                    .contig(&mutation.contig_id)
+                    .position(mutation.position)
+                    .emit();
+                mismatches.push(mutation);
+            }
+        }
+    }
+    Ok(mismatches)
+}
+
+// 库的入口（gene_snv_replace、C API 和 wasm）的参考碱基核对：与命令行不带 --strict 时相同，不一致的突变警告后跳过
+fn skip_reference_mismatches(mutations: Vec<Mutation>, contigs: &Contigs) -> Vec<Mutation> {
+    let mut mutation_sets = [(None, mutations)];
+    // strict 为 false 时不会返回错误
+    let _mismatched = drop_reference_mismatches(&mut mutation_sets, contigs, false);
+    let [(_, mutations)] = mutation_sets;
+    mutations
+}
+
+// 估算峰值内存：已加载的 contig 和突变，加上并行阶段每个活动线程各一份最大 contig 的缓冲区
+fn check_memory_limit(
+    limit: usize,
+    contigs: &Contigs,
+    mutation_sets: &[MutationSet],
+    active_threads: usize,
+) -> io::Result<()> {
+    let contig_bytes: usize = contigs.values().map(|c| c.len()).sum();
+    let mutation_bytes: usize = mutation_sets
+        .iter()
+        .flat_map(|(_, mutations)| mutations)
+        .map(|m| std::mem::size_of::<Mutation>() + m.contig_id.len() + m.mutation_id.as_ref().map_or(0, |id| id.len()))
+        .sum();
+    let largest_contig = contigs.values().map(|c| c.len()).max().unwrap_or(0);
+    let estimated = contig_bytes + mutation_bytes + largest_contig * active_threads;
+    if estimated > limit {
+        return Err(memory_limit_error(&tr!("处理突变", "processing mutations"), estimated, limit));
+    }
+    Ok(())
+}
+
+// 每个突变集合应用一次；有 --variant-set 时结果中的基因ID改为 {gene}__{set}，masked-diff 也按集合分别计算。
+// 主输出需要 masked-diff 或 fastq（其质量行由 masked-diff 得到）时同时返回 masked-diff 序列
+fn replace_mutation_sets(
+    contigs: &Contigs,
+    gene_positions_map: &HashMap<String, Vec<GeneInfo>>,
+    mutation_sets: &[MutationSet],
+    options: &ReplaceOptions,
+    pool: &rayon::ThreadPool,
+    excluded_contigs: &BTreeSet<String>,
+    rejected: &mut Vec<RejectedMutation>,
+) -> io::Result<(ReplacedContigs, Option<HashMap<String, String>>)> {
+    let wants_masked_diff =
+        options.outputs.iter().any(|(format, _)| matches!(format, OutputFormat::MaskedDiff | OutputFormat::Fastq));
+    let mut masked_diffs = wants_masked_diff.then(HashMap::new);
+    let mut replaced = ReplacedContigs::default();
+    for (set_name, mutations) in mutation_sets {
+        let mut set_replaced = replace_contigs(contigs, gene_positions_map, mutations, options.only_strand, options.strategy, Some(pool), &COUNTERS)?;
+        rejected.extend(unapplied_mutations(
+            mutations,
+            &set_replaced.applied_mutations,
+            gene_positions_map,
+            contigs,
+            excluded_contigs,
+            options.only_strand,
+        ));
+        if let Some(set_name) = set_name {
+            label_variant_set(&mut set_replaced, set_name);
+        }
+        if let Some(masked_diffs) = masked_diffs.as_mut() {
+            masked_diffs.extend(masked_diff_sequences(&set_replaced.gene_summaries, &set_replaced.applied_mutations));
+        }
+        replaced.mutated_genes.extend(set_replaced.mutated_genes);
+        replaced.applied_mutations.extend(set_replaced.applied_mutations);
+        replaced.gene_summaries.extend(set_replaced.gene_summaries);
+        replaced.busy_time += set_replaced.busy_time;
+    }
+    replaced.gene_summaries.sort_by(|a, b| a.gene.gene_id.cmp(&b.gene.gene_id));
+    Ok((replaced, masked_diffs))
+}
+
+// --collapse-ambiguity 和 --coverage：在突变后的序列上把非 ACGT 位点换回参考碱基、把低覆盖度的位置屏蔽为 N
+fn mask_output_sequences(
+    mutated_genes: &mut HashMap<String, String>,
+    gene_summaries: &[GeneSummary],
+    contigs: &Contigs,
+    options: &ReplaceOptions,
+) -> io::Result<()> {
+    if options.collapse_ambiguity {
+        let (collapsed, remaining) = collapse_ambiguity(mutated_genes, gene_summaries, contigs);
+        println!(
+            "{}",
+            tr!(
+                "--collapse-ambiguity：{} 个非 ACGT 位点替换为参考碱基",
+                "--collapse-ambiguity: replaced {} non-ACGT positions with the reference base",
+                collapsed,
+            )
+        );
+        if remaining > 0 {
+            warn("ambiguous_reference", tr!(
+                "警告：{} 个非 ACGT 位点的参考碱基也不是 ACGT，保持不变",
+                "warning: {} non-ACGT positions have a non-ACGT reference base as well and were left unchanged",
+                remaining,
+            ));
+        }
+    }
+    if let Some((coverage_file, min_coverage)) = &options.coverage {
+        let coverage = read_coverage_intervals(coverage_file, *min_coverage)?;
+        let masked = mask_low_coverage(mutated_genes, gene_summaries, &coverage);
+        println!(
+            "{}",
+            tr!(
+                "--coverage：{} 个覆盖度低于 {} 的位置屏蔽为 N",
+                "--coverage: masked {} positions with coverage below {} as N",
+                masked,
+                min_coverage,
+            )
+        );
+    }
+    Ok(())
+}
+
+// 负链基因的序列取反向互补（按基因 5'->3' 方向），其他基因原样复制
+fn orient_minus_strand(sequences: &HashMap<String, String>, gene_summaries: &[GeneSummary]) -> HashMap<String, String> {
+    let minus: HashSet<&str> = gene_summaries
+        .iter()
+        .filter(|summary| summary.gene.strand == Strand::Minus)
+        .map(|summary| summary.gene.gene_id.as_str())
+        .collect();
+    sequences
+        .iter()
+        .map(|(gene_id, sequence)| {
+            let sequence = if minus.contains(gene_id.as_str()) { reverse_complement(sequence) } else { sequence.clone() };
+            (gene_id.clone(), sequence)
+        })
+        .collect()
+}
+
+// 主输出的记录按 --sort-by 排列（gene_summaries 已按基因ID排序，这时返回 None）；--order-by-score 时按分数从高到低。
+// 其他报告仍按基因ID
+fn sort_output_summaries(
+    gene_summaries: &[GeneSummary],
+    options: &ReplaceOptions,
+    input_order: &HashMap<String, usize>,
+) -> Option<Vec<GeneSummary>> {
+    match (&options.gene_scores, options.sort_by) {
+        (Some(scores), _) => Some(order_by_score(gene_summaries, scores)),
+        (None, SortBy::Position) => {
+            let mut sorted = gene_summaries.to_vec();
+            sorted.sort_by(|a, b| {
+                let key = |summary: &GeneSummary| (summary.gene.contig_id.clone(), summary.gene.start_position, summary.gene.end_position);
+                key(a).cmp(&key(b)).then_with(|| a.gene.gene_id.cmp(&b.gene.gene_id))
+            });
+            Some(sorted)
+        }
+        (None, SortBy::Input) => {
+            let rank = |summary: &GeneSummary| {
+                let gene = &summary.gene;
+                input_order.get(&gene.gene_id).or_else(|| gene.source_id.as_ref().and_then(|id| input_order.get(id))).copied()
+            };
+            let mut sorted = gene_summaries.to_vec();
+            sorted.sort_by(|a, b| rank(a).cmp(&rank(b)).then_with(|| a.gene.gene_id.cmp(&b.gene.gene_id)));
+            Some(sorted)
+        }
+        (None, SortBy::Gene) => None,
+    }
+}
+
+// 按 options.outputs 写出每种格式的主输出，--verify-output 时逐个回读校验
+fn write_main_outputs(
+    options: &ReplaceOptions,
+    output_genes: &HashMap<String, String>,
+    output_masked_diffs: Option<&HashMap<String, String>>,
+    output_summaries: &[GeneSummary],
+    provenance: Option<&RunInfo>,
+) -> io::Result<()> {
+    let settings = OutputSettings {
+        check_output: options.check_output,
+        flush_every: options.flush_every,
+        mutated_quality: options.mutated_quality,
+        wrap: options.wrap,
+    };
+    for (format, filename) in &options.outputs {
+        let records = match format {
+            OutputFormat::MaskedDiff => output_masked_diffs.unwrap_or(output_genes),
+            _ => output_genes,
+        };
+        let written = write_output(filename, *format, records, output_masked_diffs, output_summaries, settings, provenance)?;
+        if options.verify_output {
+            verify_output(filename, *format, &written)?;
+            println!(
+                "{}",
+                tr!(
+                    "--verify-output：{} 校验通过（{} 条记录，{} 字节）",
+                    "--verify-output: {} verified ({} records, {} bytes)",
+                    filename,
+                    written.records,
+                    written.bytes,
+                )
+            );
+        }
+    }
+    Ok(())
+}
+
+// 交给 replace_contigs 却没有应用的突变及原因。同一位点的第一个突变已应用时不算；
+// 被 --max-n-fraction 排除的 contig 已从 contigs 和 gene_positions_map 中去掉，需要单独传入
+fn unapplied_mutations(
+    mutations: &[Mutation],
+    applied_mutations: &[AppliedMutation],
+    gene_positions_map: &HashMap<String, Vec<GeneInfo>>,
+    contigs: &Contigs,
+    excluded_contigs: &BTreeSet<String>,
+    only_strand: Option<Strand>,
+) -> Vec<RejectedMutation> {
+    let applied_positions: HashSet<(&str, usize)> =
+        applied_mutations.iter().map(|applied| (applied.mutation.contig_id.as_str(), applied.mutation.position)).collect();
+    let mut seen: HashSet<(&str, usize)> = HashSet::new();
+    let mut rejected = vec![];
+    for mutation in mutations {
+        let key = (mutation.contig_id.as_str(), mutation.position);
+        let first = seen.insert(key);
+        if first && applied_positions.contains(&key) {
+            continue;
+        }
+        let genes = gene_positions_map.get(&mutation.contig_id);
+        let covering_genes = || {
+            genes.into_iter().flatten().filter(|gene_info| {
+                gene_info.start_position <= mutation.position && mutation.position <= gene_info.end_position
+            })
+        };
+        let reason = if excluded_contigs.contains(&mutation.contig_id) {
+            "high_n_fraction"
+        } else if genes.is_none() {
+            "outside_genes"
+        } else {
+            match contigs.get(&mutation.contig_id) {
+                None => "missing_contig",
+                Some(sequence) if mutation.position == 0 || mutation.position > sequence.len() => "out_of_range",
+                Some(_) if !first => "duplicate_position",
+                Some(_) if covering_genes().next().is_none() => "outside_genes",
+                Some(_) if !covering_genes().any(|gene_info| only_strand.is_none_or(|strand| gene_info.strand == strand)) => {
+                    "other_strand"
+                }
+                Some(_) => "non_ascii",
+            }
+        };
+        rejected.push(RejectedMutation { mutation: mutation.clone(), reason });
+    }
+    rejected
+}
+
+// 按位置把将要应用的突变归入 MutationStats 的各类（read、applied 和 filtered 由调用方填写）；
+// 被 --max-n-fraction 排除的 contig 上的突变不归类，由调用方计入 filtered
+fn classify_mutations<'a>(
+    mutations: impl IntoIterator<Item = &'a Mutation>,
+    gene_positions_map: &HashMap<String, Vec<GeneInfo>>,
+    contigs: &Contigs,
+    excluded_contigs: &BTreeSet<String>,
+) -> MutationStats {
+    // 每个 contig 上合并后的基因区间，按起点排序，互不重叠
+    let gene_windows: HashMap<&str, Vec<(usize, usize)>> = gene_positions_map
+        .iter()
+        .map(|(contig_id, genes)| {
+            let mut intervals: Vec<(usize, usize)> = genes.iter().map(|gene| (gene.start_position, gene.end_position)).collect();
+            intervals.sort();
+            let mut merged: Vec<(usize, usize)> = vec![];
+            for (start, end) in intervals {
+                match merged.last_mut() {
+                    Some(last) if start <= last.1 + 1 => last.1 = last.1.max(end),
+                    _ => merged.push((start, end)),
+                }
+            }
+            (contig_id.as_str(), merged)
+        })
+        .collect();
+
+    let mut stats = MutationStats::default();
+    for mutation in mutations {
+        if excluded_contigs.contains(&mutation.contig_id) {
+            continue;
+        }
+        let windows = match gene_windows.get(mutation.contig_id.as_str()) {
+            Some(windows) => windows,
+            None => {
+                stats.no_gene_contig += 1;
+                continue;
+            }
+        };
+        let contig_length = match contigs.get(&mutation.contig_id) {
+            Some(sequence) => sequence.len(),
+            None => {
+                stats.missing_contig += 1;
+                continue;
+            }
+        };
+        let position = mutation.position;
+        if position == 0 || position > contig_length {
+            stats.out_of_range += 1;
+            continue;
+        }
+        // 起点不超过 position 的最后一个区间
+        let index = windows.partition_point(|&(start, _)| start <= position);
+        if index > 0 && windows[index - 1].1 >= position {
+            stats.in_genes += 1;
+        } else {
+            stats.intergenic += 1;
+        }
+    }
+    stats
+}
+
+// replace_contigs 的结果
+#[derive(Default)]
+struct ReplacedContigs {
+    mutated_genes: HashMap<String, String>,
+    applied_mutations: Vec<AppliedMutation>,
+    // 按 gene_id 排序
+    gene_summaries: Vec<GeneSummary>,
+    // 各工作线程忙碌时间之和
+    busy_time: Duration,
+}
+
+// --variant-set：把一个集合的结果中的基因ID改为 {gene}__{set}，多个集合的结果可以合并到同一个输出中
+fn label_variant_set(replaced: &mut ReplacedContigs, set_name: &str) {
+    let label = |gene_id: &str| format!("{}__{}", gene_id, set_name);
+    replaced.mutated_genes = std::mem::take(&mut replaced.mutated_genes)
+        .into_iter()
+        .map(|(gene_id, sequence)| (label(&gene_id), sequence))
+        .collect();
+    for summary in &mut replaced.gene_summaries {
+        let labelled = label(&summary.gene.gene_id);
+        let source_id = std::mem::replace(&mut summary.gene.gene_id, labelled);
+        summary.gene.source_id.get_or_insert(source_id);
+    }
+    for applied in &mut replaced.applied_mutations {
+        applied.gene_id = label(&applied.gene_id);
+    }
+}
+
+// 某个 contig 上按位置索引的突变：同一位点出现多次时只保留文件中第一次出现的记录，records 为该 contig 上的原始记录数。
+// 插入/缺失单独存放，按位置从大到小排列（同样每个位置只保留第一条）
+struct ContigMutations<'a> {
+    records: usize,
+    by_position: BTreeMap<usize, &'a Mutation>,
+    indels: Vec<&'a Mutation>,
+}
+
+// 一次性按 (contig, position) 建立突变索引，每个 contig 的任务只需一次查找，不必扫描全部突变。
+// 同一位点的重复记录中第一条生效；新碱基不同（不区分大小写）的重复记录给出警告
+fn index_mutations(mutations: &[Mutation]) -> HashMap<&str, ContigMutations<'_>> {
+    let mut index: HashMap<&str, ContigMutations> = HashMap::new();
+    let mut conflicts: Vec<(&Mutation, &Mutation)> = vec![];
+    for mutation in mutations {
+        let contig_mutations = index
+            .entry(mutation.contig_id.as_str())
+            .or_insert_with(|| ContigMutations { records: 0, by_position: BTreeMap::new(), indels: vec![] });
+        contig_mutations.records += 1;
+        if mutation.indel.is_some() {
+            contig_mutations.indels.push(mutation);
+            continue;
+        }
+        match contig_mutations.by_position.entry(mutation.position) {
+            std::collections::btree_map::Entry::Vacant(entry) => {
+                entry.insert(mutation);
+            }
+            std::collections::btree_map::Entry::Occupied(entry) => {
+                if !entry.get().new_base.eq_ignore_ascii_case(&mutation.new_base) {
+                    conflicts.push((entry.get(), mutation));
+                }
+            }
+        }
+    }
+    if !conflicts.is_empty() {
+        let message = tr!(
+            "警告：{} 条突变与同一位点上更早的记录给出不同的碱基，使用第一次出现的记录",
+            "warning: {} mutations give a different base than an earlier record at the same position; the first record is used",
+            conflicts.len(),
+        );
+        Warning::new("conflicting_duplicate", message).emit_summary(conflicts.iter().map(|(kept, ignored)| {
+            Warning::new(
+                "conflicting_duplicate",
+                tr!(
+                    "{}:{} 的突变 {} 被忽略，使用更早的 {}", "mutation {2} at {0}:{1} ignored; the earlier {3} is used",
+                    ignored.contig_id, ignored.position, ignored.new_base, kept.new_base
+                ),
+            )
+            .contig(&ignored.contig_id)
+            .position(ignored.position)
+        }));
+    }
+    for contig_mutations in index.values_mut() {
+        // 稳定排序，同一位置保留文件中第一次出现的记录
+        contig_mutations.indels.sort_by_key(|mutation| std::cmp::Reverse(mutation.position));
+        contig_mutations.indels.dedup_by_key(|mutation| mutation.position);
+    }
+    index
+}
+
+// 在一个基因的序列（与参考坐标 start..=end 逐位对应，已应用替换）上按位置从大到小应用插入/缺失，
+// 前面的编辑不会改变后面要编辑的位置。去掉参考和替换序列的共同前缀（锚定碱基）后：
+// 纯插入只在插入点两侧都是基因内的碱基时应用；被替换的区间完全在基因内时替换为替换序列的其余部分；
+// 跨越基因边界的纯缺失只删去基因内的部分，跨越边界的其他编辑以及与已应用的编辑重叠的编辑跳过并警告。
+// indels 须按位置从大到小排列。返回 (新序列, 应用的编辑)
+fn apply_indels<'a>(gene_info: &GeneInfo, sequence: String, indels: &[&'a Mutation]) -> (String, Vec<&'a Mutation>) {
+    let (gene_start, gene_end) = (gene_info.start_position, gene_info.end_position);
+    let mut bases = sequence.into_bytes();
+    let mut applied = vec![];
+    let mut edited_from = usize::MAX; // 已应用的编辑中最小的参考位置
+    for mutation in indels {
+        let Some(indel) = mutation.indel.as_deref() else {
+            continue;
+        };
+        let (reference, alt) = (indel.reference.as_bytes(), indel.alt.as_bytes());
+        let shared = reference.iter().zip(alt).take_while(|(r, a)| r.eq_ignore_ascii_case(a)).count();
+        let first = mutation.position + shared; // 第一个被替换的参考位置；纯插入时插入点在 first - 1 和 first 之间
+        let last = mutation.position + reference.len() - 1; // 纯插入时为 first - 1
+        let inserted = &alt[shared..];
+        let warning = |message: &str| {
+            Warning::new("indel_skipped", tr!(
+                "跳过插入/缺失 {}:{}（{}）：{}",
+                "skipped insertion/deletion {}:{} ({}): {}",
+                mutation.contig_id,
+                mutation.position,
+                mutation.id_or_dot(),
+                message,
+            ))
+                .contig(&mutation.contig_id)
+                .gene(&gene_info.gene_id)
+                .position(mutation.position)
+        };
+        let (from, to) = if shared == reference.len() {
+            if first <= gene_start || first > gene_end {
+                continue;
+            }
+            (first, first - 1)
+        } else if last < gene_start || first > gene_end {
+            continue;
+        } else if first < gene_start || last > gene_end {
+            if !inserted.is_empty() {
+                warning(&tr!("跨越基因边界", "crosses a gene boundary")).emit();
+                continue;
+            }
+            (first.max(gene_start), last.min(gene_end))
+        } else {
+            (first, last)
+        };
+        if to >= edited_from {
+            warning(&tr!("与已应用的插入/缺失重叠", "overlaps an insertion/deletion already applied")).emit();
+            continue;
+        }
+        bases.splice(from - gene_start..to + 1 - gene_start, inserted.iter().copied());
+        edited_from = from;
+        applied.push(*mutation);
+    }
+    (String::from_utf8_lossy(&bases).into_owned(), applied)
+}
+
+// 一个插入/缺失在参考坐标上的影响：(第一个被替换的位置, 最后一个被替换的位置, 长度变化)；
+// 纯插入时最后一个位置为第一个位置减 1
+fn indel_edit(mutation: &Mutation) -> Option<(usize, usize, isize)> {
+    let indel = mutation.indel.as_deref()?;
+    let (reference, alt) = (indel.reference.as_bytes(), indel.alt.as_bytes());
+    let shared = reference.iter().zip(alt).take_while(|(r, a)| r.eq_ignore_ascii_case(a)).count();
+    let first = mutation.position + shared;
+    let last = mutation.position + reference.len() - 1;
+    Some((first, last, alt.len() as isize - reference.len() as isize))
+}
+
+// 参考坐标 position 在应用了 edits（indel_edit 的结果）之后的位置：加上完全位于其前面的编辑的长度变化；
+// 位于被删除区间内的位置对应到删除点之后的第一个碱基
+fn edited_coordinate(position: usize, edits: &[(usize, usize, isize)]) -> usize {
+    let mut shift: isize = 0;
+    for &(first, last, delta) in edits {
+        if last < position {
+            shift += delta;
+        } else if first <= position {
+            shift -= (position - first) as isize;
+        }
+    }
+    position.saturating_add_signed(shift)
+}
+
+// 对内存中的 contig 应用突变并截取基因序列，不涉及任何文件读写。给出线程池时每个 contig 一个并行任务，
+// 否则在当前线程依次处理（如 wasm 中没有线程可用）。
+// 给出 only_strand 时只有该链上的基因应用突变，其余基因输出参考序列。
+// 基因区间超出 contig 时警告后跳过该基因，不会 panic。
+// 各 contig 的结果在并行处理结束后按 contig ID 顺序合并，同一 gene_id 重复时保留排在前面的记录（与线程数无关）；
+// 调用方应先用 dedup_genes 去重。进度计入 counters：命令行传入全局的 COUNTERS，库的入口传入本次调用自己的计数器
+fn replace_contigs(
+    contigs: &Contigs,
+    gene_positions_map: &HashMap<String, Vec<GeneInfo>>,
+    mutations: &[Mutation],
+    only_strand: Option<Strand>,
+    strategy: Strategy,
+    pool: Option<&rayon::ThreadPool>,
+    counters: &RunCounters,
+) -> Result<ReplacedContigs, GeneReplaceError> {
+    // 每个 contig 的基因序列和摘要（坐标、长度、应用的突变数），并行处理结束后再按 contig ID 合并
+    type ContigGenes = Vec<(String, String, GeneSummary)>; // (gene_id, 序列, 摘要)
+    let contig_genes: Mutex<Vec<(String, ContigGenes)>> = Mutex::new(vec![]);
+    // 记录实际应用的突变，以 (contig, position, 是否为插入/缺失) 为键去重（同一位点可能落在多个基因中）
+    type AppliedKey = (String, usize, bool);
+    let applied_mutations: Arc<RwLock<BTreeMap<AppliedKey, AppliedMutation>>> = Arc::new(RwLock::new(BTreeMap::new()));
+
+    let mutation_index = index_mutations(mutations);
+    let no_mutations = ContigMutations { records: 0, by_position: BTreeMap::new(), indels: vec![] };
+
+    // 各工作线程的忙碌时间（纳秒），用于计算实际达到的平均并行度
+    let busy_nanos = AtomicU64::new(0);
+    let replace_span = tracing::Span::current();
+    {
+        let applied_mutations_clone = Arc::clone(&applied_mutations);
+        let process_contig = |(contig_id, gene_info_list): (&String, &Vec<GeneInfo>)| {
+            // 忙碌时间只用于并行度统计；顺序处理时不计时（wasm32-unknown-unknown 上 Instant 不可用）
+            let _busy_timer = pool.map(|_| BusyTimer { start: Instant::now(), total_nanos: &busy_nanos });
+            let contig_span = info_span!(
+                parent: &replace_span,
+                "contig",
+                contig_id = %contig_id,
+                length = field::Empty,
+                genes = gene_info_list.len(),
+                mutations_applied = field::Empty
+            )
+            .entered();
+            let indexed = mutation_index.get(contig_id.as_str()).unwrap_or(&no_mutations);
+            // 缺失的 contig 已由调用方汇总报告（report_missing_contigs），这里只计数
+            let contig_sequence = match contigs.get(contig_id) {
+                Some(contig_sequence) => contig_sequence,
+                None => {
+                    counters.rejects_missing_contig.fetch_add(indexed.records as u64, Ordering::Relaxed);
+                    return Ok(());
+                }
+            };
+            // 区间不在 contig 范围内的基因（起点为 0、起点大于终点或终点超出 contig）警告后跳过，其余基因照常处理；
+            // 下面截取基因序列依赖这里的检查
+            let in_range = |gene_info: &GeneInfo| gene_fits_contig(gene_info, contig_sequence.len());
+            let valid_genes: Vec<GeneInfo>;
+            let gene_info_list = if gene_info_list.iter().all(in_range) {
+                gene_info_list
+            } else {
+                for gene_info in gene_info_list.iter().filter(|gene_info| !in_range(gene_info)) {
+                    Warning::new(
+                        "gene_out_of_range",
+                        tr!(
+                            "警告：基因 {} 的区间 {}-{} 不在 contig {} 的范围 1-{} 内，已跳过",
+                            "warning: interval {1}-{2} of gene {0} is outside contig {3} (1-{4}); skipped",
+                            gene_info.gene_id,
+                            gene_info.start_position,
+                            gene_info.end_position,
+                            contig_id,
+                            contig_sequence.len()
+                        ),
+                    )
+                    .contig(contig_id)
+                    .gene(&gene_info.gene_id)
+                    .position(gene_info.end_position)
+                    .emit();
+                }
+                valid_genes = gene_info_list.iter().filter(|gene_info| in_range(gene_info)).cloned().collect();
+                &valid_genes
+            };
+
+            contig_span.record("length", contig_sequence.len());
+            // 按位置有序、每个位点一条记录（文件中第一次出现的）
+            let contig_mutations = indexed.by_position.values().copied();
+            let (mut applied_here, gene_sequences) = match strategy {
+                Strategy::Buffer => {
+                    record_allocation(contig_sequence.len());
+                    let mut mutated_contig: Vec<u8> = contig_sequence.clone();
+
+                    // 只应用落在某个基因区间内的突变；同一位点出现多次时索引中只有文件中第一次出现的记录。
+                    // 突变按位置有序，按起点扫过基因：活动集合中是覆盖当前位置的基因 (终点, 下标)，
+                    // 覆盖基因取其中下标最小的（与按基因列表顺序查找的结果相同）
+                    let mut by_start: Vec<usize> = (0..gene_info_list.len())
+                        .filter(|&index| only_strand.is_none_or(|strand| gene_info_list[index].strand == strand))
+                        .collect();
+                    by_start.sort_by_key(|&index| gene_info_list[index].start_position);
+                    let mut pending = by_start.into_iter().peekable();
+                    let mut active: BTreeSet<(usize, usize)> = BTreeSet::new();
+                    let mut applied_here: BTreeMap<usize, AppliedMutation> = BTreeMap::new();
+                    for mutation in contig_mutations {
+                        let position = mutation.position;
+                        if position == 0 {
+                            continue;
+                        }
+                        while let Some(index) = pending.next_if(|&index| gene_info_list[index].start_position <= position) {
+                            active.insert((gene_info_list[index].end_position, index));
+                        }
+                        while active.first().is_some_and(|&(end, _)| end < position) {
+                            active.pop_first();
+                        }
+                        let covering_gene = active.iter().map(|&(_, index)| index).min().map(|index| &gene_info_list[index]);
+                        let gene_position = position - 1; // position 已排除 0
+                        let covering_gene = match covering_gene {
+                            Some(gene_info) => gene_info,
+                            None => continue,
+                        };
+                        if gene_position >= mutated_contig.len() {
+                            counters.rejects_out_of_range.fetch_add(1, Ordering::Relaxed);
+                            continue;
+                        }
+                        // 原地替换单个字节，只处理 ASCII 碱基以保证序列仍是合法的 UTF-8
+                        let ref_byte = mutated_contig[gene_position];
+                        if !ref_byte.is_ascii() || !mutation.new_base.is_ascii() {
+                            Warning::new("non_ascii_base", tr!(
+                                "跳过非 ASCII 碱基的突变：{}:{}",
+                                "skipped mutation with a non-ASCII base: {}:{}",
+                                contig_id,
+                                position,
+                            ))
+                                .contig(contig_id)
+                                .position(position)
+                                .emit();
+                            counters.rejects_non_ascii.fetch_add(1, Ordering::Relaxed);
+                            continue;
+                        }
+                        mutated_contig[gene_position] = mutation.new_base as u8;
+                        counters.mutations_applied.fetch_add(1, Ordering::Relaxed);
+                        applied_here.insert(position, AppliedMutation {
+                            mutation: mutation.clone(),
+                            ref_base: ref_byte as char,
+                            gene_id: covering_gene.gene_id.clone(),
+                            edited_position: position,
+                        });
+                    }
+
+                    // 从突变后的缓冲区中截取基因序列（小块复制）；不在 only_strand 上的基因截取参考序列。
+                    // 上面已校验 1 <= start <= end <= contig 长度
+                    let gene_sequences: Vec<String> = gene_info_list
+                        .iter()
+                        .map(|gene_info| {
+                            let start = gene_info.start_position - 1; // 起始位点（因为序列索引从0开始）
+                            let end = gene_info.end_position; // 终止位点
+                            record_allocation(end - start);
+                            let mutated = only_strand.is_none_or(|strand| gene_info.strand == strand);
+                            let source = if mutated { &mutated_contig } else { contig_sequence };
+                            String::from_utf8_lossy(&source[start..end]).into_owned()
+                        })
+                        .collect();
+                    (applied_here, gene_sequences)
+                }
+                Strategy::MergeJoin => {
+                    merge_join_contig(contig_id, contig_sequence, gene_info_list, contig_mutations, only_strand, counters)
+                }
+            };
+
+            // 汇总每个基因的序列和摘要；应用到任一基因的插入/缺失按位置记录一次，归属列表中第一个应用它的基因
+            let mut genes_here: Vec<(String, String, GeneSummary)> = Vec::with_capacity(gene_info_list.len());
+            let mut applied_indels: BTreeMap<usize, AppliedMutation> = BTreeMap::new();
+            for (gene_info, gene_sequence) in gene_info_list.iter().zip(gene_sequences) {
+                let mutated = only_strand.is_none_or(|strand| gene_info.strand == strand);
+                let (gene_sequence, indels_applied) = match mutated {
+                    true => apply_indels(gene_info, gene_sequence, &indexed.indels),
+                    false => (gene_sequence, vec![]),
+                };
+                for &mutation in &indels_applied {
+                    applied_indels.entry(mutation.position).or_insert_with(|| AppliedMutation {
+                        mutation: mutation.clone(),
+                        ref_base: mutation.indel.as_deref().and_then(|indel| indel.reference.chars().next()).unwrap_or('N'),
+                        gene_id: gene_info.gene_id.clone(),
+                        edited_position: mutation.position,
+                    });
+                }
+                let gene_applied = applied_here
+                    .range(gene_info.start_position..=gene_info.end_position)
+                    .filter(|_| mutated);
+                let summary = GeneSummary {
+                    gene: gene_info.clone(),
+                    length: gene_sequence.len(),
+                    mutations_applied: gene_applied.clone().count() + indels_applied.len(),
+                    codons_affected: count_codons_affected(gene_applied.clone().map(|(&position, _)| position), gene_info),
+                    hamming: gene_applied
+                        .filter(|(_, applied)| !applied.ref_base.eq_ignore_ascii_case(&applied.mutation.new_base))
+                        .count(),
+                };
+                emit_event("gene_done", || {
+                    vec![("gene_id", json_string(&gene_info.gene_id)), ("n_mutations", summary.mutations_applied.to_string())]
+                });
+                genes_here.push((gene_info.gene_id.clone(), gene_sequence, summary));
+                counters.genes_processed.fetch_add(1, Ordering::Relaxed);
+            }
+
+            counters.mutations_applied.fetch_add(applied_indels.len() as u64, Ordering::Relaxed);
+            contig_span.record("mutations_applied", applied_here.len() + applied_indels.len());
+            // 应用了插入/缺失之后的坐标
+            if !applied_indels.is_empty() {
+                let edits: Vec<(usize, usize, isize)> =
+                    applied_indels.values().filter_map(|applied| indel_edit(&applied.mutation)).collect();
+                for applied in applied_here.values_mut().chain(applied_indels.values_mut()) {
+                    applied.edited_position = edited_coordinate(applied.mutation.position, &edits);
+                }
+            }
+
+            contig_genes.lock().unwrap().push((contig_id.clone(), genes_here));
+            let mut applied_write = applied_mutations_clone.write().unwrap();
+            for (position, applied) in applied_here {
+                applied_write.insert((contig_id.clone(), position, false), applied);
+            }
+            for (position, applied) in applied_indels {
+                applied_write.insert((contig_id.clone(), position, true), applied);
+            }
+            Ok(())
+        };
+        match pool {
+            Some(pool) => pool.install(|| gene_positions_map.par_iter().try_for_each(process_contig))?,
+            None => gene_positions_map.iter().try_for_each(process_contig)?,
+        }
+    }
+
+    // 按 contig ID 顺序合并，结果不依赖各线程完成的先后
+    let mut contig_genes = contig_genes.into_inner().unwrap();
+    contig_genes.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut mutated_genes: HashMap<String, String> = HashMap::new();
+    let mut gene_summaries: Vec<GeneSummary> = vec![];
+    let mut collisions: Vec<GeneSummary> = vec![];
+    for (gene_id, gene_sequence, summary) in contig_genes.into_iter().flat_map(|(_, genes_here)| genes_here) {
+        match mutated_genes.entry(gene_id) {
+            Entry::Vacant(entry) => {
+                entry.insert(gene_sequence);
+                gene_summaries.push(summary);
+            }
+            Entry::Occupied(_) => collisions.push(summary),
+        }
+    }
+    if !collisions.is_empty() {
+        let message = tr!(
+            "警告：{} 个基因与另一个基因的 gene_id 相同，只输出按 contig ID 排在前面的记录",
+            "warning: {} genes share a gene_id with another gene; only the record on the first contig by ID is output",
+            collisions.len(),
+        );
+        Warning::new("duplicate_gene_id", message).emit_summary(collisions.iter().map(|summary| {
+            Warning::new(
+                "duplicate_gene_id",
+                tr!(
+                    "基因 {} ({}:{}-{}) 未输出：gene_id 重复",
+                    "gene {} ({}:{}-{}) not output: duplicate gene_id",
+                    summary.gene.gene_id,
+                    summary.gene.contig_id,
+                    summary.gene.start_position,
+                    summary.gene.end_position,
+                ),
+            )
+            .contig(&summary.gene.contig_id)
+            .gene(&summary.gene.gene_id)
+        }));
+    }
+    let applied_mutations = applied_mutations.read().unwrap().values().cloned().collect();
+    gene_summaries.sort_by(|a, b| a.gene.gene_id.cmp(&b.gene.gene_id));
+
+    Ok(ReplacedContigs {
+        mutated_genes,
+        applied_mutations,
+        gene_summaries,
+        busy_time: Duration::from_nanos(busy_nanos.load(Ordering::Relaxed)),
+    })
+}
+
+// 突变在所属基因上的表示：基因内位置（1-based）及 ref/alt 等位基因（插入/缺失时为序列）
+struct GeneRelativeVariant<'a> {
+    gene: &'a GeneInfo,
+    position: usize,
+    ref_allele: String,
+    alt_allele: String,
+}
+
+// 按基因ID查找突变所属基因；strand_relative 时负链基因按 5'->3' 计位置并取互补碱基
+struct GeneRelativeView<'a> {
+    genes: HashMap<&'a str, &'a GeneInfo>,
+    strand_relative: bool,
+}
+
+impl<'a> GeneRelativeView<'a> {
+    fn new(gene_summaries: &'a [GeneSummary], strand_relative: bool) -> Self {
+        let genes = gene_summaries.iter().map(|summary| (summary.gene.gene_id.as_str(), &summary.gene)).collect();
+        GeneRelativeView { genes, strand_relative }
+    }
+
+    fn describe(&self, applied: &AppliedMutation) -> Option<GeneRelativeVariant<'a>> {
+        let gene = *self.genes.get(applied.gene_id.as_str())?;
+        let (ref_allele, alt_allele) = applied.alleles();
+        if self.strand_relative && gene.strand == Strand::Minus {
+            // 负链上变异的 5' 端是其参考区间在基因组上的最后一个碱基
+            let last = applied.mutation.position + ref_allele.len().max(1) - 1;
+            Some(GeneRelativeVariant {
+                gene,
+                position: (gene.end_position + 1).saturating_sub(last),
+                ref_allele: reverse_complement(&ref_allele),
+                alt_allele: reverse_complement(&alt_allele),
+            })
+        } else {
+            Some(GeneRelativeVariant {
+                gene,
+                position: applied.mutation.position + 1 - gene.start_position,
+                ref_allele,
+                alt_allele,
+            })
+        }
+    }
+
+    // 报告中使用的 ref/alt：找不到所属基因时退回基因组上的等位基因
+    fn alleles(&self, applied: &AppliedMutation) -> (String, String) {
+        self.describe(applied)
+            .map(|variant| (variant.ref_allele, variant.alt_allele))
+            .unwrap_or_else(|| applied.alleles())
+    }
+}
+
+// 单个 contig 上应用突变的方式（--strategy）
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum Strategy {
+    #[default]
+    Buffer,    // 复制整个 contig 到缓冲区原地修改，再截取基因
+    MergeJoin, // 按位置归并扫描突变和基因区间，只为当前覆盖的基因保留缓冲区
+}
+
+impl Strategy {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "buffer" => Some(Strategy::Buffer),
+            "merge-join" => Some(Strategy::MergeJoin),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Strategy::Buffer => "buffer",
+            Strategy::MergeJoin => "merge-join",
+        }
+    }
+}
+
+// 主输出中记录的顺序（--sort-by）；同样的输入总是得到逐字节相同的输出
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum SortBy {
+    #[default]
+    Position, // 按 contig ID、起点、终点，再按基因ID
+    Gene,     // 按基因ID
+    Input,    // 按基因在位置文件中的顺序
+}
+
+impl SortBy {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "position" => Some(SortBy::Position),
+            "gene" | "id" => Some(SortBy::Gene),
+            "input" => Some(SortBy::Input),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            SortBy::Position => "position",
+            SortBy::Gene => "gene",
+            SortBy::Input => "input",
+        }
+    }
+}
+
+// --strategy merge-join：突变按位置、基因按起点排序后做一次前向归并。维护覆盖当前位置的“活动”基因窗口，
+// 突变直接写入每个活动基因自己的缓冲区，位置越过基因终点后即输出并释放该缓冲区。
+// 每个 contig 的开销为 O(M + G log G)（突变已按位置排序时），内存与同时重叠的基因数成正比。
+// 结果与 Strategy::Buffer 完全一致：同一位点以文件中第一次出现的为准，AppliedMutation 的 gene_id 为输入中第一个覆盖该位点的基因
+fn merge_join_contig<'a>(
+    contig_id: &str,
+    reference: &[u8],
+    gene_info_list: &[GeneInfo],
+    contig_mutations: impl Iterator<Item = &'a Mutation>,
+    only_strand: Option<Strand>,
+    counters: &RunCounters,
+) -> (BTreeMap<usize, AppliedMutation>, Vec<String>) {
+    let slice = |gene_info: &GeneInfo| &reference[gene_info.start_position - 1..gene_info.end_position];
+    let mut sorted_mutations: Vec<&Mutation> = contig_mutations.collect();
+    if !sorted_mutations.is_sorted_by_key(|mutation| mutation.position) {
+        sorted_mutations.sort_by_key(|mutation| mutation.position); // 稳定排序，保留同一位点的文件顺序
+    }
+    let mut by_start: Vec<usize> = (0..gene_info_list.len()).collect();
+    by_start.sort_by_key(|&index| gene_info_list[index].start_position);
+    let mut pending = by_start.into_iter().peekable();
+
+    let mut gene_sequences: Vec<String> = vec![String::new(); gene_info_list.len()];
+    // 活动基因：(终点, 基因下标) -> 该基因的缓冲区，按终点排序以便依次退出
+    let mut active: BTreeMap<(usize, usize), Vec<u8>> = BTreeMap::new();
+    let mut applied_here: BTreeMap<usize, AppliedMutation> = BTreeMap::new();
+    for mutation in sorted_mutations {
+        let position = mutation.position;
+        if position == 0 || applied_here.contains_key(&position) {
+            continue;
+        }
+        while let Some(index) = pending.next_if(|&index| gene_info_list[index].start_position <= position) {
+            let gene_info = &gene_info_list[index];
+            if only_strand.is_none_or(|strand| gene_info.strand == strand) {
+                record_allocation(gene_info.end_position + 1 - gene_info.start_position);
+                active.insert((gene_info.end_position, index), slice(gene_info).to_vec());
+            } else {
+                gene_sequences[index] = String::from_utf8_lossy(slice(gene_info)).into_owned();
+            }
+        }
+        while let Some(entry) = active.first_entry().filter(|entry| entry.key().0 < position) {
+            let ((_, index), buffer) = entry.remove_entry();
+            gene_sequences[index] = String::from_utf8_lossy(&buffer).into_owned();
+        }
+        // 活动窗口中的基因都覆盖当前位置（且活动基因的终点不超过 contig 长度）
+        let Some(&(_, covering_index)) = active.keys().min_by_key(|&&(_, index)| index) else {
+            continue;
+        };
+        let ref_byte = reference[position - 1];
+        if !ref_byte.is_ascii() || !mutation.new_base.is_ascii() {
+            Warning::new("non_ascii_base", tr!(
+                "跳过非 ASCII 碱基的突变：{}:{}",
+                "skipped mutation with a non-ASCII base: {}:{}",
+                contig_id,
+                position,
+            ))
+                .contig(contig_id)
+                .position(position)
+                .emit();
+            counters.rejects_non_ascii.fetch_add(1, Ordering::Relaxed);
+            continue;
+        }
+        for (&(_, index), buffer) in active.iter_mut() {
+            buffer[position - gene_info_list[index].start_position] = mutation.new_base as u8;
+        }
+        counters.mutations_applied.fetch_add(1, Ordering::Relaxed);
+        applied_here.insert(position, AppliedMutation {
+            mutation: mutation.clone(),
+            ref_base: ref_byte as char,
+            gene_id: gene_info_list[covering_index].gene_id.clone(),
+            edited_position: position,
+        });
+    }
+    for ((_, index), buffer) in active {
+        gene_sequences[index] = String::from_utf8_lossy(&buffer).into_owned();
+    }
+    for index in pending {
+        gene_sequences[index] = String::from_utf8_lossy(slice(&gene_info_list[index])).into_owned();
+    }
+    (applied_here, gene_sequences)
+}
+
+// 将实际应用的突变写成一个最小的 VCF（CHROM/POS/ID/REF/ALT），作为有效改动的权威记录
+// strand_relative 时 CHROM/POS/REF/ALT 仍为基因组坐标，链相对的表示写入 INFO
+fn write_applied_vcf(
+    filename: &str,
+    applied_mutations: &[AppliedMutation],
+    view: Option<&GeneRelativeView>,
+    provenance: Option<&RunInfo>,
+) -> io::Result<()> {
+    let mut vcf_file = BufWriter::new(File::create(filename)?);
+    writeln!(vcf_file, "##fileformat=VCFv4.2")?;
+    writeln!(vcf_file, "##source=z10")?;
+    if let Some(run_info) = provenance {
+        run_info.write_provenance(&mut vcf_file, "##z10_")?;
+    }
+    if view.is_some() {
+        writeln!(vcf_file, "##INFO=<ID=GENE,Number=1,Type=String,Description=\"Gene covering the variant\">")?;
+        writeln!(vcf_file, "##INFO=<ID=STRAND,Number=1,Type=String,Description=\"Strand of the gene\">")?;
+        writeln!(vcf_file, "##INFO=<ID=GPOS,Number=1,Type=Integer,Description=\"1-based position within the gene, 5' to 3'\">")?;
+        writeln!(vcf_file, "##INFO=<ID=GREF,Number=1,Type=String,Description=\"Reference allele on the gene strand\">")?;
+        writeln!(vcf_file, "##INFO=<ID=GALT,Number=1,Type=String,Description=\"Alternate allele on the gene strand\">")?;
+    }
+    writeln!(
+        vcf_file,
+        "##INFO=<ID=EPOS,Number=1,Type=Integer,Description=\"Position after the applied insertions and deletions, when different from POS\">"
+    )?;
+    writeln!(vcf_file, "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO")?;
+    for applied in applied_mutations {
+        let mut info: Vec<String> = vec![];
+        if let Some(variant) = view.and_then(|view| view.describe(applied)) {
+            info.push(format!(
+                "GENE={};STRAND={};GPOS={};GREF={};GALT={}",
+                variant.gene.gene_id,
+                variant.gene.strand.as_str(),
+                variant.position,
+                variant.ref_allele,
+                variant.alt_allele
+            ));
+        }
+        if applied.edited_position != applied.mutation.position {
+            info.push(format!("EPOS={}", applied.edited_position));
+        }
+        let (ref_allele, alt_allele) = applied.alleles();
+        writeln!(
+            vcf_file,
+            "{}\t{}\t{}\t{}\t{}\t.\tPASS\t{}",
+            applied.mutation.contig_id,
+            applied.mutation.position,
+            applied.mutation.id_or_dot(),
+            ref_allele,
+            alt_allele,
+            if info.is_empty() { ".".to_string() } else { info.join(";") }
+        )?;
+    }
+    vcf_file.flush()
+}
+
+// 将实际应用的突变写成 BED 轨道（0-based 半开区间），名称为 ref>alt，可直接载入基因组浏览器。
+// 坐标为应用了插入/缺失之后的 contig 坐标（没有插入/缺失时与参考坐标相同），区间覆盖替换后的等位基因
+// strand_relative 时名称使用基因链上的等位基因，并补充 BED6 的 score 和 strand 列
+fn write_track_bed(filename: &str, applied_mutations: &[AppliedMutation], view: Option<&GeneRelativeView>) -> io::Result<()> {
+    let mut bed_file = BufWriter::new(File::create(filename)?);
+    writeln!(bed_file, "track name=z10_applied description=\"Applied mutations\"")?;
+    for applied in applied_mutations {
+        let (ref_allele, alt_allele) = applied.alleles();
+        let start = applied.edited_position - 1;
+        write!(bed_file, "{}\t{}\t{}\t", applied.mutation.contig_id, start, start + alt_allele.len().max(1))?;
+        match view {
+            Some(view) => {
+                let strand = view.describe(applied).map_or(Strand::Unknown, |variant| variant.gene.strand);
+                let (ref_allele, alt_allele) = view.alleles(applied);
+                writeln!(bed_file, "{}>{}\t0\t{}", ref_allele, alt_allele, strand.as_str())?;
+            }
+            None => writeln!(bed_file, "{}>{}", ref_allele, alt_allele)?,
+        }
+    }
+    bed_file.flush()
+}
+
+// 替换的类型：转换（嘌呤<->嘌呤 A<->G，嘧啶<->嘧啶 C<->T）、颠换，或 ref/alt 不是 ACGT（含简并碱基）
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SubstitutionClass {
+    Transition,
+    Transversion,
+    Other,
+}
+
+impl SubstitutionClass {
+    // 替换后与参考相同（不区分大小写）时返回 None
+    fn classify(ref_base: char, alt_base: char) -> Option<Self> {
+        let (ref_base, alt_base) = (ref_base.to_ascii_uppercase(), alt_base.to_ascii_uppercase());
+        if ref_base == alt_base {
+            return None;
+        }
+        let is_purine = |base: char| match base {
+            'A' | 'G' => Some(true),
+            'C' | 'T' => Some(false),
+            _ => None,
+        };
+        Some(match (is_purine(ref_base), is_purine(alt_base)) {
+            (Some(first), Some(second)) if first == second => SubstitutionClass::Transition,
+            (Some(_), Some(_)) => SubstitutionClass::Transversion,
+            _ => SubstitutionClass::Other,
+        })
+    }
+}
+
+// 写出转换/颠换统计（TSV）：第一行为全部实际应用的替换（每个位点计一次），之后每个基因一行（按基因ID排序），
+// 统计落在基因区间内的替换，重叠基因上的位点在每个基因中各计一次。比值在没有颠换时为 NA
+fn write_ts_tv_report(filename: &str, applied_mutations: &[AppliedMutation], gene_summaries: &[GeneSummary]) -> io::Result<()> {
+    let mut by_contig: HashMap<&str, Vec<(usize, SubstitutionClass)>> = HashMap::new();
+    for applied in applied_mutations.iter().filter(|applied| !applied.is_indel()) {
+        if let Some(class) = SubstitutionClass::classify(applied.ref_base, applied.mutation.new_base) {
+            by_contig.entry(applied.mutation.contig_id.as_str()).or_default().push((applied.mutation.position, class));
+        }
+    }
+    for positions in by_contig.values_mut() {
+        positions.sort_unstable_by_key(|&(position, _)| position);
+    }
+    let tally = |classes: &[(usize, SubstitutionClass)]| {
+        let count = |wanted| classes.iter().filter(|&&(_, class)| class == wanted).count();
+        (count(SubstitutionClass::Transition), count(SubstitutionClass::Transversion), count(SubstitutionClass::Other))
+    };
+    let mut report_file = BufWriter::new(File::create(filename)?);
+    writeln!(report_file, "gene_id\ttransitions\ttransversions\tother\tts_tv")?;
+    let mut write_row = |label: &str, (transitions, transversions, other): (usize, usize, usize)| {
+        let ratio = if transversions == 0 { "NA".to_string() } else { format!("{:.3}", transitions as f64 / transversions as f64) };
+        writeln!(report_file, "{}\t{}\t{}\t{}\t{}", label, transitions, transversions, other, ratio)
+    };
+    let all: Vec<(usize, SubstitutionClass)> = by_contig.values().flatten().copied().collect();
+    write_row("ALL", tally(&all))?;
+    let mut summaries: Vec<&GeneSummary> = gene_summaries.iter().collect();
+    summaries.sort_by(|a, b| a.gene.gene_id.cmp(&b.gene.gene_id));
+    for summary in summaries {
+        let gene = &summary.gene;
+        // 没有应用突变的基因（如 --only-strand 排除的链）计为 0
+        let classes = match by_contig.get(gene.contig_id.as_str()) {
+            Some(positions) if summary.mutations_applied > 0 => {
+                let first = positions.partition_point(|&(position, _)| position < gene.start_position);
+                let last = positions.partition_point(|&(position, _)| position <= gene.end_position);
+                &positions[first..last]
+            }
+            _ => &[],
+        };
+        write_row(&gene.gene_id, tally(classes))?;
+    }
+    report_file.flush()
+}
+
+// 将基因ID转换为安全的归档条目名：只保留字母数字和 . _ -，其余替换为 _，并避免以 . 开头
+fn sanitize_entry_name(gene_id: &str) -> String {
+    let name: String = gene_id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-' { c } else { '_' })
+        .collect();
+    match name.strip_prefix('.') {
+        Some(rest) => format!("_{}", rest),
+        None if name.is_empty() => "_".to_string(),
+        None => name,
+    }
+}
+
+// 清理后的 <名称>.fasta 文件名，清理后重名时追加序号
+fn unique_fasta_name(used_names: &mut HashSet<String>, name: &str) -> String {
+    let base_name = sanitize_entry_name(name);
+    let mut entry_name = format!("{}.fasta", base_name);
+    let mut suffix = 1;
+    while !used_names.insert(entry_name.clone()) {
+        suffix += 1;
+        entry_name = format!("{}_{}.fasta", base_name, suffix);
+    }
+    entry_name
+}
+
+// 读取 gene_id -> 类别 对应表（每行 gene_id,category，也接受制表符分隔），一个基因可以属于多个类别
+fn read_gene_groups(filename: &str) -> io::Result<HashMap<String, Vec<String>>> {
+    let mut reader = HashingReader::new(open_input(filename)?);
+    let mut groups: HashMap<String, Vec<String>> = HashMap::new();
+    for (line_number, line) in BufReader::new(&mut reader).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match line.split_once([',', '\t']) {
+            Some((gene_id, category)) if !gene_id.trim().is_empty() && !category.trim().is_empty() => {
+                groups.entry(gene_id.trim().to_string()).or_default().push(category.trim().to_string());
+            }
+            _ => {
+                return Err(GeneReplaceError::ParseError(tr!(
+                    "{} 第 {} 行不是 gene_id,category 格式", "{} line {} is not in gene_id,category format",
+                    filename,
+                    line_number + 1
+                ))
+                .into());
+            }
+        }
+    }
+    reader.record_input(filename);
+    Ok(groups)
+}
+
+// 不属于任何类别的基因写入的文件
+const UNGROUPED_CATEGORY: &str = "ungrouped";
+
+// 按类别把基因写到目录下的 <类别>.fasta（文件名按 tar 条目同样的规则清理），不在对应表中的基因写入 ungrouped.fasta；
+// 每个文件中的基因按ID排序，记录格式（--wrap）与主 FASTA 输出相同。返回 (类别, 文件路径, 基因数)
+fn write_grouped_output(
+    directory: &str,
+    mutated_genes: &HashMap<String, String>,
+    groups: &HashMap<String, Vec<String>>,
+    wrap: usize,
+) -> io::Result<Vec<(String, String, usize)>> {
+    std::fs::create_dir_all(directory)?;
+    let ungrouped = vec![UNGROUPED_CATEGORY.to_string()];
+    let mut members: BTreeMap<&str, Vec<&String>> = BTreeMap::new();
+    let mut gene_ids: Vec<&String> = mutated_genes.keys().collect();
+    gene_ids.sort();
+    for gene_id in gene_ids {
+        for category in groups.get(gene_id).unwrap_or(&ungrouped) {
+            members.entry(category.as_str()).or_default().push(gene_id);
+        }
+    }
+    let mut used_names: HashSet<String> = HashSet::new();
+    let mut written = vec![];
+    for (category, gene_ids) in members {
+        let path = std::path::Path::new(directory).join(unique_fasta_name(&mut used_names, category));
+        let mut group_file = RecordFlusher::new(BufWriter::new(File::create(&path)?), None);
+        write_fasta_records(&mut group_file, OutputFormat::Fasta, mutated_genes, gene_ids.iter().copied(), false, wrap)?;
+        group_file.flush()?;
+        written.push((category.to_string(), path.display().to_string(), gene_ids.len()));
+    }
+    Ok(written)
+}
+
+// 将每个基因作为单独的 FASTA 条目写入一个 tar 归档（路径以 .gz 结尾时 gzip 压缩），记录格式（--wrap）与主 FASTA 输出相同；
+// 条目按基因ID排序，修改时间固定为 0，保证相同输入得到相同的归档
+fn write_split_output_tar(filename: &str, mutated_genes: &HashMap<String, String>, wrap: usize) -> io::Result<()> {
+    let file = BufWriter::new(File::create(filename)?);
+    let writer: Box<dyn Write> = if filename.ends_with(".gz") {
+        Box::new(flate2::write::GzEncoder::new(file, flate2::Compression::default()))
+    } else {
+        Box::new(file)
+    };
+    let mut archive = tar::Builder::new(writer);
+
+    let mut gene_ids: Vec<&String> = mutated_genes.keys().collect();
+    gene_ids.sort();
+    let mut used_names: HashSet<String> = HashSet::new();
+    for gene_id in gene_ids {
+        let entry_name = unique_fasta_name(&mut used_names, gene_id);
+
+        let mut record = RecordFlusher::new(Vec::new(), None);
+        write_fasta_records(&mut record, OutputFormat::Fasta, mutated_genes, [gene_id], false, wrap)?;
+        let record = record.into_inner();
+        let mut header = tar::Header::new_gnu();
+        header.set_size(record.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(0);
+        header.set_cksum();
+        archive.append_data(&mut header, &entry_name, record.as_slice())?;
+    }
+    archive.into_inner()?.flush()
+}
+
+// 输出记录ID到原始基因位置记录的映射（TSV）。基因没有链信息时 strand 为 "."
+fn write_id_map(filename: &str, gene_summaries: &[GeneSummary], provenance: Option<&RunInfo>) -> io::Result<()> {
+    let mut map_file = BufWriter::new(File::create(filename)?);
+    if let Some(run_info) = provenance {
+        run_info.write_provenance(&mut map_file, "; ")?;
+    }
+    writeln!(map_file, "output_id\tgene_id\tcontig\tstart\tend\tstrand")?;
+    for summary in gene_summaries {
+        let gene = &summary.gene;
+        writeln!(
+            map_file,
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            gene.gene_id,
+            gene.source_id.as_deref().unwrap_or(&gene.gene_id),
+            gene.contig_id,
+            gene.start_position,
+            gene.end_position,
+            gene.strand.as_str()
+        )?;
+    }
+    map_file.flush()
+}
+
+// --edited-coords：每个输出基因的参考坐标及其在应用了插入/缺失之后的 contig 上的坐标（TSV，按基因ID排序）。
+// 编辑后的起点加上位于其前面的全部已应用插入/缺失的长度变化（起点被删除时对应到删除点之后），
+// 终点为起点加上输出序列的长度；没有插入/缺失的 contig 上两组坐标相同
+fn write_edited_coords(
+    filename: &str,
+    gene_summaries: &[GeneSummary],
+    applied_mutations: &[AppliedMutation],
+    provenance: Option<&RunInfo>,
+) -> io::Result<()> {
+    let mut edits: HashMap<&str, Vec<(usize, usize, isize)>> = HashMap::new();
+    for applied in applied_mutations {
+        if let Some(edit) = indel_edit(&applied.mutation) {
+            edits.entry(applied.mutation.contig_id.as_str()).or_default().push(edit);
+        }
+    }
+    let mut coords_file = BufWriter::new(File::create(filename)?);
+    if let Some(run_info) = provenance {
+        run_info.write_provenance(&mut coords_file, "; ")?;
+    }
+    writeln!(coords_file, "gene_id\tcontig\tstart\tend\tedited_start\tedited_end\tstrand")?;
+    for summary in gene_summaries {
+        let gene = &summary.gene;
+        let contig_edits = edits.get(gene.contig_id.as_str()).map_or(&[][..], Vec::as_slice);
+        let edited_start = edited_coordinate(gene.start_position, contig_edits);
+        writeln!(
+            coords_file,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            gene.gene_id,
+            gene.contig_id,
+            gene.start_position,
+            gene.end_position,
+            edited_start,
+            edited_start + summary.length - 1,
+            gene.strand.as_str()
+        )?;
+    }
+    coords_file.flush()
+}
+
+// --summary：每个输出基因一行，给出坐标、长度和实际应用的突变数（已排除参考碱基不一致等被跳过的突变），按基因ID排序
+// translation_flags 为 --translate 得到的（含内部终止密码子的基因, 末尾密码子不完整的基因），给出时追加两列 yes/no
+fn write_gene_summary(
+    filename: &str,
+    gene_summaries: &[GeneSummary],
+    translation_flags: Option<&(HashSet<String>, HashSet<String>)>,
+    provenance: Option<&RunInfo>,
+) -> io::Result<()> {
+    let mut summary_file = BufWriter::new(File::create(filename)?);
+    if let Some(run_info) = provenance {
+        run_info.write_provenance(&mut summary_file, "; ")?;
+    }
+    write!(summary_file, "gene_id\tcontig_id\tstart\tend\tlength\tnum_mutations_applied")?;
+    if translation_flags.is_some() {
+        write!(summary_file, "\tinternal_stop\tincomplete_codon")?;
+    }
+    writeln!(summary_file)?;
+    let yes_no = |flag: bool| if flag { "yes" } else { "no" };
+    for summary in gene_summaries {
+        let gene = &summary.gene;
+        write!(
+            summary_file,
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            gene.gene_id, gene.contig_id, gene.start_position, gene.end_position, summary.length, summary.mutations_applied
+        )?;
+        if let Some((internal_stop, incomplete)) = translation_flags {
+            write!(
+                summary_file,
+                "\t{}\t{}",
+                yes_no(internal_stop.contains(&gene.gene_id)),
+                yes_no(incomplete.contains(&gene.gene_id))
+            )?;
+        }
+        writeln!(summary_file)?;
+    }
+    summary_file.flush()
+}
+
+// 按固定宽度分箱统计输出基因的长度分布，用于快速发现截断或融合的基因
+fn write_length_histogram(
+    filename: &str,
+    mutated_genes: &HashMap<String, String>,
+    bin_width: usize,
+    provenance: Option<&RunInfo>,
+) -> io::Result<()> {
+    let bin_width = bin_width.max(1);
+    let mut bins: BTreeMap<usize, usize> = BTreeMap::new();
+    for sequence in mutated_genes.values() {
+        *bins.entry(sequence.len() / bin_width).or_insert(0) += 1;
+    }
+
+    let mut histogram_file = BufWriter::new(File::create(filename)?);
+    if let Some(run_info) = provenance {
+        run_info.write_provenance(&mut histogram_file, "; ")?;
+    }
+    writeln!(histogram_file, "bin_start\tbin_end\tcount")?;
+    if let Some(&last_bin) = bins.keys().next_back() {
+        for bin in 0..=last_bin {
+            let count = bins.get(&bin).copied().unwrap_or(0);
+            writeln!(histogram_file, "{}\t{}\t{}", bin * bin_width, (bin + 1) * bin_width - 1, count)?;
+        }
+    }
+    histogram_file.flush()
+}
+// 标准遗传密码（NCBI 表 1），密码子按 T、C、A、G 的顺序编号
+const STANDARD_CODE: &[u8; 64] = b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG";
+
+// --genetic-code 可选的 NCBI 遗传密码表，编号方式与 STANDARD_CODE 相同。替代起始密码子不做特殊处理（按表中的氨基酸翻译）；
+// 终止密码子依赖上下文的表 27、28、31 不支持
+const GENETIC_CODES: [(u32, &[u8; 64]); 22] = [
+    (1, STANDARD_CODE),
+    (2, b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSS**VVVVAAAADDEEGGGG"),
+    (3, b"FFLLSSSSYY**CCWWTTTTPPPPHHQQRRRRIIMMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (4, b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (5, b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSSSSVVVVAAAADDEEGGGG"),
+    (6, b"FFLLSSSSYYQQCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (9, b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNNKSSSSVVVVAAAADDEEGGGG"),
+    (10, b"FFLLSSSSYY**CCCWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (11, STANDARD_CODE),
+    (12, b"FFLLSSSSYY**CC*WLLLSPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (13, b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSSGGVVVVAAAADDEEGGGG"),
+    (14, b"FFLLSSSSYYY*CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNNKSSSSVVVVAAAADDEEGGGG"),
+    (16, b"FFLLSSSSYY*LCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (21, b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNNKSSSSVVVVAAAADDEEGGGG"),
+    (22, b"FFLLSS*SYY*LCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (23, b"FF*LSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (24, b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSSKVVVVAAAADDEEGGGG"),
+    (25, b"FFLLSSSSYY**CCGWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (26, b"FFLLSSSSYY**CC*WLLLAPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (29, b"FFLLSSSSYYYYCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (30, b"FFLLSSSSYYEECC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (33, b"FFLLSSSSYYY*CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSSKVVVVAAAADDEEGGGG"),
+];
+
+fn genetic_code(table: u32) -> Option<&'static [u8; 64]> {
+    GENETIC_CODES.iter().find(|(number, _)| *number == table).map(|(_, code)| *code)
+}
+
+// 互补碱基，支持大小写和 IUPAC 简并碱基，未知字符原样返回
+fn complement_base(base: u8) -> u8 {
+    match base {
+        b'A' => b'T', b'T' => b'A', b'G' => b'C', b'C' => b'G', b'U' => b'A',
+        b'a' => b't', b't' => b'a', b'g' => b'c', b'c' => b'g', b'u' => b'a',
+        b'R' => b'Y', b'Y' => b'R', b'K' => b'M', b'M' => b'K', b'B' => b'V', b'V' => b'B', b'D' => b'H', b'H' => b'D',
+        b'r' => b'y', b'y' => b'r', b'k' => b'm', b'm' => b'k', b'b' => b'v', b'v' => b'b', b'd' => b'h', b'h' => b'd',
+        other => other, // N、S、W 以及未知字符的互补是其自身
+    }
+}
+
+// 按字符反转，非 ASCII 字符原样保留（不会拆开多字节字符）
+fn reverse_complement(sequence: &str) -> String {
+    sequence.chars().rev().map(|c| if c.is_ascii() { complement_base(c as u8) as char } else { c }).collect()
+}
+
+fn translate_codon(codon: &[u8], code: &[u8; 64]) -> char {
+    let mut index = 0;
+    for &base in codon {
+        let value = match base.to_ascii_uppercase() {
+            b'T' | b'U' => 0,
+            b'C' => 1,
+            b'A' => 2,
+            b'G' => 3,
+            _ => return 'X',
+        };
+        index = index * 4 + value;
+    }
+    code[index] as char
+}
+
+// 按给定读码框起点翻译，末尾不完整的密码子被忽略
+fn translate(sequence: &str, frame: usize, code: &[u8; 64]) -> String {
+    sequence.as_bytes().get(frame..).unwrap_or(&[]).chunks_exact(3).map(|codon| translate_codon(codon, code)).collect()
+}
+
+// 把基因序列中的非 ACGT 碱基（不区分大小写）替换为参考序列同一位置的碱基；
+// 返回 (替换的位点数, 参考碱基也不是 ACGT 而保持不变的位点数)
+fn collapse_ambiguity(
+    mutated_genes: &mut HashMap<String, String>,
+    gene_summaries: &[GeneSummary],
+    contigs: &Contigs,
+) -> (usize, usize) {
+    let is_acgt = |base: u8| matches!(base.to_ascii_uppercase(), b'A' | b'C' | b'G' | b'T');
+    let mut collapsed = 0;
+    let mut remaining = 0;
+    for summary in gene_summaries {
+        let gene = &summary.gene;
+        let (Some(sequence), Some(contig_sequence)) = (mutated_genes.get_mut(&gene.gene_id), contigs.get(&gene.contig_id)) else {
+            continue;
+        };
+        if sequence.bytes().all(|base| is_acgt(base) || base == GAP) {
+            continue;
+        }
+        let Some(reference) = gene.start_position.checked_sub(1).and_then(|start| contig_sequence.get(start..gene.end_position)) else {
+            continue;
+        };
+        let mut bases = std::mem::take(sequence).into_bytes();
+        for (base, &reference_base) in bases.iter_mut().zip(reference) {
+            if is_acgt(*base) || *base == GAP {
+                continue;
+            }
+            if is_acgt(reference_base) {
+                *base = reference_base;
+                collapsed += 1;
+            } else {
+                remaining += 1;
+            }
+        }
+        *sequence = String::from_utf8_lossy(&bases).into_owned();
+    }
+    (collapsed, remaining)
+}
+
+// 序列中允许出现的字符：IUPAC 核苷酸（含简并碱基，大小写均可）以及表示缺失的 '-'
+fn is_iupac_base(base: u8) -> bool {
+    matches!(base.to_ascii_uppercase(), b'A' | b'C' | b'G' | b'T' | b'U' | b'N' | b'R' | b'Y' | b'K' | b'M' | b'S' | b'W' | b'B' | b'D' | b'H' | b'V' | b'-')
+}
+
+// 输出 FASTA 的一致性检查（--check-output）：ID 唯一、非空且描述分隔符之前不含空白，
+// 序列非空且只含允许的字符，折行时除最后一行外各行等长且最后一行不长于其他行
+struct FastaConformance {
+    ids: HashSet<String>,
+    allow_mask: bool, // masked-diff 输出中未改变的位置为 '.'
+}
+
+impl FastaConformance {
+    fn new(allow_mask: bool) -> Self {
+        FastaConformance { ids: HashSet::new(), allow_mask }
+    }
+
+    // header 为 '>' 之后的内容，lines 为该记录的各个序列行
+    fn check(&mut self, header: &str, lines: &[&str]) -> Result<(), String> {
+        let (id, description) = match header.split_once(' ') {
+            Some((id, description)) => (id, Some(description)),
+            None => (header, None),
+        };
+        if id.is_empty() {
+            return Err(tr!("记录 {:?} 的 ID 为空", "record {:?} has an empty ID", header));
+        }
+        if id.chars().any(char::is_whitespace) || description.is_some_and(|d| d.contains(['\r', '\n'])) {
+            return Err(tr!("记录 {:?} 的标题含有多余的空白字符", "the header of record {:?} contains extra whitespace", header));
+        }
+        if !self.ids.insert(id.to_string()) {
+            return Err(tr!("记录 ID {:?} 重复", "duplicate record ID {:?}", id));
+        }
+        if lines.iter().all(|line| line.is_empty()) {
+            return Err(tr!("记录 {} 的序列为空", "record {} has an empty sequence", id));
+        }
+        for line in lines {
+            if let Some(base) = line.bytes().find(|&base| !(is_iupac_base(base) || self.allow_mask && base == b'.')) {
+                return Err(tr!(
+                    "记录 {} 含有不允许的字符 {:?}",
+                    "record {} contains the disallowed character {:?}",
+                    id,
+                    base as char,
+                ));
+            }
+        }
+        if let Some((last, wrapped)) = lines.split_last() {
+            let width = wrapped.first().map_or(last.len(), |line| line.len());
+            if wrapped.iter().any(|line| line.len() != width) || last.len() > width {
+                return Err(tr!("记录 {} 的折行长度不一致", "record {} has inconsistent line wrapping", id));
+            }
+        }
+        Ok(())
+    }
+}
+
+// 主输出文件的格式
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum OutputFormat {
+    #[default]
+    Fasta,      // 突变后的基因序列
+    MaskedDiff, // 与基因等长，未改变的位置为 '.'，改变的位置为替换后的碱基
+    Tsv,        // 每个基因一行：坐标、链、应用的突变数和突变后的序列
+    Json,       // 与 tsv 相同的字段，写成 {"genes": [...]}
+    Jsonl,      // 与 json 相同的对象，每行一个，便于流式解析
+    Fastq,      // 突变后的序列加质量行，被改变的位置使用较低的质量
+}
+
+impl OutputFormat {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "fasta" => Some(OutputFormat::Fasta),
+            "masked-diff" => Some(OutputFormat::MaskedDiff),
+            "tsv" => Some(OutputFormat::Tsv),
+            "json" => Some(OutputFormat::Json),
+            "jsonl" => Some(OutputFormat::Jsonl),
+            "fastq" => Some(OutputFormat::Fastq),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            OutputFormat::Fasta => "fasta",
+            OutputFormat::MaskedDiff => "masked-diff",
+            OutputFormat::Tsv => "tsv",
+            OutputFormat::Json => "json",
+            OutputFormat::Jsonl => "jsonl",
+            OutputFormat::Fastq => "fastq",
+        }
+    }
+
+    // 对应的 --<格式>-out 参数名
+    fn out_arg(self) -> &'static str {
+        match self {
+            OutputFormat::Fasta => "fasta_out",
+            OutputFormat::MaskedDiff => "masked_diff_out",
+            OutputFormat::Tsv => "tsv_out",
+            OutputFormat::Json => "json_out",
+            OutputFormat::Jsonl => "jsonl_out",
+            OutputFormat::Fastq => "fastq_out",
+        }
+    }
+}
+
+// 写出一种格式的主输出。先写入同目录下的临时文件，全部写完（且 FASTA 检查通过）后再改名为最终路径，
+// 中途失败不会留下不完整的输出。设置 flush_every 时直接写最终路径并每 N 条记录刷新一次，
+// 让读取该文件的下游程序及时看到记录（失败时会留下已写出的部分）
+fn write_output(
+    filename: &str,
+    format: OutputFormat,
+    records: &HashMap<String, String>,
+    masked_diffs: Option<&HashMap<String, String>>, // fastq 据此判断哪些位置被改变
+    gene_summaries: &[GeneSummary],
+    settings: OutputSettings,
+    provenance: Option<&RunInfo>, // json/jsonl 中写出的来源信息
+) -> io::Result<WrittenOutput> {
+    let OutputSettings { check_output, flush_every, mutated_quality, wrap } = settings;
+    let temp_file = match flush_every {
+        Some(_) => filename.to_string(),
+        None => format!("{}.tmp-{}", filename, std::process::id()),
+    };
+    let mut writer = RecordFlusher::new(OutputWriter::create(&temp_file, filename.ends_with(".gz"))?, flush_every);
+    let written = match format {
+        OutputFormat::Fasta | OutputFormat::MaskedDiff => {
+            let order = gene_summaries.iter().map(|summary| &summary.gene.gene_id);
+            write_fasta_records(&mut writer, format, records, order, check_output, wrap).map(|()| {
+                COUNTERS.bytes_written.fetch_add(writer.bytes, Ordering::Relaxed);
+            })
+        }
+        OutputFormat::Fastq => {
+            let empty = HashMap::new();
+            write_fastq_records(&mut writer, records, masked_diffs.unwrap_or(&empty), gene_summaries, mutated_quality)
+        }
+        OutputFormat::Tsv => write_tsv_records(&mut writer, records, gene_summaries),
+        OutputFormat::Json => write_json_records(&mut writer, records, gene_summaries, provenance),
+        OutputFormat::Jsonl => write_jsonl_records(&mut writer, records, gene_summaries, provenance),
+    };
+    let finished = written.and_then(|_| {
+        let written = writer.written();
+        writer.into_inner().finish().map(|_| written)
+    });
+    let written = match finished {
+        Ok(written) => written,
+        Err(error) => {
+            if flush_every.is_none() {
+                let _ = std::fs::remove_file(&temp_file);
+            }
+            return Err(error);
+        }
+    };
+    if flush_every.is_none() {
+        std::fs::rename(&temp_file, filename)?;
+    }
+    Ok(written)
+}
+
+// write_output 的公共设置
+#[derive(Debug, Clone, Copy)]
+struct OutputSettings {
+    check_output: bool,
+    flush_every: Option<usize>,
+    mutated_quality: u8, // fastq 中被改变位置的 Phred 质量
+    wrap: usize,         // FASTA/masked-diff 序列每行的字符数，0 为不折行
+}
+
+// fastq 中未改变位置的 Phred 质量
+const REFERENCE_QUALITY: u8 = 40;
+
+// Phred 质量按 Sanger / Illumina 1.8+ 的 Phred+33 编码为一个字符
+fn phred33(quality: u8) -> u8 {
+    quality.min(93) + 33
+}
+
+// fastq：每个基因四行（@ID、序列、+、质量），与参考相同的位置质量为 REFERENCE_QUALITY，
+// 被突变改变的位置（masked-diff 中不是 '.' 的位置）为 mutated_quality
+fn write_fastq_records<W: Write>(
+    writer: &mut RecordFlusher<W>,
+    records: &HashMap<String, String>,
+    masked_diffs: &HashMap<String, String>,
+    order: &[GeneSummary],
+    mutated_quality: u8,
+) -> io::Result<()> {
+    for (gene_id, sequence) in order.iter().filter_map(|summary| records.get_key_value(&summary.gene.gene_id)) {
+        let changed = masked_diffs.get(gene_id).map(|masked| masked.as_bytes());
+        let quality: Vec<u8> = (0..sequence.len())
+            .map(|index| match changed.and_then(|changed| changed.get(index)) {
+                Some(&base) if base != b'.' => phred33(mutated_quality),
+                _ => phred33(REFERENCE_QUALITY),
+            })
+            .collect();
+        writeln!(writer, "@{}\n{}\n+\n{}", gene_id, sequence, String::from_utf8_lossy(&quality))?;
+        COUNTERS.bytes_written.fetch_add((gene_id.len() + 2 * sequence.len() + 6) as u64, Ordering::Relaxed);
+        writer.end_record()?;
+    }
+    Ok(())
+}
+
+// 主输出的底层文件：路径以 .gz 结尾时 gzip 压缩；finish 写出 gzip 尾部并刷新
+enum OutputWriter {
+    Plain(BufWriter<File>),
+    Gzip(BufWriter<flate2::write::GzEncoder<File>>),
+}
+
+impl OutputWriter {
+    fn create(path: &str, gzip: bool) -> io::Result<Self> {
+        let file = File::create(path)?;
+        Ok(match gzip {
+            true => OutputWriter::Gzip(BufWriter::new(flate2::write::GzEncoder::new(file, flate2::Compression::default()))),
+            false => OutputWriter::Plain(BufWriter::new(file)),
+        })
+    }
+
+    fn finish(self) -> io::Result<()> {
+        match self {
+            OutputWriter::Plain(mut writer) => writer.flush(),
+            OutputWriter::Gzip(writer) => writer.into_inner().map_err(io::IntoInnerError::into_error)?.finish().map(drop),
+        }
+    }
+}
+
+impl Write for OutputWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            OutputWriter::Plain(writer) => writer.write(buf),
+            OutputWriter::Gzip(writer) => writer.write(buf),
+        }
+    }
+
+    // gzip 时同步刷新压缩流，已写出的记录可以被跟随读取的下游解压
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            OutputWriter::Plain(writer) => writer.flush(),
+            OutputWriter::Gzip(writer) => writer.flush(),
+        }
+    }
+}
+
+// 写出的一个主输出的记录数、字节数和 BLAKE3（gzip 输出时按解压后的内容计算），供 --verify-output 回读比较
+struct WrittenOutput {
+    records: usize,
+    bytes: u64,
+    blake3: blake3::Hash,
+}
+
+// --verify-output：重新读取刚写出的文件，按格式数记录，并与写出时的记录数、字节数和 BLAKE3 比较，
+// 发现截断（如磁盘写满）或内容不一致时返回错误。gzip 输出解压后比较
+fn verify_output(filename: &str, format: OutputFormat, expected: &WrittenOutput) -> io::Result<()> {
+    let mut reader = HashingReader::new(open_input(filename)?);
+    let mut lines: usize = 0;
+    let mut headers: usize = 0;
+    let mut provenance_lines: usize = 0;
+    for line in BufReader::new(&mut reader).lines() {
+        let line = line?;
+        lines += 1;
+        if line.starts_with('>') {
+            headers += 1;
+        }
+        if line.starts_with("{\"provenance\":") {
+            provenance_lines += 1;
+        }
+    }
+    let records = match format {
+        OutputFormat::Fasta | OutputFormat::MaskedDiff => headers,
+        OutputFormat::Tsv => lines.saturating_sub(1),
+        OutputFormat::Json => lines.saturating_sub(2),
+        OutputFormat::Jsonl => lines - provenance_lines,
+        OutputFormat::Fastq => lines / 4,
+    };
+    let actual = reader.digest.finalize();
+    let mismatch = if reader.bytes_read != expected.bytes {
+        Some(tr!("大小为 {} 字节，写出时为 {} 字节", "size is {} bytes, {} bytes when written", reader.bytes_read, expected.bytes))
+    } else if actual != expected.blake3 {
+        Some(tr!("BLAKE3 为 {}，写出时为 {}", "BLAKE3 is {}, {} when written", actual.to_hex(), expected.blake3.to_hex()))
+    } else if records != expected.records {
+        Some(tr!("含 {} 条记录，写出时为 {} 条", "contains {} records, {} when written", records, expected.records))
+    } else {
+        None
+    };
+    match mismatch {
+        Some(mismatch) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            tr!("--verify-output：{} {}", "--verify-output: {} {}", filename, mismatch),
+        )),
+        None => Ok(()),
+    }
+}
+
+// 每写完 N 条记录刷新一次内部的 BufWriter（--flush-every）；没有设置时只在最后刷新。
+// 同时统计记录数、字节数和 BLAKE3，供 --verify-output 使用
+struct RecordFlusher<W: Write> {
+    inner: W,
+    flush_every: Option<usize>,
+    records: usize,
+    bytes: u64,
+    digest: blake3::Hasher,
+}
+
+impl<W: Write> RecordFlusher<W> {
+    fn new(inner: W, flush_every: Option<usize>) -> Self {
+        RecordFlusher { inner, flush_every, records: 0, bytes: 0, digest: blake3::Hasher::new() }
+    }
+
+    fn written(&self) -> WrittenOutput {
+        WrittenOutput { records: self.records, bytes: self.bytes, blake3: self.digest.finalize() }
+    }
+
+    fn into_inner(self) -> W {
+        self.inner
+    }
+
+    // 一条记录写完
+    fn end_record(&mut self) -> io::Result<()> {
+        self.records += 1;
+        match self.flush_every {
+            Some(every) if self.records.is_multiple_of(every) => self.inner.flush(),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<W: Write> Write for RecordFlusher<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.digest.update(&buf[..n]);
+        self.bytes += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// 按 order 中基因ID的顺序写出 records 中的基因（主输出、--split-output-tar 和 --group-by 共用）
+fn write_fasta_records<'a, W: Write>(
+    writer: &mut RecordFlusher<W>,
+    format: OutputFormat,
+    records: &HashMap<String, String>,
+    order: impl IntoIterator<Item = &'a String>,
+    check_output: bool,
+    wrap: usize,
+) -> io::Result<()> {
+    let mut checker = check_output.then(|| FastaConformance::new(format == OutputFormat::MaskedDiff));
+    for (gene_id, mutated_sequence) in order.into_iter().filter_map(|gene_id| records.get_key_value(gene_id)) {
+        let lines = wrap_lines(mutated_sequence, wrap);
+        if let Some(checker) = checker.as_mut() {
+            checker
+                .check(gene_id, &lines)
+                .map_err(|violation| io::Error::new(io::ErrorKind::InvalidData, tr!(
+                    "输出检查失败：{}",
+                    "output check failed: {}",
+                    violation,
+                )))?;
+        }
+        let write_record = |writer: &mut RecordFlusher<W>| -> io::Result<()> {
+            writeln!(writer, ">{}", gene_id)?;
+            for line in &lines {
+                writer.write_all(line.as_bytes())?;
+                writer.write_all(b"\n")?;
+            }
+            Ok(())
+        };
+        write_record(writer)
+            .map_err(|e| io::Error::new(e.kind(), tr!("写入输出文件出错：{}", "error writing to the output file: {}", e)))?;
+        writer.end_record()?;
+    }
+    Ok(())
+}
+
+// 把序列切成每行 width 个字符（按字符而不是字节计数，不会拆开非 ASCII 字符）；
+// width 为 0 时整条序列一行。空序列仍输出一个空行
+fn wrap_lines(sequence: &str, width: usize) -> Vec<&str> {
+    if width == 0 || sequence.is_empty() {
+        return vec![sequence];
+    }
+    let mut lines = Vec::with_capacity(sequence.len().div_ceil(width));
+    let mut rest = sequence;
+    while !rest.is_empty() {
+        let end = rest.char_indices().nth(width).map_or(rest.len(), |(index, _)| index);
+        let (line, tail) = rest.split_at(end);
+        lines.push(line);
+        rest = tail;
+    }
+    lines
+}
+
+// tsv 和 json 按 gene_summaries 的顺序写出（与主输出的 --sort-by 一致）
+fn write_tsv_records<W: Write>(writer: &mut RecordFlusher<W>, records: &HashMap<String, String>, gene_summaries: &[GeneSummary]) -> io::Result<()> {
+    writeln!(writer, "gene_id\tcontig\tstart\tend\tstrand\tmutations_applied\thamming\tsequence")?;
+    for summary in gene_summaries {
+        let gene = &summary.gene;
+        let sequence = records.get(&gene.gene_id).map_or("", |sequence| sequence.as_str());
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            gene.gene_id,
+            gene.contig_id,
+            gene.start_position,
+            gene.end_position,
+            gene.strand.as_str(),
+            summary.mutations_applied,
+            summary.hamming,
+            sequence
+        )?;
+        COUNTERS.bytes_written.fetch_add(sequence.len() as u64, Ordering::Relaxed);
+        writer.end_record()?;
+    }
+    Ok(())
+}
+
+// json 和 jsonl 中一个基因的对象（单行）
+fn json_gene_object(summary: &GeneSummary, sequence: &str) -> String {
+    let gene = &summary.gene;
+    format!(
+        "{{\"gene_id\":{},\"contig\":{},\"start\":{},\"end\":{},\"strand\":{},\"mutations_applied\":{},\"hamming\":{},\"sequence\":{}}}",
+        json_string(&gene.gene_id),
+        json_string(&gene.contig_id),
+        gene.start_position,
+        gene.end_position,
+        json_string(gene.strand.as_str()),
+        summary.mutations_applied,
+        summary.hamming,
+        json_string(sequence)
+    )
+}
+
+// 给出来源信息时写在 "genes" 之前的同一行，记录仍从第二行开始
+fn write_json_records<W: Write>(
+    writer: &mut RecordFlusher<W>,
+    records: &HashMap<String, String>,
+    gene_summaries: &[GeneSummary],
+    provenance: Option<&RunInfo>,
+) -> io::Result<()> {
+    match provenance {
+        Some(run_info) => writeln!(writer, "{{\"provenance\":{},\"genes\":[", run_info.provenance_json())?,
+        None => writeln!(writer, "{{\"genes\":[")?,
+    }
+    for (index, summary) in gene_summaries.iter().enumerate() {
+        let sequence = records.get(&summary.gene.gene_id).map_or("", |sequence| sequence.as_str());
+        let separator = if index + 1 < gene_summaries.len() { "," } else { "" };
+        writeln!(writer, "{}{}", json_gene_object(summary, sequence), separator)?;
+        COUNTERS.bytes_written.fetch_add(sequence.len() as u64, Ordering::Relaxed);
+        writer.end_record()?;
+    }
+    writeln!(writer, "]}}")
+}
+
+// 每行一个基因对象，没有外层数组，读到一行即可解析一个基因。给出来源信息时第一行是 {"provenance":...}
+fn write_jsonl_records<W: Write>(
+    writer: &mut RecordFlusher<W>,
+    records: &HashMap<String, String>,
+    gene_summaries: &[GeneSummary],
+    provenance: Option<&RunInfo>,
+) -> io::Result<()> {
+    if let Some(run_info) = provenance {
+        writeln!(writer, "{{\"provenance\":{}}}", run_info.provenance_json())?;
+    }
+    for summary in gene_summaries {
+        let sequence = records.get(&summary.gene.gene_id).map_or("", |sequence| sequence.as_str());
+        writeln!(writer, "{}", json_gene_object(summary, sequence))?;
+        COUNTERS.bytes_written.fetch_add(sequence.len() as u64, Ordering::Relaxed);
+        writer.end_record()?;
+    }
+    Ok(())
+}
+
+// 为每个基因生成 masked-diff 序列；替换后碱基与参考相同的位点不算改变
+fn masked_diff_sequences(gene_summaries: &[GeneSummary], applied_mutations: &[AppliedMutation]) -> HashMap<String, String> {
+    let mut changes: HashMap<&str, BTreeMap<usize, char>> = HashMap::new();
+    for applied in applied_mutations.iter().filter(|applied| !applied.is_indel()) {
+        if !applied.ref_base.eq_ignore_ascii_case(&applied.mutation.new_base) {
+            changes
+                .entry(applied.mutation.contig_id.as_str())
+                .or_default()
+                .insert(applied.mutation.position, applied.mutation.new_base);
+        }
+    }
+    gene_summaries
+        .iter()
+        .map(|summary| {
+            let gene = &summary.gene;
+            let mut masked = vec![b'.'; summary.length];
+            if let Some(contig_changes) = changes.get(gene.contig_id.as_str()) {
+                for (&position, &new_base) in contig_changes.range(gene.start_position..=gene.end_position) {
+                    masked[position - gene.start_position] = new_base as u8;
+                }
+            }
+            (gene.gene_id.clone(), String::from_utf8(masked).unwrap())
+        })
+        .collect()
+}
+
+// 翻译结果中出现内部终止密码子（不在末尾的 '*'）时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum InternalStopPolicy {
+    TranslateThrough, // 保留 '*' 继续翻译
+    Truncate,         // 在第一个内部终止密码子处截断
+    Mark,             // 保留序列，在记录头中标记 internal_stop
+    Skip,             // 不输出该记录
+}
+
+impl InternalStopPolicy {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "translate-through" => Some(InternalStopPolicy::TranslateThrough),
+            "truncate" => Some(InternalStopPolicy::Truncate),
+            "mark" => Some(InternalStopPolicy::Mark),
+            "skip" => Some(InternalStopPolicy::Skip),
+            _ => None,
+        }
+    }
+}
+
+fn has_internal_stop(protein: &str) -> bool {
+    protein.trim_end_matches('*').contains('*')
+}
+
+// 按策略写出一条蛋白记录，返回该记录是否含内部终止密码子
+fn write_protein_record<W: Write>(
+    writer: &mut W,
+    record_id: &str,
+    protein: &str,
+    policy: InternalStopPolicy,
+) -> io::Result<bool> {
+    if !has_internal_stop(protein) {
+        writeln!(writer, ">{}\n{}", record_id, protein)?;
+        return Ok(false);
+    }
+    match policy {
+        InternalStopPolicy::TranslateThrough => writeln!(writer, ">{}\n{}", record_id, protein)?,
+        InternalStopPolicy::Truncate => {
+            let stop = protein.find('*').unwrap_or(protein.len());
+            writeln!(writer, ">{}\n{}", record_id, &protein[..stop])?
+        }
+        InternalStopPolicy::Mark => writeln!(writer, ">{} internal_stop\n{}", record_id, protein)?,
+        InternalStopPolicy::Skip => {}
+    }
+    Ok(true)
+}
+
+// 对每个基因输出正向和反向各三个读码框的翻译，记录名为 {gene}_f1..f3 和 {gene}_r1..r3；
+// canonical_only 时只输出基因注释的读码框：负链为 r1，正链和没有链信息的基因为 f1（基因从第一个碱基起读，phase 为 0）。
+// 返回含内部终止密码子的记录数
+fn write_six_frame_translations(
+    filename: &str,
+    mutated_genes: &HashMap<String, String>,
+    gene_summaries: &[GeneSummary],
+    canonical_only: bool,
+    code: &[u8; 64],
+    internal_stop_policy: InternalStopPolicy,
+) -> io::Result<usize> {
+    let strands: HashMap<&str, Strand> =
+        gene_summaries.iter().map(|summary| (summary.gene.gene_id.as_str(), summary.gene.strand)).collect();
+    let mut translation_file = BufWriter::new(File::create(filename)?);
+    let mut internal_stop_records = 0;
+    let mut gene_ids: Vec<&String> = mutated_genes.keys().collect();
+    gene_ids.sort();
+    for gene_id in gene_ids {
+        let forward = &mutated_genes[gene_id];
+        let reverse = reverse_complement(forward);
+        let minus = strands.get(gene_id.as_str()) == Some(&Strand::Minus);
+        for (strand, sequence) in [("f", forward.as_str()), ("r", reverse.as_str())] {
+            if canonical_only && (strand == "r") != minus {
+                continue;
+            }
+            let frames = if canonical_only { 0..1 } else { 0..3 };
+            for frame in frames {
+                let record_id = format!("{}_{}{}", gene_id, strand, frame + 1);
+                let protein = translate(sequence, frame, code);
+                if write_protein_record(&mut translation_file, &record_id, &protein, internal_stop_policy)? {
+                    internal_stop_records += 1;
+                }
+            }
+        }
+    }
+    translation_file.flush()?;
+    Ok(internal_stop_records)
+}
+
+// 每个基因写成相邻的两条记录 {gene}_nt（按基因注释的链 5'->3' 的核苷酸序列，负链为反向互补）和 {gene}_aa
+// （_nt 从第一个碱基起的翻译），两条记录方向一致。按基因ID排序；Skip 策略下含内部终止密码子的基因两条记录都不输出，使成对关系保持完整。
+// 返回含内部终止密码子的基因数
+fn write_paired_nt_aa(
+    filename: &str,
+    mutated_genes: &HashMap<String, String>,
+    gene_summaries: &[GeneSummary],
+    code: &[u8; 64],
+    internal_stop_policy: InternalStopPolicy,
+) -> io::Result<usize> {
+    let strands: HashMap<&str, Strand> =
+        gene_summaries.iter().map(|summary| (summary.gene.gene_id.as_str(), summary.gene.strand)).collect();
+    let mut paired_file = BufWriter::new(File::create(filename)?);
+    let mut internal_stop_records = 0;
+    let mut gene_ids: Vec<&String> = mutated_genes.keys().collect();
+    gene_ids.sort();
+    for gene_id in gene_ids {
+        let sequence = match strands.get(gene_id.as_str()) {
+            Some(Strand::Minus) => reverse_complement(&mutated_genes[gene_id]),
+            _ => mutated_genes[gene_id].clone(),
+        };
+        let protein = translate(&sequence, 0, code);
+        if internal_stop_policy == InternalStopPolicy::Skip && has_internal_stop(&protein) {
+            internal_stop_records += 1;
+            continue;
+        }
+        writeln!(paired_file, ">{}_nt\n{}", gene_id, sequence)?;
+        if write_protein_record(&mut paired_file, &format!("{}_aa", gene_id), &protein, internal_stop_policy)? {
+            internal_stop_records += 1;
+        }
+    }
+    paired_file.flush()?;
+    Ok(internal_stop_records)
+}
+
+// --translate：每个基因按注释的链（负链先取反向互补）从第一个碱基起翻译，写为蛋白记录 {gene}，按基因ID排序；
+// 末尾不完整的密码子丢弃。返回 (含内部终止密码子的记录数, 末尾有不完整密码子的基因)
+fn write_protein_fasta(
+    filename: &str,
+    mutated_genes: &HashMap<String, String>,
+    gene_summaries: &[GeneSummary],
+    code: &[u8; 64],
+    internal_stop_policy: InternalStopPolicy,
+) -> io::Result<(Vec<GeneInfo>, Vec<GeneInfo>)> {
+    let mut protein_file = BufWriter::new(File::create(filename)?);
+    let mut internal_stop = vec![];
+    let mut incomplete = vec![];
+    for summary in gene_summaries {
+        let Some(sequence) = mutated_genes.get(&summary.gene.gene_id) else {
+            continue;
+        };
+        if sequence.len() % 3 != 0 {
+            incomplete.push(summary.gene.clone());
+        }
+        let protein = match summary.gene.strand {
+            Strand::Minus => translate(&reverse_complement(sequence), 0, code),
+            _ => translate(sequence, 0, code),
+        };
+        if write_protein_record(&mut protein_file, &summary.gene.gene_id, &protein, internal_stop_policy)? {
+            internal_stop.push(summary.gene.clone());
+        }
+    }
+    protein_file.flush()?;
+    Ok((internal_stop, incomplete))
+}
+
+// 把突变后的基因序列中实际应用过的位点恢复为参考碱基，得到每个基因的参考序列；同一位点有多个突变时取第一个记录的参考碱基
+fn reference_gene_sequences(
+    mutated_genes: &HashMap<String, String>,
+    gene_summaries: &[GeneSummary],
+    applied_mutations: &[AppliedMutation],
+) -> HashMap<String, String> {
+    let mut reference_bases: HashMap<&str, BTreeMap<usize, char>> = HashMap::new();
+    for applied in applied_mutations.iter().filter(|applied| !applied.is_indel()) {
+        reference_bases
+            .entry(applied.mutation.contig_id.as_str())
+            .or_default()
+            .entry(applied.mutation.position)
+            .or_insert(applied.ref_base);
+    }
+    gene_summaries
+        .iter()
+        .filter_map(|summary| {
+            let gene = &summary.gene;
+            let mut bases = mutated_genes.get(&gene.gene_id)?.clone().into_bytes();
+            if let Some(contig_bases) = reference_bases.get(gene.contig_id.as_str()) {
+                for (&position, &ref_base) in contig_bases.range(gene.start_position..=gene.end_position) {
+                    if let Some(base) = bases.get_mut(position - gene.start_position) {
+                        *base = ref_base as u8;
+                    }
+                }
+            }
+            Some((gene.gene_id.clone(), String::from_utf8_lossy(&bases).into_owned()))
+        })
+        .collect()
+}
+
+// 把序列从第一个碱基起按密码子切开，以空格分隔；末尾不足一个密码子的碱基被丢弃
+fn split_codons(sequence: &str) -> String {
+    let codons: Vec<&str> = sequence
+        .as_bytes()
+        .chunks_exact(3)
+        .map(|codon| std::str::from_utf8(codon).unwrap_or("NNN"))
+        .collect();
+    codons.join(" ")
+}
+
+// 每个基因输出相邻的 {gene}_ref 和 {gene}_mut 两条记录，序列按密码子以空格分隔，供 dN/dS 工具使用；
+// 负链基因先取反向互补，再从第一个碱基起切分（phase 为 0）。按基因ID排序。
+// 返回 (长度不是 3 的倍数、末尾碱基被丢弃的基因数, 因含插入/缺失而跳过的基因数)
+fn write_codon_alignment(
+    filename: &str,
+    mutated_genes: &HashMap<String, String>,
+    gene_summaries: &[GeneSummary],
+    applied_mutations: &[AppliedMutation],
+) -> io::Result<(usize, usize)> {
+    let strands: HashMap<&str, Strand> =
+        gene_summaries.iter().map(|summary| (summary.gene.gene_id.as_str(), summary.gene.strand)).collect();
+    let references = reference_gene_sequences(mutated_genes, gene_summaries, applied_mutations);
+    let lengths: HashMap<&str, usize> = gene_summaries
+        .iter()
+        .map(|summary| (summary.gene.gene_id.as_str(), summary.gene.end_position + 1 - summary.gene.start_position))
+        .collect();
+    let mut alignment_file = BufWriter::new(File::create(filename)?);
+    let mut truncated = 0;
+    let mut indel_genes = 0;
+    let mut gene_ids: Vec<&String> = references.keys().collect();
+    gene_ids.sort();
+    for gene_id in gene_ids {
+        let (mut reference, mut mutated) = (references[gene_id].clone(), mutated_genes[gene_id].clone());
+        // 含插入/缺失的基因长度改变，无法按密码子逐个对应
+        if reference.len() != lengths.get(gene_id.as_str()).copied().unwrap_or(reference.len()) {
+            indel_genes += 1;
+            continue;
+        }
+        if strands.get(gene_id.as_str()) == Some(&Strand::Minus) {
+            reference = reverse_complement(&reference);
+            mutated = reverse_complement(&mutated);
+        }
+        if reference.len() % 3 != 0 {
+            truncated += 1;
+        }
+        writeln!(alignment_file, ">{}_ref\n{}", gene_id, split_codons(&reference))?;
+        writeln!(alignment_file, ">{}_mut\n{}", gene_id, split_codons(&mutated))?;
+    }
+    alignment_file.flush()?;
+    Ok((truncated, indel_genes))
+}
+
+// 将所有基因按基因ID排序后首尾相接成一条超级基因，并写出每个基因在其中的位置（1-based，闭区间）
+fn write_concatenated_genes(
+    filename: &str,
+    partition_filename: &str,
+    mutated_genes: &HashMap<String, String>,
+) -> io::Result<()> {
+    let mut gene_ids: Vec<&String> = mutated_genes.keys().collect();
+    gene_ids.sort();
+
+    let mut partition_file = BufWriter::new(File::create(partition_filename)?);
+    writeln!(partition_file, "gene_id\tstart\tend")?;
+    let mut supergene = String::with_capacity(mutated_genes.values().map(|s| s.len()).sum());
+    for gene_id in gene_ids {
+        let sequence = &mutated_genes[gene_id];
+        if sequence.is_empty() {
+            continue;
+        }
+        writeln!(partition_file, "{}\t{}\t{}", gene_id, supergene.len() + 1, supergene.len() + sequence.len())?;
+        supergene.push_str(sequence);
+    }
+    partition_file.flush()?;
+
+    let mut concatenated_file = BufWriter::new(File::create(filename)?);
+    writeln!(concatenated_file, ">supergene\n{}", supergene)?;
+    concatenated_file.flush()
+}
+
+// 运行信息：写入 SQLite 的 run 表，并作为来源信息（provenance）写入支持注释的输出。
+// finished_at 为处理结束、开始写输出的时间，所有输出中的来源信息因此完全相同
+struct RunInfo {
+    // 生效的配置（含默认值），见 effective_configuration
+    configuration: Vec<(&'static str, String)>,
+    started_at: u64,
+    finished_at: u64,
+    inputs: Vec<InputDigest>,
+}
+
+impl RunInfo {
+    fn provenance_entries(&self) -> Vec<(String, String)> {
+        let mut entries = vec![("version".to_string(), env!("CARGO_PKG_VERSION").to_string())];
+        if let Some(git_describe) = GIT_DESCRIBE {
+            entries.push(("git_describe".to_string(), git_describe.to_string()));
+        }
+        for (key, value) in &self.configuration {
+            entries.push((format!("config.{}", key), value.clone()));
+        }
+        for input in &self.inputs {
+            entries.push(("input".to_string(), format!("{} size={} blake3={}", input.path, input.size, input.blake3)));
+        }
+        entries.push(("started_at".to_string(), self.started_at.to_string()));
+        entries.push(("finished_at".to_string(), self.finished_at.to_string()));
+        entries
+    }
+
+    // 配置写成一个 JSON 对象，值均为字符串
+    fn configuration_json(&self) -> String {
+        let fields: Vec<String> =
+            self.configuration.iter().map(|(key, value)| format!("{}:{}", json_string(key), json_string(value))).collect();
+        format!("{{{}}}", fields.join(","))
+    }
+
+    // 每项一行：prefix + key=value
+    fn write_provenance<W: Write>(&self, writer: &mut W, prefix: &str) -> io::Result<()> {
+        for (key, value) in self.provenance_entries() {
+            writeln!(writer, "{}{}={}", prefix, key, value)?;
+        }
+        Ok(())
+    }
+
+    fn provenance_json(&self) -> String {
+        let inputs: Vec<String> = self
+            .inputs
+            .iter()
+            .map(|input| {
+                format!(
+                    "{{\"path\":{},\"size\":{},\"blake3\":{}}}",
+                    json_string(&input.path),
+                    input.size,
+                    json_string(&input.blake3)
+                )
+            })
+            .collect();
+        format!(
+            "{{\"version\":{},\"git_describe\":{},\"configuration\":{},\"inputs\":[{}],\"started_at\":{},\"finished_at\":{}}}",
+            json_string(env!("CARGO_PKG_VERSION")),
+            GIT_DESCRIBE.map_or("null".to_string(), json_string),
+            self.configuration_json(),
+            inputs.join(","),
+            self.started_at,
+            self.finished_at
+        )
+    }
+}
+
+// 将基因、实际应用的突变和没有应用的突变（status 为 rejected，reason 为原因）写入一个 SQLite 数据库（需要 sqlite feature）
+#[cfg(feature = "sqlite")]
+fn write_sqlite(
+    filename: &str,
+    result: &ReplaceResult,
+    run_info: &RunInfo,
+    include_sequences: bool,
+) -> io::Result<()> {
+    // 表结构变化时递增，写入 PRAGMA user_version 以便检测
+    const SCHEMA_VERSION: i32 = 3;
+
+    let to_io = |e: rusqlite::Error| io::Error::other(format!("SQLite error: {}", e));
+    let _ = std::fs::remove_file(filename);
+    let mut conn = rusqlite::Connection::open(filename).map_err(to_io)?;
+    conn.execute_batch(&format!(
+        "PRAGMA user_version = {};
+         CREATE TABLE run (configuration TEXT NOT NULL, version TEXT NOT NULL, started_at INTEGER NOT NULL, finished_at INTEGER NOT NULL);
+         CREATE TABLE genes (id TEXT NOT NULL, contig TEXT NOT NULL, start INTEGER NOT NULL, end INTEGER NOT NULL,
+                             strand TEXT, length INTEGER NOT NULL, n_mutations INTEGER NOT NULL, n_codons_affected INTEGER NOT NULL,
+                             sequence TEXT);
+         CREATE TABLE mutations (id TEXT, contig TEXT NOT NULL, position INTEGER NOT NULL, ref TEXT, alt TEXT NOT NULL,
+                                 status TEXT NOT NULL, reason TEXT, gene_id TEXT, edited_position INTEGER);",
+        SCHEMA_VERSION
+    ))
+    .map_err(to_io)?;
+
+    // 所有插入放在一个事务中，避免每行一次提交
+    let tx = conn.transaction().map_err(to_io)?;
+    tx.execute(
+        "INSERT INTO run (configuration, version, started_at, finished_at) VALUES (?1, ?2, ?3, ?4)",
+        rusqlite::params![run_info.configuration_json(), env!("CARGO_PKG_VERSION"), run_info.started_at as i64, run_info.finished_at as i64],
+    )
+    .map_err(to_io)?;
+    {
+        let mut insert_gene = tx
+            .prepare("INSERT INTO genes (id, contig, start, end, strand, length, n_mutations, n_codons_affected, sequence) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)")
+            .map_err(to_io)?;
+        for summary in &result.gene_summaries {
+            let sequence = if include_sequences { result.mutated_genes.get(&summary.gene.gene_id) } else { None };
+            insert_gene
+                .execute(rusqlite::params![
+                    summary.gene.gene_id,
+                    summary.gene.contig_id,
+                    summary.gene.start_position as i64,
+                    summary.gene.end_position as i64,
+                    (summary.gene.strand != Strand::Unknown).then(|| summary.gene.strand.as_str()),
+                    summary.length as i64,
+                    summary.mutations_applied as i64,
+                    summary.codons_affected as i64,
+                    sequence,
+                ])
+                .map_err(to_io)?;
+        }
+        let mut insert_mutation = tx
+            .prepare("INSERT INTO mutations (id, contig, position, ref, alt, status, reason, gene_id, edited_position) VALUES (?1, ?2, ?3, ?4, ?5, 'applied', NULL, ?6, ?7)")
+            .map_err(to_io)?;
+        for applied in &result.applied_mutations {
+            let (ref_allele, alt_allele) = applied.alleles();
+            insert_mutation
+                .execute(rusqlite::params![
+                    applied.mutation.mutation_id,
+                    applied.mutation.contig_id,
+                    applied.mutation.position as i64,
+                    ref_allele,
+                    alt_allele,
+                    applied.gene_id,
+                    applied.edited_position as i64,
+                ])
+                .map_err(to_io)?;
+        }
+        let mut insert_rejected = tx
+            .prepare("INSERT INTO mutations (id, contig, position, ref, alt, status, reason, gene_id, edited_position) VALUES (?1, ?2, ?3, ?4, ?5, 'rejected', ?6, NULL, NULL)")
+            .map_err(to_io)?;
+        for rejected in &result.rejected_mutations {
+            let (ref_allele, alt_allele) = rejected.alleles();
+            insert_rejected
+                .execute(rusqlite::params![
+                    rejected.mutation.mutation_id,
+                    rejected.mutation.contig_id,
+                    rejected.mutation.position as i64,
+                    ref_allele,
+                    alt_allele,
+                    rejected.reason,
+                ])
+                .map_err(to_io)?;
+        }
+    }
+    tx.commit().map_err(to_io)
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn write_sqlite(_filename: &str, _result: &ReplaceResult, _run_info: &RunInfo, _include_sequences: bool) -> io::Result<()> {
+    Err(io::Error::other("--sqlite requires z10 to be built with the \"sqlite\" feature"))
+}
+
+// 将实际应用的突变和没有应用的突变写成 Parquet（需要 arrow feature）。列与 SQLite 的 mutations 表一致
+// （status 为 applied 或 rejected，rejected 时 reason 为原因，gene_id、gene_position 和 edited_position 为空），
+// 另加基因内位置（1 起始）。没有效应预测，因此不写 effect 列。按行组分批构建列，不会把整张表再复制一份
+#[cfg(feature = "arrow")]
+fn write_report_parquet(filename: &str, result: &ReplaceResult, strand_relative: bool) -> io::Result<()> {
+    use arrow_array::builder::{StringBuilder, StringDictionaryBuilder, UInt64Builder};
+    use arrow_array::types::Int32Type;
+    use arrow_array::{ArrayRef, RecordBatch};
+    use arrow_schema::{DataType, Field, Schema, SchemaRef};
+    use parquet::arrow::ArrowWriter;
+    use parquet::basic::Compression;
+    use parquet::file::properties::WriterProperties;
+    use std::sync::Arc as ArrowArc;
+
+    const ROW_GROUP_SIZE: usize = 1 << 20;
+
+    // 一个行组的各列
+    #[derive(Default)]
+    struct Columns {
+        rows: usize,
+        gene_id: StringBuilder,
+        contig: StringDictionaryBuilder<Int32Type>,
+        position: UInt64Builder,
+        ref_allele: StringDictionaryBuilder<Int32Type>,
+        alt_allele: StringDictionaryBuilder<Int32Type>,
+        status: StringDictionaryBuilder<Int32Type>,
+        reason: StringDictionaryBuilder<Int32Type>,
+        gene_position: UInt64Builder,
+        id: StringBuilder,
+        edited_position: UInt64Builder,
+    }
+
+    impl Columns {
+        fn finish(&mut self, schema: &SchemaRef) -> io::Result<RecordBatch> {
+            self.rows = 0;
+            let columns: Vec<ArrayRef> = vec![
+                ArrowArc::new(self.gene_id.finish()),
+                ArrowArc::new(self.contig.finish()),
+                ArrowArc::new(self.position.finish()),
+                ArrowArc::new(self.ref_allele.finish()),
+                ArrowArc::new(self.alt_allele.finish()),
+                ArrowArc::new(self.status.finish()),
+                ArrowArc::new(self.reason.finish()),
+                ArrowArc::new(self.gene_position.finish()),
+                ArrowArc::new(self.id.finish()),
+                ArrowArc::new(self.edited_position.finish()),
+            ];
+            RecordBatch::try_new(schema.clone(), columns).map_err(|e| io::Error::other(tr!("Arrow 错误：{}", "Arrow error: {}", e)))
+        }
+    }
+
+    let dictionary = || DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8));
+    let schema = ArrowArc::new(Schema::new(vec![
+        Field::new("gene_id", DataType::Utf8, true),
+        Field::new("contig", dictionary(), false),
+        Field::new("position", DataType::UInt64, false),
+        Field::new("ref", dictionary(), true),
+        Field::new("alt", dictionary(), false),
+        Field::new("status", dictionary(), false),
+        Field::new("reason", dictionary(), true),
+        Field::new("gene_position", DataType::UInt64, true),
+        Field::new("id", DataType::Utf8, true),
+        Field::new("edited_position", DataType::UInt64, true),
+    ]));
+
+    let to_io = |e: parquet::errors::ParquetError| io::Error::other(tr!("Parquet 错误：{}", "Parquet error: {}", e));
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .set_dictionary_enabled(true)
+        .set_max_row_group_size(ROW_GROUP_SIZE)
+        .build();
+    let mut writer = ArrowWriter::try_new(File::create(filename)?, schema.clone(), Some(properties)).map_err(to_io)?;
+    let mut write_full_batch = |columns: &mut Columns, force: bool| -> io::Result<()> {
+        if columns.rows == ROW_GROUP_SIZE || (force && columns.rows > 0) {
+            writer.write(&columns.finish(&schema)?).map_err(to_io)?;
+            writer.flush().map_err(to_io)?;
+        }
+        Ok(())
+    };
+
+    let view = GeneRelativeView::new(&result.gene_summaries, strand_relative);
+    let mut columns = Columns::default();
+    for applied in &result.applied_mutations {
+        let variant = view.describe(applied);
+        let (ref_value, alt_value) = view.alleles(applied);
+        columns.gene_id.append_value(&applied.gene_id);
+        columns.contig.append_value(&applied.mutation.contig_id);
+        columns.position.append_value(applied.mutation.position as u64);
+        columns.ref_allele.append_value(&ref_value);
+        columns.alt_allele.append_value(&alt_value);
+        columns.status.append_value("applied");
+        columns.reason.append_null();
+        columns.gene_position.append_value(variant.map_or(applied.mutation.position, |variant| variant.position) as u64);
+        columns.id.append_option(applied.mutation.mutation_id.as_deref());
+        columns.edited_position.append_value(applied.edited_position as u64);
+        columns.rows += 1;
+        write_full_batch(&mut columns, false)?;
+    }
+    for rejected in &result.rejected_mutations {
+        let (ref_value, alt_value) = rejected.alleles();
+        columns.gene_id.append_null();
+        columns.contig.append_value(&rejected.mutation.contig_id);
+        columns.position.append_value(rejected.mutation.position as u64);
+        columns.ref_allele.append_option(ref_value);
+        columns.alt_allele.append_value(&alt_value);
+        columns.status.append_value("rejected");
+        columns.reason.append_value(rejected.reason);
+        columns.gene_position.append_null();
+        columns.id.append_option(rejected.mutation.mutation_id.as_deref());
+        columns.edited_position.append_null();
+        columns.rows += 1;
+        write_full_batch(&mut columns, false)?;
+    }
+    write_full_batch(&mut columns, true)?;
+    writer.close().map_err(to_io)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "arrow"))]
+fn write_report_parquet(_filename: &str, _result: &ReplaceResult, _strand_relative: bool) -> io::Result<()> {
+    Err(io::Error::other("--report-parquet requires z10 to be built with the \"arrow\" feature"))
+}
+
+// 统计被突变触及的不同密码子数（按第 0 读码框划分：正链从基因起点、负链从基因终点起算；位置须升序给出，
+// 两条链上密码子编号都随位置单调变化，相邻比较即可去重）
+fn count_codons_affected(positions: impl Iterator<Item = usize>, gene_info: &GeneInfo) -> usize {
+    let mut last_codon = None;
+    let mut count = 0;
+    for position in positions {
+        let codon = match gene_info.strand {
+            Strand::Minus => (gene_info.end_position - position) / 3,
+            _ => (position - gene_info.start_position) / 3,
+        };
+        if last_codon != Some(codon) {
+            last_codon = Some(codon);
+            count += 1;
+        }
+    }
+    count
+}
+
+// 超出 --max-memory 时的错误信息，给出可以降低内存占用的选项
+fn memory_limit_error(stage: &str, estimated: usize, limit: usize) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::OutOfMemory,
+        tr!(
+            "{}时预计需要 {} 字节内存，超过 --max-memory 限制 {} 字节。\
+             可以减少线程数（-t）、用 --max-n-fraction 排除低质量 contig、拆分输入文件，\
+             或用 --contigs-fai 只读取需要的 contig",
+            "{} would need about {} bytes of memory, over the --max-memory limit of {} bytes. \
+             Reduce the thread count (-t), exclude low-quality contigs with --max-n-fraction, split the input files, \
+             or read only the needed contigs with --contigs-fai",
+            stage, estimated, limit
+        ),
+    )
+}
+
+// 解析可选的数值参数：未给出时为 None；无法解析或不满足 valid 时返回 InvalidInput 错误（expected 说明需要的值），
+// 不会悄悄使用默认值
+fn parse_number_arg<T: std::str::FromStr>(
+    matches: &clap::ArgMatches,
+    name: &str,
+    valid: impl Fn(&T) -> bool,
+    expected: String,
+) -> io::Result<Option<T>> {
+    let Some(value) = matches.value_of(name) else {
+        return Ok(None);
+    };
+    match value.trim().parse::<T>() {
+        Ok(number) if valid(&number) => Ok(Some(number)),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidInput, tr!("{}：{:?}", "{}: {:?}", expected, value))),
+    }
+}
+
+// 解析 --max-n-fraction：0-1 之间的比例，或带 % 的百分比（如 5%）
+fn parse_n_fraction(value: &str) -> io::Result<f64> {
+    let trimmed = value.trim();
+    let fraction = match trimmed.strip_suffix('%') {
+        Some(percent) => percent.trim().parse::<f64>().map(|percent| percent / 100.0),
+        None => trimmed.parse::<f64>(),
+    };
+    match fraction {
+        Ok(fraction) if (0.0..=1.0).contains(&fraction) => Ok(fraction),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            tr!(
+                "--max-n-fraction 需要 0-1 之间的比例或百分比（如 0.05 或 5%）：{:?}",
+                "--max-n-fraction needs a fraction from 0 to 1 or a percentage (e.g. 0.05 or 5%): {:?}",
+                value,
+            ),
+        )),
+    }
+}
+
+// 解析字节数：K/M/G/T 后缀为 1024 进制，可写成 4G、4GB 或 4GiB，不区分大小写，数值可带小数（如 1.5G）；
+// 无法解析时返回 InvalidInput 错误，不会悄悄关闭上限
+fn parse_byte_size(value: &str) -> io::Result<usize> {
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            tr!(
+                "--max-memory 需要字节数（可用 K/M/G/T 后缀，如 4G、4GB、1.5G）：{:?}",
+                "--max-memory needs a byte count (K/M/G/T suffixes accepted, e.g. 4G, 4GB, 1.5G): {:?}",
+                value,
+            ),
+        )
+    };
+    let value_upper = value.trim().to_ascii_uppercase();
+    let unit_start = value_upper.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(value_upper.len());
+    let (number, unit) = (value_upper[..unit_start].trim(), &value_upper[unit_start..]);
+    let multiplier: u64 = match unit {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        _ => return Err(invalid()),
+    };
+    let bytes = if number.contains('.') {
+        let number: f64 = number.parse().map_err(|_| invalid())?;
+        let bytes = (number * multiplier as f64).round();
+        if !(0.0..u64::MAX as f64).contains(&bytes) {
+            return Err(invalid());
+        }
+        bytes as u64
+    } else {
+        number.parse::<u64>().map_err(|_| invalid())?.checked_mul(multiplier).ok_or_else(invalid)?
+    };
+    usize::try_from(bytes).map_err(|_| invalid())
+}
+
+fn unix_timestamp() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// 按 Prometheus 文本格式渲染当前计数器
+fn render_metrics(labels: &str) -> String {
+    let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+    let mut text = String::new();
+    text.push_str("# HELP run_info Static information about the running z10 job.\n");
+    text.push_str("# TYPE run_info gauge\n");
+    text.push_str(&format!("run_info{{version=\"{}\"{}}} 1\n", env!("CARGO_PKG_VERSION"), labels));
+    text.push_str("# HELP genes_processed_total Genes extracted from mutated contigs.\n");
+    text.push_str("# TYPE genes_processed_total counter\n");
+    text.push_str(&format!("genes_processed_total {}\n", load(&COUNTERS.genes_processed)));
+    text.push_str("# HELP mutations_applied_total Mutations written into gene windows.\n");
+    text.push_str("# TYPE mutations_applied_total counter\n");
+    text.push_str(&format!("mutations_applied_total {}\n", load(&COUNTERS.mutations_applied)));
+    text.push_str("# HELP rejects_total Mutations that could not be applied, by reason.\n");
+    text.push_str("# TYPE rejects_total counter\n");
+    for (reason, counter) in [
+        ("missing_contig", &COUNTERS.rejects_missing_contig),
+        ("out_of_range", &COUNTERS.rejects_out_of_range),
+        ("non_ascii", &COUNTERS.rejects_non_ascii),
+    ] {
+        text.push_str(&format!("rejects_total{{reason=\"{}\"}} {}\n", reason, load(counter)));
+    }
+    text.push_str("# HELP invalid_records_total Gene position and mutation records skipped because they could not be parsed.\n");
+    text.push_str("# TYPE invalid_records_total counter\n");
+    text.push_str(&format!("invalid_records_total {}\n", load(&COUNTERS.invalid_records)));
+    text.push_str("# HELP bytes_written_total Bytes written to the output FASTA.\n");
+    text.push_str("# TYPE bytes_written_total counter\n");
+    text.push_str(&format!("bytes_written_total {}\n", load(&COUNTERS.bytes_written)));
+    text
+}
+
+// 先写临时文件再重命名，保证采集器不会读到写了一半的文件
+fn write_metrics_file(filename: &str, labels: &str) -> io::Result<()> {
+    let tmp_filename = format!("{}.tmp", filename);
+    std::fs::write(&tmp_filename, render_metrics(labels))?;
+    std::fs::rename(&tmp_filename, filename)
+}
+
+// 后台定时刷新 metrics 文件的线程；finish 时停止线程并写入最终值
+struct MetricsTicker {
+    filename: String,
+    labels: String,
+    stop: mpsc::Sender<()>,
+    handle: thread::JoinHandle<()>,
+}
+
+impl MetricsTicker {
+    fn start(filename: &str, labels: &str, interval: Duration) -> MetricsTicker {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread_filename = filename.to_string();
+        let thread_labels = labels.to_string();
+        let handle = thread::spawn(move || {
+            while let Err(mpsc::RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                if let Err(e) = write_metrics_file(&thread_filename, &thread_labels) {
+                    warn("metrics_write_failed", tr!("写入指标文件出错：{}", "error writing the metrics file: {}", e));
+                }
+            }
+        });
+        MetricsTicker { filename: filename.to_string(), labels: labels.to_string(), stop, handle }
+    }
+
+    fn finish(self) -> io::Result<()> {
+        drop(self.stop);
+        let _ = self.handle.join();
+        write_metrics_file(&self.filename, &self.labels)
+    }
+}
+
+impl ReplaceOptions {
+    // 由命令行参数填充；--contig-offset、--order-by-score 和 --liftover 给出的文件在这里读取
+    fn from_matches(matches: &clap::ArgMatches) -> io::Result<ReplaceOptions> {
+        let output_file = matches.value_of("output_file").unwrap_or("output.fasta");
+        // --format 可以列出多种格式（逗号分隔），各自写到 --<格式>-out；列出的第一种格式（默认 fasta）默认写到 --output。
+        // 给出 --<格式>-out 时也会输出该格式
+        let mut output_formats: Vec<OutputFormat> = matches
+            .values_of("format")
+            .map(|values| values.filter_map(OutputFormat::parse).collect())
+            .unwrap_or_else(|| vec![OutputFormat::default()]);
+        for format in [OutputFormat::Fasta, OutputFormat::MaskedDiff, OutputFormat::Tsv, OutputFormat::Json, OutputFormat::Jsonl, OutputFormat::Fastq] {
+            if matches.is_present(format.out_arg()) && !output_formats.contains(&format) {
+                output_formats.push(format);
+            }
+        }
+        let mut outputs = vec![];
+        for (index, &format) in output_formats.iter().enumerate() {
+            let filename = match matches.value_of(format.out_arg()) {
+                Some(filename) => filename,
+                None if index == 0 => output_file,
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        tr!(
+                            "--format 包含 {0} 时需要用 --{0}-out 指定其输出路径",
+                            "--format includes {0}, so --{0}-out must give its output path",
+                            format.name(),
+                        ),
+                    ));
+                }
+            };
+            outputs.push((format, filename.to_string()));
+        }
+        // --variant-set name=path：集合名不能为空或重复
+        let mut variant_sets: Vec<(String, String)> = vec![];
+        for pair in matches.values_of("variant_set").into_iter().flatten() {
+            let Some((name, path)) = pair.split_once('=').filter(|(name, path)| !name.is_empty() && !path.is_empty()) else {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, tr!(
+                    "--variant-set 需要 name=path 格式：{}",
+                    "--variant-set needs name=path: {}",
+                    pair,
+                )));
+            };
+            if variant_sets.iter().any(|(existing, _)| existing == name) {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, tr!(
+                    "--variant-set 集合名重复：{}",
+                    "duplicate --variant-set name: {}",
+                    name,
+                )));
+            }
+            variant_sets.push((name.to_string(), path.to_string()));
+        }
+        let wrap: usize = match matches.value_of("wrap") {
+            Some(value) => value
+                .parse()
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, tr!(
+                    "--wrap 需要非负整数：{}",
+                    "--wrap needs a non-negative integer: {}",
+                    value,
+                )))?,
+            None => 60,
+        };
+        let min_coverage: f64 = parse_number_arg(
+            matches,
+            "min_coverage",
+            |&coverage: &f64| coverage.is_finite() && coverage >= 0.0,
+            tr!("--min-coverage 需要非负数", "--min-coverage needs a non-negative number"),
+        )?
+        .unwrap_or(1.0);
+        let mut options = ReplaceOptions {
+            index_in: matches.value_of("index_in").map(|f| f.to_string()),
+            index_out: matches.value_of("index_out").map(|f| f.to_string()),
+            n_report: matches.value_of("n_report").map(|f| f.to_string()),
+            max_n_fraction: matches.value_of("max_n_fraction").map(parse_n_fraction).transpose()?,
+            max_memory: matches.value_of("max_memory").map(parse_byte_size).transpose()?,
+            only_strand: matches.value_of("only_strand").and_then(Strand::parse),
+            collapse_ambiguity: matches.is_present("collapse_ambiguity"),
+            check_output: matches.value_of("check_output") != Some("off"),
+            dedup_genes: matches.value_of("dedup_genes").and_then(DedupGenes::parse).unwrap_or_default(),
+            strict: matches.is_present("strict"),
+            gap_aware: matches.is_present("gap_aware"),
+            het_as_ambiguity: matches.is_present("het_as_ambiguity"),
+            variant_sets,
+            verify_output: matches.is_present("verify_output"),
+            provenance: !matches.is_present("no_provenance"),
+            exclude_boundary: matches.is_present("exclude_boundary"),
+            forward_minus: matches.is_present("no_revcomp_minus"),
+            mutated_quality: parse_number_arg(
+                matches,
+                "mutated_quality",
+                |&quality: &u8| quality <= 93,
+                tr!("--mutated-quality 需要 0-93 之间的整数", "--mutated-quality needs an integer from 0 to 93"),
+            )?
+            .unwrap_or(20),
+            wrap,
+            liftover_mutations: matches.is_present("liftover_mutations"),
+            flush_every: parse_number_arg(
+                matches,
+                "flush_every",
+                |&every: &usize| every > 0,
+                tr!("--flush-every 需要正整数", "--flush-every needs a positive integer"),
+            )?,
+            ignore_missing_contigs: matches.is_present("ignore_missing_contigs"),
+            dropped_genes: matches.value_of("dropped_genes").map(|f| f.to_string()),
+            strategy: matches.value_of("strategy").and_then(Strategy::parse).unwrap_or_default(),
+            sort_by: matches.value_of("sort_by").and_then(SortBy::parse).unwrap_or_default(),
+            outputs,
+            coverage: matches.value_of("coverage").map(|coverage_file| (coverage_file.to_string(), min_coverage)),
+            positions_sqlite: matches.value_of("positions_sqlite").map(|database| {
+                let query = matches
+                    .value_of("positions_query")
+                    .unwrap_or(DEFAULT_POSITIONS_QUERY);
+                (database.to_string(), query.to_string())
+            }),
+            parse: ParseOptions {
+                zero_based_genes: matches.value_of("coord_base") == Some("0"),
+                lenient_numbers: matches.is_present("lenient_numbers"),
+                vcf_first_alt: matches.value_of("vcf_multiallelic") == Some("first"),
+            },
+            ..ReplaceOptions::default()
+        };
+        if let Some(offsets_file) = matches.value_of("contig_offset") {
+            options.contig_offsets = read_contig_offsets(offsets_file)?;
+        }
+        if let Some(scores_file) = matches.value_of("order_by_score") {
+            options.gene_scores = Some(read_gene_scores(scores_file)?);
+        }
+        if let Some(chain_file) = matches.value_of("liftover") {
+            options.liftover = Some(Liftover::read(chain_file)?);
+        }
+        options.remote = RemoteOptions {
+            contigs_fai: matches.value_of("contigs_fai").map(|f| f.to_string()),
+            expect_sha256: matches
+                .values_of("expect_sha256")
+                .map(|values| {
+                    values
+                        .filter_map(|pair| pair.rsplit_once('='))
+                        .map(|(url, hex)| (url.to_string(), hex.to_string()))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            retries: parse_number_arg(
+                matches,
+                "remote_retries",
+                |_| true,
+                tr!("--remote-retries 需要非负整数", "--remote-retries needs a non-negative integer"),
+            )?
+            .unwrap_or(3),
+            retry_backoff: Duration::from_millis(
+                parse_number_arg(
+                    matches,
+                    "remote_backoff_ms",
+                    |_| true,
+                    tr!("--remote-backoff-ms 需要非负整数", "--remote-backoff-ms needs a non-negative integer"),
+                )?
+                .unwrap_or(500),
+            ),
+        };
+        Ok(options)
+    }
+}
+
+// 写入来源信息的生效配置：影响结果的选项都取解析后的值（未给出的为默认值），键为命令行的长选项名；
+// 其余给出的参数（报告路径等）按给出的值附在后面
+fn effective_configuration(matches: &clap::ArgMatches, options: &ReplaceOptions, num_threads: usize) -> Vec<(&'static str, String)> {
+    let flag = |value: bool| value.to_string();
+    let optional = |value: Option<String>| value.unwrap_or_else(|| "none".to_string());
+    let mutations: Vec<&str> = ["mutations_file", "extra_mutations", "vcf"]
+        .into_iter()
+        .flat_map(|name| matches.values_of(name).into_iter().flatten())
+        .collect();
+    let mut configuration = vec![
+        ("contigs", matches.value_of("contigs_file").unwrap_or_default().to_string()),
+        ("mutations", mutations.join(",")),
+        ("positions", matches.value_of("gene_positions_file").unwrap_or_default().to_string()),
+        ("threads", num_threads.to_string()),
+        ("lang", if ENGLISH_MESSAGES.load(Ordering::Relaxed) { "en" } else { "zh" }.to_string()),
+        ("outputs", options.outputs.iter().map(|(format, path)| format!("{}={}", format.name(), path)).collect::<Vec<_>>().join(",")),
+        ("wrap", options.wrap.to_string()),
+        ("sort-by", options.sort_by.name().to_string()),
+        ("strategy", options.strategy.name().to_string()),
+        ("dedup-genes", options.dedup_genes.name().to_string()),
+        ("only-strand", optional(options.only_strand.map(|strand| strand.as_str().to_string()))),
+        ("revcomp-minus", flag(!options.forward_minus)),
+        ("coord-base", if options.parse.zero_based_genes { "0" } else { "1" }.to_string()),
+        ("lenient-numbers", flag(options.parse.lenient_numbers)),
+        ("vcf-multiallelic", if options.parse.vcf_first_alt { "first" } else { "all" }.to_string()),
+        ("strict", flag(options.strict)),
+        ("ignore-missing-contigs", flag(options.ignore_missing_contigs)),
+        ("check-output", if options.check_output { "on" } else { "off" }.to_string()),
+        ("verify-output", flag(options.verify_output)),
+        ("collapse-ambiguity", flag(options.collapse_ambiguity)),
+        ("gap-aware", flag(options.gap_aware)),
+        ("het-as-ambiguity", flag(options.het_as_ambiguity)),
+        ("exclude-boundary", flag(options.exclude_boundary)),
+        ("liftover-mutations", flag(options.liftover_mutations)),
+        ("mutated-quality", options.mutated_quality.to_string()),
+        ("flush-every", optional(options.flush_every.map(|every| every.to_string()))),
+        ("max-n-fraction", optional(options.max_n_fraction.map(|fraction| fraction.to_string()))),
+        ("max-memory", optional(options.max_memory.map(|bytes| bytes.to_string()))),
+        ("coverage", optional(options.coverage.as_ref().map(|(path, _)| path.clone()))),
+        ("min-coverage", optional(options.coverage.as_ref().map(|(_, min_coverage)| min_coverage.to_string()))),
+        ("remote-retries", options.remote.retries.to_string()),
+        ("remote-backoff-ms", options.remote.retry_backoff.as_millis().to_string()),
+    ];
+    if let Some((database, query)) = &options.positions_sqlite {
+        configuration.push(("positions-sqlite", database.clone()));
+        configuration.push(("positions-query", query.clone()));
+    }
+    // 参数名为长选项名中的 '-' 换成 '_'
+    for key in [
+        "variant-set", "liftover", "contig-offset", "order-by-score", "contigs-fai", "index-in", "index-out",
+        "n-report", "dropped-genes", "applied-vcf", "track-bed", "ts-tv-report", "id-map", "edited-coords", "summary",
+        "stats", "length-histogram", "histogram-bin-width", "sqlite", "report-parquet", "concatenate", "partition-file",
+        "split-output-tar", "group-by", "group-dir", "translate", "six-frame", "paired-nt-aa", "genetic-code",
+        "internal-stop", "strand-relative", "codon-align", "canonical-only", "sqlite-sequences", "expect-sha256",
+    ] {
+        let name = key.replace('-', "_");
+        if let Some(values) = matches.values_of(&name) {
+            configuration.push((key, values.collect::<Vec<_>>().join(",")));
+        } else if matches.is_present(&name) {
+            configuration.push((key, flag(true)));
+        }
+    }
+    configuration
+}
+
+// 命令行参数的定义
+fn cli_app() -> App<'static, 'static> {
+    App::new("z10")
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .subcommand(SubCommand::with_name("diff")
+            .about("逐个基因比较两次运行输出的 FASTA")
+            .arg(Arg::with_name("first")
+                .required(true)
+                .index(1)
+                .help("第一个 FASTA"))
+            .arg(Arg::with_name("second")
+                .required(true)
+                .index(2)
+                .help("第二个 FASTA"))
+            .arg(Arg::with_name("output_file")
+                .required(false)
+                .takes_value(true)
+                .short("o")
+                .long("output")
+                .help("比较结果（TSV）的输出路径，默认写到标准输出")))
+        .arg(Arg::with_name("contigs_file")
+            .required(true)
+            .takes_value(true)
+            .index(1)
+            .help("Contigs 文件路径"))
+        .arg(Arg::with_name("mutations_file")
+            .required(true)
+            .takes_value(true)
+            .index(2)
+            .help("突变信息文件路径"))
+        .arg(Arg::with_name("gene_positions_file")
+            .required_unless("positions_sqlite")
+            .takes_value(true)
+            .index(3)
+            .help("基因位置文件路径"))
+        .arg(Arg::with_name("output_file")
+            .required(false)
+            .takes_value(true)
+            .short("o")
+            .long("output")
+            .help("输出文件路径"))
+        .arg(Arg::with_name("format")
+            .required(false)
+            .takes_value(true)
+            .long("format")
+            .use_delimiter(true)
+            .possible_values(&["fasta", "masked-diff", "tsv", "json", "jsonl", "fastq"])
+            .help("输出格式，可用逗号分隔多种格式一次输出：fasta 为突变后的序列，masked-diff 中未改变的位置为 '.'，改变的位置为新碱基，tsv/json 为每个基因的坐标、突变数和序列，jsonl 为每行一个基因的 json 对象，fastq 为序列加质量行（默认 fasta）"))
+        .arg(Arg::with_name("fasta_out")
+            .required(false)
+            .takes_value(true)
+            .long("fasta-out")
+            .help("fasta 格式的输出路径"))
+        .arg(Arg::with_name("masked_diff_out")
+            .required(false)
+            .takes_value(true)
+            .long("masked-diff-out")
+            .help("masked-diff 格式的输出路径"))
+        .arg(Arg::with_name("tsv_out")
+            .required(false)
+            .takes_value(true)
+            .long("tsv-out")
+            .help("tsv 格式的输出路径"))
+        .arg(Arg::with_name("json_out")
+            .required(false)
+            .takes_value(true)
+            .long("json-out")
+            .help("json 格式的输出路径"))
+        .arg(Arg::with_name("jsonl_out")
+            .required(false)
+            .takes_value(true)
+            .long("jsonl-out")
+            .help("jsonl 格式的输出路径"))
+        .arg(Arg::with_name("fastq_out")
+            .required(false)
+            .takes_value(true)
+            .long("fastq-out")
+            .help("fastq 格式的输出路径"))
+        .arg(Arg::with_name("wrap")
+            .required(false)
+            .takes_value(true)
+            .long("wrap")
+            .visible_alias("line-width")
+            .help("FASTA 和 masked-diff 输出的序列每行的字符数（默认 60，0 表示不折行）"))
+        .arg(Arg::with_name("mutated_quality")
+            .required(false)
+            .takes_value(true)
+            .long("mutated-quality")
+            .help("fastq 中被突变改变的位置的 Phred 质量（0-93，默认 20；未改变的位置为 40），超出范围时报错"))
+        .arg(Arg::with_name("coverage")
+            .required(false)
+            .takes_value(true)
+            .long("coverage")
+            .help("覆盖度轨道（bedGraph：chrom start end value），覆盖度低于 --min-coverage 或没有记录的位置在输出中屏蔽为 N"))
+        .arg(Arg::with_name("min_coverage")
+            .required(false)
+            .takes_value(true)
+            .long("min-coverage")
+            .requires("coverage")
+            .help("--coverage 的最低覆盖度（默认 1）"))
+        .arg(Arg::with_name("gap_aware")
+            .required(false)
+            .long("gap-aware")
+            .help("参考序列中的 '-' 视为比对空位：基因和突变坐标只数碱基、不数空位，突变不会改写空位，基因内部的空位保留在输出中"))
+        .arg(Arg::with_name("het_as_ambiguity")
+            .required(false)
+            .long("het-as-ambiguity")
+            .conflicts_with("collapse_ambiguity")
+            .help("突变文件第5列基因型为杂合（如 0/1、0/0/1/1）的位点输出为参考碱基和替换碱基的 IUPAC 简并碱基（如 A/G -> R）"))
+        .arg(Arg::with_name("liftover")
+            .required(false)
+            .takes_value(true)
+            .long("liftover")
+            .conflicts_with("contig_lengths")
+            .help("UCSC chain 文件：提取之前把基因坐标从注释所在的组装（target）换算到参考组装（query），无法换算的基因跳过并报告"))
+        .arg(Arg::with_name("liftover_mutations")
+            .required(false)
+            .long("liftover-mutations")
+            .requires("liftover")
+            .help("突变坐标也与基因一样通过 --liftover 换算（默认突变已经是参考组装的坐标）"))
+        .arg(Arg::with_name("order_by_score")
+            .required(false)
+            .takes_value(true)
+            .long("order-by-score")
+            .help("gene_id,score 对应表：主输出的记录按分数从高到低排列，没有分数的基因排在最后（默认 tsv/json 按基因ID）"))
+        .arg(Arg::with_name("no_revcomp_minus")
+            .required(false)
+            .long("no-revcomp-minus")
+            .help("主输出中负链基因也按基因组正链方向输出（默认负链基因写为反向互补序列，按基因 5'->3' 方向）"))
+        .arg(Arg::with_name("revcomp_minus")
+            .required(false)
+            .long("revcomp-minus")
+            .conflicts_with("no_revcomp_minus")
+            .hidden(true)
+            .help("负链基因写为反向互补序列（已是默认行为，保留以兼容旧的命令行）"))
+        .arg(Arg::with_name("exclude_boundary")
+            .required(false)
+            .long("exclude-boundary")
+            .help("不应用恰好落在基因第一个或最后一个碱基上的突变（如对剪接位点敏感的分析；默认基因两端的突变都会应用）"))
+        .arg(Arg::with_name("verify_output")
+            .required(false)
+            .long("verify-output")
+            .help("写完后重新读取每个主输出，核对记录数、大小和 BLAKE3 与写出时一致（发现截断等问题时报错）"))
+        .arg(Arg::with_name("flush_every")
+            .required(false)
+            .takes_value(true)
+            .long("flush-every")
+            .help("主输出每写 N 条记录刷新一次，直接写到最终路径而不是先写临时文件，便于下游边写边读（默认只在最后刷新）"))
+        .arg(Arg::with_name("variant_set")
+            .required(false)
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .long("variant-set")
+            .conflicts_with_all(&["applied_vcf", "track_bed", "ts_tv_report", "codon_align", "sqlite", "report_parquet"])
+            .help("命名的突变集合 name=path（可重复）：每个集合与位置参数给出的突变合并后单独应用一次，输出记录为 {gene}__{name}"))
+        .arg(Arg::with_name("strategy")
+            .required(false)
+            .takes_value(true)
+            .long("strategy")
+            .possible_values(&["buffer", "merge-join"])
+            .help("应用突变的方式：buffer 复制整个 contig 后原地修改，merge-join 按位置归并扫描已排序的突变和基因区间，内存只与同时重叠的基因数有关（默认 buffer）"))
+        .arg(Arg::with_name("sort_by")
+            .required(false)
+            .takes_value(true)
+            .long("sort-by")
+            .visible_alias("sort")
+            .possible_values(&["position", "gene", "id", "input"])
+            .help("主输出中记录的顺序：position 按 contig ID 和起点，gene（或 id）按基因ID，input 按位置文件中的顺序（默认 position）；\
+                   同样的输入总是得到逐字节相同的输出"))
+        .arg(Arg::with_name("strict")
+            .required(false)
+            .long("strict")
+            .help("输入有问题时报错退出而不是只给出警告（目前：基因引用了 FASTA 中不存在的 contig；基因行的区间无效或超出 contig 范围；\
+                   突变的参考碱基与 contig 不一致）"))
+        .arg(Arg::with_name("ignore_missing_contigs")
+            .required(false)
+            .long("ignore-missing-contigs")
+            .help("--strict 下仍把基因引用了 FASTA 中不存在的 contig 视为警告（注释覆盖的 replicon 多于当前组装时使用）"))
+        .arg(Arg::with_name("dropped_genes")
+            .required(false)
+            .takes_value(true)
+            .long("dropped-genes")
+            .help("输出未输出的基因及原因（TSV：gene_id、contig、start、end、reason）"))
+        .arg(Arg::with_name("dedup_genes")
+            .required(false)
+            .takes_value(true)
+            .long("dedup-genes")
+            .possible_values(&["first", "longest", "none", "locus"])
+            .help("同一 gene_id 出现多次时：first 在每个 contig 上保留第一次出现的记录，longest 保留区间最长的（出现在多个 contig 上时按 locus 改名），none 全部输出（重复的记为 gene_id_2、gene_id_3 ...），locus 全部输出并记为 gene_id_contig_start_end（默认 first）"))
+        .arg(Arg::with_name("check_output")
+            .required(false)
+            .takes_value(true)
+            .long("check-output")
+            .possible_values(&["on", "off"])
+            .default_value("on")
+            .help("写出时检查输出 FASTA：ID 唯一、非空、不含空白，序列非空且只含 IUPAC 碱基，折行长度一致；不通过时不生成输出文件"))
+        .arg(Arg::with_name("only_strand")
+            .required(false)
+            .takes_value(true)
+            .long("only-strand")
+            .possible_values(&["+", "-"])
+            .allow_hyphen_values(true)
+            .help("只对该链上的基因应用突变，其他基因输出参考序列（需要位置文件第5列给出链）"))
+        .arg(Arg::with_name("collapse_ambiguity")
+            .required(false)
+            .long("collapse-ambiguity")
+            .help("输出前将序列中的非 ACGT 碱基（如 IUPAC 简并碱基）替换为参考碱基，并报告替换的位点数"))
+        .arg(Arg::with_name("strand_relative")
+            .required(false)
+            .long("strand-relative")
+            .help("突变报告（applied VCF 的 INFO、BED 轨道、Parquet）中负链基因的位置按 5'->3' 计算，ref/alt 使用互补碱基"))
+        .arg(Arg::with_name("no_provenance")
+            .required(false)
+            .long("no-provenance")
+            .help("不在报告、VCF 和 JSON 输出中写入来源信息（版本、命令行、输入文件哈希、起止时间）"))
+        .arg(Arg::with_name("provenance_sidecar")
+            .required(false)
+            .long("provenance-sidecar")
+            .conflicts_with("no_provenance")
+            .help("在输出 FASTA 旁写出 <输出>.provenance.json"))
+        .arg(Arg::with_name("vcf")
+            .required(false)
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .long("vcf")
+            .help("额外的 VCF 突变文件（可重复），读取 CHROM、POS、ID、REF、ALT 和第一个样本的 GT；任何突变文件都按内容自动识别 VCF"))
+        .arg(Arg::with_name("vcf_multiallelic")
+            .required(false)
+            .takes_value(true)
+            .long("vcf-multiallelic")
+            .possible_values(&["error", "first"])
+            .help("VCF 中 ALT 含多个等位基因时的处理：error 报错退出（默认），first 只应用第一个 ALT"))
+        .arg(Arg::with_name("lang")
+            .required(false)
+            .takes_value(true)
+            .long("lang")
+            .possible_values(&["en", "zh"])
+            .help("运行中输出的消息、警告和错误的语言（默认根据 LC_ALL/LC_MESSAGES/LANG 选择，zh、C 或未设置时为中文）；--help 仍为中文"))
+        .arg(Arg::with_name("verbose")
+            .required(false)
+            .long("verbose")
+            .help("汇总过的警告（如基因引用了不存在的 contig）在标准错误上也逐项列出；默认只输出一条汇总，逐项的内容写入 --warnings-json"))
+        .arg(Arg::with_name("coord_base")
+            .required(false)
+            .takes_value(true)
+            .long("coord-base")
+            .possible_values(&["0", "1"])
+            .default_value("1")
+            .help("基因位置文件中起点和终点的坐标约定：1 为 1-based 闭区间（默认），0 为 0-based 半开区间（如 BED）；\
+                   解析后统一转为 1-based，输出中的坐标仍为 1-based，突变位置不受影响"))
+        .arg(Arg::with_name("lenient_numbers")
+            .required(false)
+            .long("lenient-numbers")
+            .help("接受带千分位逗号或下划线的坐标（如 \"1,234,567\"），每个被修正的值以 debug 级别记入 --trace-json"))
+        .arg(Arg::with_name("gene_contigs_file")
+            .required(false)
+            .takes_value(true)
+            .short("gc")
+            .long("gene-contigs")
+            .hidden(true) // 已不使用，保留以兼容旧的命令行
+            .help("基因和contigs对应关系文件路径"))
+        .arg(Arg::with_name("num_threads")
+            .required(false)
+            .takes_value(true)
+            .short("t")
+            .long("num_threads")
+            .help("线程数"))
+        .arg(Arg::with_name("applied_vcf")
+            .required(false)
+            .takes_value(true)
+            .long("applied-vcf")
+            .help("输出实际应用的突变的 VCF 文件路径"))
+        .arg(Arg::with_name("length_histogram")
+            .required(false)
+            .takes_value(true)
+            .long("length-histogram")
+            .help("输出基因长度分布直方图文件路径"))
+        .arg(Arg::with_name("histogram_bin_width")
+            .required(false)
+            .takes_value(true)
+            .long("histogram-bin-width")
+            .help("长度直方图的分箱宽度（默认 100）"))
+        .arg(Arg::with_name("timings")
+            .required(false)
+            .long("timings")
+            .help("输出各阶段耗时和最大单次内存分配"))
+        .arg(Arg::with_name("extra_mutations")
+            .required(false)
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .long("mutations")
+            .help("额外的突变信息文件路径（可重复），内容完全相同的文件只读入一次"))
+        .arg(Arg::with_name("metrics_file")
+            .required(false)
+            .takes_value(true)
+            .long("metrics-file")
+            .help("定期写入 Prometheus 文本格式计数器的文件路径"))
+        .arg(Arg::with_name("metrics_interval")
+            .required(false)
+            .takes_value(true)
+            .long("metrics-interval")
+            .help("metrics 文件刷新间隔秒数（默认 15）"))
+        .arg(Arg::with_name("metrics_label")
+            .required(false)
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .long("metrics-label")
+            .help("附加到 run_info 的标签 key=value（可重复）"))
+        .arg(Arg::with_name("index_out")
+            .required(false)
+            .takes_value(true)
+            .long("index-out")
+            .help("将解析后的突变索引写入该文件，供后续运行复用"))
+        .arg(Arg::with_name("index_in")
+            .required(false)
+            .takes_value(true)
+            .long("index-in")
+            .help("读取预先构建的突变索引（来源文件哈希不一致时重新解析）"))
+        .arg(Arg::with_name("stats")
+            .required(false)
+            .takes_value(true)
+            .long("stats")
+            .help("将突变去向统计（读取数、落在基因区间内、已应用、基因间、contig 缺失、超出范围等）以 JSON 写入该文件；\
+                   同样的统计总会输出到标准错误"))
+        .arg(Arg::with_name("n_report")
+            .required(false)
+            .takes_value(true)
+            .long("n-report")
+            .help("输出每个 contig 的 N 碱基数量和比例报告文件路径"))
+        .arg(Arg::with_name("max_n_fraction")
+            .required(false)
+            .takes_value(true)
+            .long("max-n-fraction")
+            .help("N 碱基比例上限（0-1 或百分比，如 0.05 或 5%），超过的 contig 及其基因将被排除"))
+        .arg(Arg::with_name("trace_json")
+            .required(false)
+            .takes_value(true)
+            .long("trace-json")
+            .help("以换行分隔的 JSON 写出各阶段和每个 contig 的 tracing span（含耗时）"))
+        .arg(Arg::with_name("warnings_json")
+            .required(false)
+            .takes_value(true)
+            .long("warnings-json")
+            .help("把每个警告写成一行 JSON 对象（type、message、contig、gene、position）到该文件；标准错误仍输出可读的警告"))
+        .arg(Arg::with_name("events")
+            .required(false)
+            .takes_value(true)
+            .long("events")
+            .help("运行过程中以 JSON Lines 输出进度事件，\"-\" 表示输出到标准错误"))
+        .arg(Arg::with_name("sqlite")
+            .required(false)
+            .takes_value(true)
+            .long("sqlite")
+            .help("将基因、实际应用的突变和运行信息写入 SQLite 数据库（需要 sqlite feature）"))
+        .arg(Arg::with_name("sqlite_sequences")
+            .required(false)
+            .long("sqlite-sequences")
+            .help("在 SQLite 的 genes 表中同时保存突变后的基因序列"))
+        .arg(Arg::with_name("report_parquet")
+            .required(false)
+            .takes_value(true)
+            .long("report-parquet")
+            .help("将实际应用的突变报告写为 Parquet 文件（需要 arrow feature）"))
+        .arg(Arg::with_name("six_frame")
+            .required(false)
+            .takes_value(true)
+            .long("six-frame")
+            .help("输出每个基因六个读码框翻译结果的 FASTA 文件路径"))
+        .arg(Arg::with_name("paired_nt_aa")
+            .required(false)
+            .takes_value(true)
+            .long("paired-nt-aa")
+            .help("每个基因输出相邻的 {gene}_nt 核苷酸记录和 {gene}_aa 蛋白记录（按基因的链翻译）到该文件"))
+        .arg(Arg::with_name("translate")
+            .required(false)
+            .takes_value(true)
+            .long("translate")
+            .help("每个基因按其所在的链翻译为蛋白序列，写到该 FASTA 文件（记录名为基因ID）"))
+        .arg(Arg::with_name("genetic_code")
+            .required(false)
+            .takes_value(true)
+            .long("genetic-code")
+            .visible_alias("codon-table")
+            .help("翻译使用的 NCBI 遗传密码表编号（默认 1，细菌为 11），作用于 --translate、--six-frame 和 --paired-nt-aa"))
+        .arg(Arg::with_name("codon_align")
+            .required(false)
+            .takes_value(true)
+            .long("codon-align")
+            .help("每个基因输出按密码子以空格分隔的参考序列 {gene}_ref 和突变后序列 {gene}_mut（按基因的链）到该文件"))
+        .arg(Arg::with_name("canonical_only")
+            .required(false)
+            .long("canonical-only")
+            .requires("six_frame")
+            .help("--six-frame 只输出基因注释的读码框（按基因的链，从第一个碱基起读），不输出其他五个推测的读码框"))
+        .arg(Arg::with_name("positions_sqlite")
+            .required(false)
+            .takes_value(true)
+            .long("positions-sqlite")
+            .help("从 SQLite 数据库读取基因位置（替代基因位置文件，需要 sqlite feature）"))
+        .arg(Arg::with_name("positions_query")
+            .required(false)
+            .takes_value(true)
+            .long("positions-query")
+            .requires("positions_sqlite")
+            .help("读取基因位置的查询，须返回 contig、gene_id、start、end 四列，可加第5列 strand（默认 SELECT contig, gene_id, start, end FROM genes）"))
+        .arg(Arg::with_name("contigs_fai")
+            .required(false)
+            .takes_value(true)
+            .long("contigs-fai")
+            .help("FASTA 的 .fai 索引（samtools faidx），提供时只读取有基因的 contig：本地 FASTA 按偏移随机读取（须未压缩），\
+                   远程 FASTA 给出索引 URL 并按字节范围下载（需要 remote feature）"))
+        .arg(Arg::with_name("expect_sha256")
+            .required(false)
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .long("expect-sha256")
+            .help("远程输入的期望 SHA-256，格式 URL=HEX（可重复）"))
+        .arg(Arg::with_name("remote_retries")
+            .required(false)
+            .takes_value(true)
+            .long("remote-retries")
+            .help("网络请求失败时的重试次数（默认 3）"))
+        .arg(Arg::with_name("remote_backoff_ms")
+            .required(false)
+            .takes_value(true)
+            .long("remote-backoff-ms")
+            .help("第一次重试前的等待毫秒数，之后每次翻倍（默认 500）"))
+        .arg(Arg::with_name("concatenate")
+            .required(false)
+            .takes_value(true)
+            .long("concatenate")
+            .help("将所有基因按ID排序拼接成一条超级基因并写入该 FASTA 文件"))
+        .arg(Arg::with_name("partition_file")
+            .required(false)
+            .takes_value(true)
+            .long("partition-file")
+            .requires("concatenate")
+            .help("超级基因分区文件路径（默认 <concatenate>.partitions.tsv）"))
+        .arg(Arg::with_name("internal_stop")
+            .required(false)
+            .takes_value(true)
+            .long("internal-stop")
+            .possible_values(&["translate-through", "truncate", "mark", "skip"])
+            .help("翻译中遇到内部终止密码子时的处理方式（默认 translate-through）"))
+        .arg(Arg::with_name("track_bed")
+            .required(false)
+            .takes_value(true)
+            .long("track-bed")
+            .help("以基因组坐标输出实际应用突变的 BED 轨道文件路径"))
+        .arg(Arg::with_name("ts_tv_report")
+            .required(false)
+            .takes_value(true)
+            .long("ts-tv-report")
+            .help("输出实际应用的替换中转换（A<->G、C<->T）与颠换的计数及 Ts/Tv 比值（总体和每个基因，TSV）"))
+        .arg(Arg::with_name("id_map")
+            .required(false)
+            .takes_value(true)
+            .long("id-map")
+            .help("输出记录ID与原始基因ID、contig、起止位置、链的对应表（TSV）"))
+        .arg(Arg::with_name("edited_coords")
+            .required(false)
+            .takes_value(true)
+            .long("edited-coords")
+            .help("输出每个基因的参考起止位置及应用插入/缺失之后的起止位置（TSV）"))
+        .arg(Arg::with_name("summary")
+            .required(false)
+            .takes_value(true)
+            .long("summary")
+            .help("输出每个基因的坐标、长度和实际应用的突变数（TSV）"))
+        .arg(Arg::with_name("split_output_tar")
+            .required(false)
+            .takes_value(true)
+            .long("split-output-tar")
+            .help("将每个基因写成单独的 FASTA 条目并打包为 tar 归档（.gz 结尾时压缩）"))
+        .arg(Arg::with_name("group_by")
+            .required(false)
+            .takes_value(true)
+            .long("group-by")
+            .help("gene_id,category 对应表，按类别把基因写成 <类别>.fasta，不在表中的基因写入 ungrouped.fasta"))
+        .arg(Arg::with_name("group_dir")
+            .required(false)
+            .takes_value(true)
+            .long("group-dir")
+            .requires("group_by")
+            .help("--group-by 的输出目录（默认 groups）"))
+        .arg(Arg::with_name("max_memory")
+            .required(false)
+            .takes_value(true)
+            .long("max-memory")
+            .help("内存软上限（字节，可用 K/M/G/T 后缀，如 4G、4GB、1.5G），预计超过时提前报错退出；无法解析时报错"))
+        .arg(Arg::with_name("contig_lengths")
+            .required(false)
+            .takes_value(true)
+            .long("contig-lengths")
+            .help("只根据 contig<TAB>长度 清单（如 .fai）校验基因和突变坐标，不读取 contigs FASTA，也不输出序列"))
+        .arg(Arg::with_name("check")
+            .required(false)
+            .long("check")
+            .help("只解析输入并校验基因和突变坐标（contig 是否存在、是否超出 contig 长度、记录能否解析），报告问题后退出，不写输出；\
+                   有问题时以非零状态退出"))
+        .arg(Arg::with_name("contig_offset")
+            .required(false)
+            .takes_value(true)
+            .long("contig-offset")
+            .help("contig 坐标偏移文件路径（每行 contig_id,offset，偏移量可为负）"))
+}
+
+/// Formats an error returned by [`run_cli`] as the one-line message the binary prints.
+#[doc(hidden)]
+pub fn render_error(err: &io::Error) -> String {
+    tr!("错误：{}", "error: {}", err)
+}
+
+/// Runs the command-line tool with the process arguments.
+#[doc(hidden)]
+pub fn run_cli() -> io::Result<()> {
+    let matches = cli_app().get_matches();
+
+    let english = match matches.value_of("lang") {
+        Some(lang) => lang == "en",
+        None => english_from_locale(),
+    };
+    ENGLISH_MESSAGES.store(english, Ordering::Relaxed);
+
+    if let Some(diff_matches) = matches.subcommand_matches("diff") {
+        return run_diff(diff_matches);
+    }
+
+    let warnings = matches.value_of("warnings_json").map(File::create).transpose()?.map(io::LineWriter::new);
+    *CLI_RUN.lock().unwrap() = Some(CliRun { warnings, verbose: matches.is_present("verbose"), inputs: vec![] });
+
+    let missing_argument = |message: &str| io::Error::new(io::ErrorKind::InvalidInput, message);
+    let contigs_file = matches.value_of("contigs_file").ok_or_else(|| missing_argument(&tr!(
+        "未提供 Contigs 文件路径！",
+        "no contigs file given!",
+    )))?;
+
+    let mutations_file = matches.value_of("mutations_file").ok_or_else(|| missing_argument(&tr!(
+        "未提供突变信息文件路径！",
+        "no mutations file given!",
+    )))?;
+    let mut mutations_files = vec![mutations_file];
+    if let Some(extra_files) = matches.values_of("extra_mutations") {
+        mutations_files.extend(extra_files);
+    }
+    if let Some(vcf_files) = matches.values_of("vcf") {
+        mutations_files.extend(vcf_files);
+    }
+
+    let gene_positions_file = match matches.value_of("gene_positions_file") {
+        Some(gene_positions_file) => gene_positions_file,
+        None if matches.is_present("positions_sqlite") => "",
+        None => return Err(missing_argument(&tr!("未提供基因位置文件路径！", "no gene positions file given!"))),
+    };
+
+    let options = ReplaceOptions::from_matches(&matches)?;
+    if !matches.is_present("contig_lengths") && !matches.is_present("check") {
+        for (format, filename) in &options.outputs {
+            println!("{}", tr!("输出突变基因序列（{}）: {}", "mutated gene sequences ({}): {}", format.name(), filename));
+        }
+    }
+    let num_threads: usize = parse_number_arg(&matches, "num_threads", |_| true, tr!("-t 需要非负整数", "-t needs a non-negative integer"))?
+        .unwrap_or_else(num_cpus::get);
+
+    // 只有指定 --trace-json 时才安装 subscriber，否则 span 只是一次被缓存的禁用检查
+    if let Some(trace_file) = matches.value_of("trace_json") {
+        tracing_subscriber::fmt()
+            .json()
+            .with_span_events(FmtSpan::CLOSE)
+            .with_max_level(tracing::Level::DEBUG)
+            .with_writer(Mutex::new(File::create(trace_file)?))
+            .init();
+    }
+
+    // http(s) 输入先下载到临时文件；远程 FASTA 配合 --contigs-fai 时由 gene_snv_replace_files 按范围读取
+    let mut downloads = Downloads { remote: &options.remote, files: vec![] };
+    let contigs_file = if options.remote.contigs_fai.is_some() || matches.is_present("contig_lengths") {
+        contigs_file.to_string()
+    } else {
+        downloads.resolve(contigs_file)?
+    };
+    let mutations_files = mutations_files
+        .iter()
+        .map(|path| downloads.resolve(path))
+        .collect::<io::Result<Vec<String>>>()?;
+    let mutations_files: Vec<&str> = mutations_files.iter().map(|path| path.as_str()).collect();
+    let gene_positions_file = downloads.resolve(gene_positions_file)?;
+
+    // "-" 表示从标准输入读取，且只能用于一个输入
+    let stdin_inputs = std::iter::once(contigs_file.as_str())
+        .chain(mutations_files.iter().copied())
+        .chain(std::iter::once(gene_positions_file.as_str()))
+        .filter(|path| *path == "-")
+        .count();
+    if stdin_inputs > 1 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            tr!("标准输入（\"-\"）只能用于一个输入文件", "standard input (\"-\") can be used for only one input file"),
+        ));
+    }
+    if stdin_inputs == 1 {
+        start_stdin_drain();
+    }
+
+    if matches.is_present("check") || matches.is_present("contig_lengths") {
+        return run_check(&matches, &options, &contigs_file, &mutations_files, &gene_positions_file, &mut downloads);
+    }
+
+    let metrics_ticker = start_metrics_ticker(&matches)?;
+    let (code, internal_stop_policy) = translation_settings(&matches)?;
+
+    let started_at = unix_timestamp();
+    // 结束时间和输入摘要由 gene_snv_replace_files 在写主输出之前填写
+    let mut run_info = RunInfo {
+        configuration: effective_configuration(&matches, &options, num_threads),
+        started_at,
+        finished_at: started_at,
+        inputs: vec![],
+    };
+    let event_emitter = matches.value_of("events").map(EventEmitter::start).transpose()?;
+    emit_event("run_started", || vec![("config", run_info.configuration_json())]);
+
+    // 调用 gene_snv_replace_files 函数并获取 mutated_genes 的结果
+    let result = gene_snv_replace_files(
+        &contigs_file,
+        &mutations_files,
+        &gene_positions_file,
+        num_threads,
+        &options,
+        &mut run_info,
+    )?;
+
+    if let Some(metrics_ticker) = metrics_ticker {
+        metrics_ticker.finish()?;
+    }
+
+    let provenance = options.provenance.then_some(&run_info);
+    write_reports(&matches, &options, &result, &run_info, code, internal_stop_policy)?;
+    print_run_summary(&matches, &result, num_threads);
+
+    emit_event("run_finished", || {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        let summary = format!(
+            "{{\"genes\":{},\"gene_mutations\":{},\"mutations_applied\":{},\"rejects_missing_contig\":{},\"rejects_out_of_range\":{},\"rejects_non_ascii\":{},\"bytes_written\":{},\"elapsed_seconds\":{}}}",
+            load(&COUNTERS.genes_processed),
+            result.gene_summaries.iter().map(|summary| summary.mutations_applied).sum::<usize>(),
+            load(&COUNTERS.mutations_applied),
+            load(&COUNTERS.rejects_missing_contig),
+            load(&COUNTERS.rejects_out_of_range),
+            load(&COUNTERS.rejects_non_ascii),
+            load(&COUNTERS.bytes_written),
+            unix_timestamp() - started_at
+        );
+        let mut fields = vec![("summary", summary)];
+        if let Some(run_info) = provenance {
+            fields.push(("provenance", run_info.provenance_json()));
+        }
+        fields
+    });
+    if let Some(event_emitter) = event_emitter {
+        event_emitter.finish()?;
+    }
+
+    // 手动刷新 stdout，确保立即显示输出
+    std::io::stdout().flush()?;
+
+    Ok(())
+}
+
+// diff 子命令：逐个基因比较两次运行输出的 FASTA
+fn run_diff(diff_matches: &clap::ArgMatches) -> io::Result<()> {
+    let first = diff_matches.value_of("first").unwrap_or_default();
+    let second = diff_matches.value_of("second").unwrap_or_default();
+    let counts = match diff_matches.value_of("output_file") {
+        Some(output_file) => {
+            let mut writer = BufWriter::new(File::create(output_file)?);
+            let counts = diff_fastas(first, second, &mut writer)?;
+            writer.flush()?;
+            counts
+        }
+        None => diff_fastas(first, second, &mut io::stdout().lock())?,
+    };
+    eprintln!(
+        "{}",
+        tr!(
+            "相同 {}，不同 {}，仅在 {} 中 {}，仅在 {} 中 {}",
+            "identical {}, different {}, only in {} {}, only in {} {}",
+            counts.identical,
+            counts.different,
+            first,
+            counts.only_first,
+            second,
+            counts.only_second,
+        )
+    );
+    Ok(())
+}
+
+// --check 或只有 contig 长度时只做坐标校验，不读取序列、不写输出；需要序列或写输出的选项不能与之同时使用
+fn run_check(
+    matches: &clap::ArgMatches,
+    options: &ReplaceOptions,
+    contigs_file: &str,
+    mutations_files: &[&str],
+    gene_positions_file: &str,
+    downloads: &mut Downloads,
+) -> io::Result<()> {
+    let mode = if matches.is_present("check") { "--check" } else { "--contig-lengths" };
+    const NEEDS_SEQUENCE: [(&str, &str); 24] = [
+        ("output_file", "--output"),
+        ("format", "--format"),
+        ("fasta_out", "--fasta-out"),
+        ("masked_diff_out", "--masked-diff-out"),
+        ("tsv_out", "--tsv-out"),
+        ("json_out", "--json-out"),
+        ("jsonl_out", "--jsonl-out"),
+        ("fastq_out", "--fastq-out"),
+        ("applied_vcf", "--applied-vcf"),
+        ("track_bed", "--track-bed"),
+        ("ts_tv_report", "--ts-tv-report"),
+        ("six_frame", "--six-frame"),
+        ("paired_nt_aa", "--paired-nt-aa"),
+        ("translate", "--translate"),
+        ("summary", "--summary"),
+        ("stats", "--stats"),
+        ("codon_align", "--codon-align"),
+        ("concatenate", "--concatenate"),
+        ("split_output_tar", "--split-output-tar"),
+        ("group_by", "--group-by"),
+        ("sqlite", "--sqlite"),
+        ("report_parquet", "--report-parquet"),
+        ("n_report", "--n-report"),
+        ("max_n_fraction", "--max-n-fraction"),
+    ];
+    if let Some((_, flag)) = NEEDS_SEQUENCE.iter().find(|(name, _)| matches.is_present(name)) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            tr!(
+                "{} 只做坐标校验，不能与需要序列的 {} 同时使用",
+                "{} only validates coordinates and cannot be combined with {}, which needs sequence",
+                mode,
+                flag,
+            ),
+        ));
+    }
+    // 长度来源：--contig-lengths 清单，其次 --contigs-fai 索引，否则顺序读取 FASTA 只统计长度
+    let contig_lengths = match (matches.value_of("contig_lengths"), &options.remote.contigs_fai) {
+        (Some(lengths_file), _) => read_contig_lengths(&downloads.resolve(lengths_file)?)?,
+        (None, Some(fai_file)) => read_contig_lengths(&downloads.resolve(fai_file)?)?,
+        (None, None) => read_fasta_lengths(contigs_file)?,
+    };
+    // 区间无效的基因行在解析时已计入 invalid_records
+    let (genes, _) = match &options.positions_sqlite {
+        Some((database, query)) => read_gene_positions_sqlite(database, query, &options.parse)?,
+        None => read_gene_positions_checked(gene_positions_file, &options.parse)?,
+    };
+    // 与正常运行相同：先按 --liftover 换算基因（和 --liftover-mutations 时的突变），再按 --contig-offset 调整突变位置
+    let mut genes = dedup_genes(genes, options.dedup_genes).0;
+    let (mut mutations, _) = read_mutation_files(mutations_files, &options.parse)?;
+    if let Some(liftover) = &options.liftover {
+        let (lifted, unlifted) = lift_genes(genes, liftover);
+        warn_unlifted_genes(&unlifted);
+        genes = lifted;
+        if options.liftover_mutations {
+            let (lifted, unlifted) = lift_mutations(mutations, liftover);
+            warn_unlifted_mutations(unlifted.len());
+            mutations = lifted;
+        }
+    }
+    // 偏移后超出范围的突变与其他越界坐标一样计为问题
+    let mut offset_problems = 0;
+    if !options.contig_offsets.is_empty() {
+        let (kept, dropped) = apply_contig_offsets(mutations, &options.contig_offsets, |contig_id| contig_lengths.get(contig_id).copied());
+        mutations = kept;
+        offset_problems = dropped.len();
+    }
+    let gene_positions_map = group_genes_by_contig(genes);
+    let problems = validate_coordinates(&gene_positions_map, &mutations, &contig_lengths) + offset_problems;
+    let invalid_records = COUNTERS.invalid_records.load(Ordering::Relaxed);
+    if problems > 0 || invalid_records > 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            tr!(
+                "坐标校验发现 {} 个问题，{} 条基因位置或突变记录无法解析",
+                "coordinate validation found {} problems and {} unparseable gene position or mutation records",
+                problems,
+                invalid_records,
+            ),
+        ));
+    }
+    println!(
+        "{}",
+        tr!(
+            "坐标校验通过：{} 个基因，{} 个突变，{} 个 contig",
+            "coordinate validation passed: {} genes, {} mutations, {} contigs",
+            gene_positions_map.values().map(|genes| genes.len()).sum::<usize>(),
+            mutations.len(),
+            contig_lengths.len(),
+        )
+    );
+    Ok(())
+}
+
+// http(s) 输入下载到的临时文件，运行结束（或 --check 完成）后删除
+struct Downloads<'a> {
+    remote: &'a RemoteOptions,
+    files: Vec<std::path::PathBuf>,
+}
+
+impl Downloads<'_> {
+    // 本地路径原样返回；http(s) 地址先下载，返回临时文件的路径
+    fn resolve(&mut self, path: &str) -> io::Result<String> {
+        if !is_remote(path) {
+            return Ok(path.to_string());
+        }
+        let local_path = download(path, self.remote)?;
+        eprintln!("{}", tr!("已下载 {} -> {}", "downloaded {} -> {}", path, local_path.display()));
+        let local = local_path.to_string_lossy().into_owned();
+        self.files.push(local_path);
+        Ok(local)
+    }
+
+}
+
+// 提前返回错误时也删除已下载的文件
+impl Drop for Downloads<'_> {
+    fn drop(&mut self) {
+        for downloaded_file in &self.files {
+            let _ = std::fs::remove_file(downloaded_file);
+        }
+    }
+}
+
+// --metrics-file：按 --metrics-interval 定期写出运行计数，标签来自 --metrics-label key=value
+fn start_metrics_ticker(matches: &clap::ArgMatches) -> io::Result<Option<MetricsTicker>> {
+    let interval: u64 = parse_number_arg(
+        matches,
+        "metrics_interval",
+        |&interval: &u64| interval > 0,
+        tr!("--metrics-interval 需要正整数（秒）", "--metrics-interval needs a positive number of seconds"),
+    )?
+    .unwrap_or(15);
+    Ok(matches.value_of("metrics_file").map(|metrics_file| {
+        let labels: String = matches
+            .values_of("metrics_label")
+            .map(|values| {
+                values
+                    .filter_map(|label| label.split_once('='))
+                    .map(|(key, value)| format!(",{}=\"{}\"", key, value.replace('\\', "\\\\").replace('"', "\\\"")))
+                    .collect()
+            })
+            .unwrap_or_default();
+        MetricsTicker::start(metrics_file, &labels, Duration::from_secs(interval.max(1)))
+    }))
+}
+
+// --genetic-code 和 --internal-stop：翻译输出使用的密码表和内部终止密码子的处理方式
+fn translation_settings(matches: &clap::ArgMatches) -> io::Result<(&'static [u8; 64], InternalStopPolicy)> {
+    let internal_stop_policy = matches
+        .value_of("internal_stop")
+        .and_then(InternalStopPolicy::parse)
+        .unwrap_or(InternalStopPolicy::TranslateThrough);
+    let code = match matches.value_of("genetic_code") {
+        Some(value) => value.parse().ok().and_then(genetic_code).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, tr!(
+                "--genetic-code：不支持的 NCBI 遗传密码表 {}",
+                "--genetic-code: unsupported NCBI genetic code {}",
+                value,
+            ))
+        })?,
+        None => STANDARD_CODE,
+    };
+    Ok((code, internal_stop_policy))
+}
+
+// 主输出之外的各种报告，按命令行参数逐个写出
+fn write_reports(
+    matches: &clap::ArgMatches,
+    options: &ReplaceOptions,
+    result: &ReplaceResult,
+    run_info: &RunInfo,
+    code: &[u8; 64],
+    internal_stop_policy: InternalStopPolicy,
+) -> io::Result<()> {
+    let provenance = options.provenance.then_some(run_info);
+    let strand_relative = matches.is_present("strand_relative");
+    let strand_view = strand_relative.then(|| GeneRelativeView::new(&result.gene_summaries, true));
+    if matches.is_present("provenance_sidecar") {
+        // 写在 FASTA 主输出旁；没有 FASTA 输出时写在第一个主输出旁
+        let fasta_output = options.outputs.iter().find(|(format, _)| *format == OutputFormat::Fasta).or(options.outputs.first());
+        let sidecar_file = format!("{}.provenance.json", fasta_output.map_or("output.fasta", |(_, path)| path.as_str()));
+        std::fs::write(&sidecar_file, run_info.provenance_json() + "\n")?;
+        println!("{}", tr!("输出来源信息: {}", "provenance: {}", sidecar_file));
+    }
+
+    if let Some(sqlite_file) = matches.value_of("sqlite") {
+        write_sqlite(sqlite_file, result, run_info, matches.is_present("sqlite_sequences"))?;
+        println!("{}", tr!("输出 SQLite 数据库: {}", "SQLite database: {}", sqlite_file));
+    }
+
+    if let Some(parquet_file) = matches.value_of("report_parquet") {
+        write_report_parquet(parquet_file, result, strand_relative)?;
+        println!("{}", tr!("输出 Parquet 突变报告: {}", "Parquet mutation report: {}", parquet_file));
+    }
+
+    if let Some(concatenated_file) = matches.value_of("concatenate") {
+        let partition_file = matches
+            .value_of("partition_file")
+            .map(|f| f.to_string())
+            .unwrap_or_else(|| format!("{}.partitions.tsv", concatenated_file));
+        write_concatenated_genes(concatenated_file, &partition_file, &result.mutated_genes)?;
+        println!(
+            "{}",
+            tr!(
+                "输出拼接的超级基因: {}（分区: {}）",
+                "concatenated supergene: {} (partitions: {})",
+                concatenated_file,
+                partition_file,
+            )
+        );
+    }
+
+    if let Some(six_frame_file) = matches.value_of("six_frame") {
+        let internal_stop_records = write_six_frame_translations(
+            six_frame_file,
+            &result.mutated_genes,
+            &result.gene_summaries,
+            matches.is_present("canonical_only"),
+            code,
+            internal_stop_policy,
+        )?;
+        println!(
+            "{}",
+            tr!(
+                "输出六框翻译: {}（{} 条记录含内部终止密码子）",
+                "six-frame translation: {} ({} records with internal stop codons)",
+                six_frame_file,
+                internal_stop_records,
+            )
+        );
+    }
+
+    if let Some(paired_file) = matches.value_of("paired_nt_aa") {
+        let internal_stop_records =
+            write_paired_nt_aa(paired_file, &result.mutated_genes, &result.gene_summaries, code, internal_stop_policy)?;
+        println!(
+            "{}",
+            tr!(
+                "输出核苷酸/蛋白成对记录: {}（{} 个基因含内部终止密码子）",
+                "paired nucleotide/protein records: {} ({} genes with internal stop codons)",
+                paired_file,
+                internal_stop_records,
+            )
+        );
+    }
+
+    // --translate 时 --summary 中标记含内部终止密码子和末尾密码子不完整的基因
+    let translation_flags = match matches.value_of("translate") {
+        Some(protein_file) => Some(write_translation(protein_file, result, code, internal_stop_policy)?),
+        None => None,
+    };
+
+    if let Some(codon_file) = matches.value_of("codon_align") {
+        write_codon_align(codon_file, result)?;
+    }
+
+    if let Some(applied_vcf_file) = matches.value_of("applied_vcf") {
+        write_applied_vcf(applied_vcf_file, &result.applied_mutations, strand_view.as_ref(), provenance)?;
+        println!("{}", tr!("输出实际应用的突变: {}", "applied mutations: {}", applied_vcf_file));
+    }
+
+    // 按基因拆分的输出与主输出一样，负链基因（除非 --no-revcomp-minus）按基因方向写出
+    let oriented_genes = (!options.forward_minus && (matches.is_present("split_output_tar") || matches.is_present("group_by")))
+        .then(|| orient_minus_strand(&result.mutated_genes, &result.gene_summaries));
+    let output_genes = oriented_genes.as_ref().unwrap_or(&result.mutated_genes);
+    if let Some(tar_file) = matches.value_of("split_output_tar") {
+        write_split_output_tar(tar_file, output_genes, options.wrap)?;
+        println!("{}", tr!("输出按基因拆分的 tar 归档: {}", "per-gene tar archive: {}", tar_file));
+    }
+
+    if let Some(groups_file) = matches.value_of("group_by") {
+        let groups = read_gene_groups(groups_file)?;
+        let group_dir = matches.value_of("group_dir").unwrap_or("groups");
+        let written = write_grouped_output(group_dir, output_genes, &groups, options.wrap)?;
+        println!(
+            "{}",
+            tr!("按类别输出 {} 个 FASTA 文件到 {}", "wrote {} FASTA files by category to {}", written.len(), group_dir)
+        );
+        for (category, path, genes) in written {
+            println!("{}", tr!("  {}\t{} 个基因\t{}", "  {}\t{} genes\t{}", category, genes, path));
+        }
+    }
+
+    if let Some(ts_tv_file) = matches.value_of("ts_tv_report") {
+        write_ts_tv_report(ts_tv_file, &result.applied_mutations, &result.gene_summaries)?;
+        println!("{}", tr!("输出转换/颠换统计: {}", "transition/transversion counts: {}", ts_tv_file));
+    }
+
+    if let Some(track_file) = matches.value_of("track_bed") {
+        write_track_bed(track_file, &result.applied_mutations, strand_view.as_ref())?;
+        println!("{}", tr!("输出突变 BED 轨道: {}", "mutation BED track: {}", track_file));
+    }
+
+    if let Some(id_map_file) = matches.value_of("id_map") {
+        write_id_map(id_map_file, &result.gene_summaries, provenance)?;
+        println!("{}", tr!("输出记录ID对应表: {}", "record ID map: {}", id_map_file));
+    }
+
+    if let Some(edited_coords_file) = matches.value_of("edited_coords") {
+        write_edited_coords(edited_coords_file, &result.gene_summaries, &result.applied_mutations, provenance)?;
+        println!("{}", tr!("输出编辑后的基因坐标: {}", "post-edit gene coordinates: {}", edited_coords_file));
+    }
+
+    if let Some(summary_file) = matches.value_of("summary") {
+        write_gene_summary(summary_file, &result.gene_summaries, translation_flags.as_ref(), provenance)?;
+        println!("{}", tr!("输出基因突变数汇总: {}", "per-gene mutation summary: {}", summary_file));
+    }
+
+    if let Some(histogram_file) = matches.value_of("length_histogram") {
+        let bin_width: usize = parse_number_arg(
+            matches,
+            "histogram_bin_width",
+            |&width: &usize| width > 0,
+            tr!("--histogram-bin-width 需要正整数", "--histogram-bin-width needs a positive integer"),
+        )?
+        .unwrap_or(100);
+        write_length_histogram(histogram_file, &result.mutated_genes, bin_width, provenance)?;
+        println!("{}", tr!("输出基因长度分布: {}", "gene length distribution: {}", histogram_file));
+    }
+
+    if let Some(stats_file) = matches.value_of("stats") {
+        let mut json = result.mutation_stats.to_json();
+        if let Some(run_info) = provenance {
+            json.pop();
+            json.push_str(&format!(",\"provenance\":{}}}", run_info.provenance_json()));
+        }
+        std::fs::write(stats_file, json + "\n")?;
+        println!("{}", tr!("输出突变统计: {}", "mutation statistics: {}", stats_file));
+    }
+    Ok(())
+}
+
+// --translate：写出蛋白序列，汇总报告末尾密码子不完整的基因；返回 (含内部终止密码子的基因, 末尾密码子不完整的基因)
+fn write_translation(
+    protein_file: &str,
+    result: &ReplaceResult,
+    code: &[u8; 64],
+    internal_stop_policy: InternalStopPolicy,
+) -> io::Result<(HashSet<String>, HashSet<String>)> {
+    let (internal_stop, incomplete) =
+        write_protein_fasta(protein_file, &result.mutated_genes, &result.gene_summaries, code, internal_stop_policy)?;
+    if !incomplete.is_empty() {
+        let message = tr!(
+            "警告：{} 个基因的长度不是 3 的倍数，翻译时丢弃了末尾不完整的密码子",
+            "warning: {} genes have a length that is not a multiple of 3; the trailing partial codon was dropped in translation",
+            incomplete.len(),
+        );
+        Warning::new("incomplete_codon", message).emit_summary(incomplete.iter().map(|gene_info| {
+            Warning::new("incomplete_codon", tr!(
+                "基因 {} 的长度不是 3 的倍数，末尾不完整的密码子未翻译",
+                "the length of gene {} is not a multiple of 3; the trailing partial codon was not translated",
+                gene_info.gene_id,
+            ))
+                .contig(&gene_info.contig_id)
+                .gene(&gene_info.gene_id)
+        }));
+    }
+    println!(
+        "{}",
+        tr!(
+            "输出蛋白序列: {}（{} 个基因含内部终止密码子）",
+            "protein sequences: {} ({} genes with internal stop codons)",
+            protein_file,
+            internal_stop.len(),
+        )
+    );
+    let gene_ids = |genes: Vec<GeneInfo>| genes.into_iter().map(|gene_info| gene_info.gene_id).collect();
+    Ok((gene_ids(internal_stop), gene_ids(incomplete)))
+}
+
+// --codon-align：写出密码子对齐的参考/突变记录，报告被截短和因插入/缺失而跳过的基因
+fn write_codon_align(codon_file: &str, result: &ReplaceResult) -> io::Result<()> {
+    let (truncated, indel_genes) =
+        write_codon_alignment(codon_file, &result.mutated_genes, &result.gene_summaries, &result.applied_mutations)?;
+    println!(
+        "{}",
+        tr!(
+            "输出密码子对齐: {}（{} 个基因长度不是 3 的倍数，末尾碱基已丢弃）",
+            "codon alignment: {} ({} genes with a length not a multiple of 3 had trailing bases dropped)",
+            codon_file,
+            truncated,
+        )
+    );
+    if indel_genes > 0 {
+        warn("codon_align_indel", tr!(
+            "警告：{} 个基因含插入/缺失，长度改变，未写入 --codon-align",
+            "warning: {} genes contain insertions/deletions that change their length and were not written to --codon-align",
+            indel_genes,
+        ));
+    }
+    Ok(())
+}
+
+// 运行结束时在 stderr 上输出突变去向统计、各突变输入文件（多于一个时）和 --timings
+fn print_run_summary(matches: &clap::ArgMatches, result: &ReplaceResult, num_threads: usize) {
+    let stats = &result.mutation_stats;
+    eprintln!(
+        "{}",
+        tr!(
+            "[mutation-stats] 读取 {}，落在基因区间内 {}（已应用 {}），基因间 {}，所在 contig 没有基因 {}，contig 缺失 {}，超出 contig 范围 {}，应用前被跳过 {}",
+            "[mutation-stats] read {}, in gene windows {} (applied {}), intergenic {}, on contigs without genes {}, missing contig {}, past contig end {}, skipped before application {}",
+            stats.read,
+            stats.in_genes,
+            stats.applied,
+            stats.intergenic,
+            stats.no_gene_contig,
+            stats.missing_contig,
+            stats.out_of_range,
+            stats.filtered,
+        )
+    );
+
+    if result.mutation_inputs.len() > 1 {
+        for input in &result.mutation_inputs {
+            let status = match &input.duplicate_of {
+                Some(original) => format!("skipped (duplicate of {})", original),
+                None => "ingested".to_string(),
+            };
+            eprintln!(
+                "[mutations] {}\tblake3={}\tsize={}\trecords={}\t{}",
+                input.path, input.content_digest, input.size, input.records, status
+            );
+        }
+    }
+
+    if matches.is_present("timings") {
+        for (stage, elapsed) in &result.timings {
+            eprintln!("[timings] {}: {:.3}s", stage, elapsed.as_secs_f64());
+        }
+        eprintln!("[timings] largest_allocation: {} bytes", largest_allocation());
+        eprintln!(
+            "[timings] average_parallelism: {:.2} ({} work items, {} threads)",
+            result.average_parallelism, result.work_items, num_threads
+        );
+    }
+}
+
+// 标准输入（"-"）的状态：程序启动时即在后台线程中完整读入，避免上游进程因管道写满而阻塞；
+// 读完后缓存下来，同一输入可以被多次打开（例如先校验哈希再解析）
+enum StdinState {
+    NotStarted,
+    Draining(thread::JoinHandle<io::Result<Vec<u8>>>),
+    Drained(Arc<Vec<u8>>),
+}
+
+static STDIN_STATE: Mutex<StdinState> = Mutex::new(StdinState::NotStarted);
+
+fn start_stdin_drain() {
+    let mut state = STDIN_STATE.lock().unwrap();
+    if let StdinState::NotStarted = *state {
+        *state = StdinState::Draining(thread::spawn(|| {
+            let mut bytes = vec![];
+            io::stdin().lock().read_to_end(&mut bytes)?;
+            Ok(bytes)
+        }));
+    }
+}
+
+struct SharedBytes(Arc<Vec<u8>>);
+
+impl AsRef<[u8]> for SharedBytes {
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_slice()
+    }
+}
+
+// 打开一个输入：路径为 "-" 时返回已读入的标准输入内容，否则打开文件。
+// 所有文本输入都经由这里打开，并按内容开头的魔数（而不是文件名）自动解压
+fn open_input(filename: &str) -> io::Result<Box<dyn Read + Send>> {
+    decompress_input(filename, open_raw_input(filename)?)
+}
+
+fn open_raw_input(filename: &str) -> io::Result<Box<dyn Read + Send>> {
+    if filename != "-" {
+        return Ok(Box::new(File::open(filename)?));
+    }
+    let mut state = STDIN_STATE.lock().unwrap();
+    let bytes = match std::mem::replace(&mut *state, StdinState::NotStarted) {
+        StdinState::Drained(bytes) => bytes,
+        StdinState::Draining(handle) => {
+            let bytes = handle.join().map_err(|_| io::Error::other("stdin reader thread panicked"))??;
+            Arc::new(bytes)
+        }
+        StdinState::NotStarted => {
+            let mut bytes = vec![];
+            io::stdin().lock().read_to_end(&mut bytes)?;
+            Arc::new(bytes)
+        }
+    };
+    *state = StdinState::Drained(Arc::clone(&bytes));
+    Ok(Box::new(io::Cursor::new(SharedBytes(bytes))))
+}
+
+// 输入内容的压缩格式，由开头的魔数判断
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum InputCompression {
+    None,
+    Gzip,
+    Bgzf, // 带 BC 扩展字段的 gzip 块序列（samtools/htslib）
+    Zstd,
+    Xz,
+}
+
+impl InputCompression {
+    fn detect(header: &[u8]) -> Self {
+        match header {
+            // gzip 头部 FEXTRA 标志位置位，且第一个扩展子字段为 "BC"
+            [0x1f, 0x8b, 0x08, flags, _, _, _, _, _, _, _, _, b'B', b'C', ..] if flags & 0x04 != 0 => InputCompression::Bgzf,
+            [0x1f, 0x8b, ..] => InputCompression::Gzip,
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => InputCompression::Zstd,
+            [0xfd, b'7', b'z', b'X', b'Z', 0x00, ..] => InputCompression::Xz,
+            _ => InputCompression::None,
+        }
+    }
+
+    // 根据扩展名推测的压缩格式，仅用于和实际内容不一致时给出提示
+    fn from_extension(filename: &str) -> Self {
+        let filename = filename.to_ascii_lowercase();
+        if filename.ends_with(".bgz") {
+            InputCompression::Bgzf
+        } else if filename.ends_with(".gz") {
+            InputCompression::Gzip
+        } else if filename.ends_with(".zst") {
+            InputCompression::Zstd
+        } else if filename.ends_with(".xz") {
+            InputCompression::Xz
+        } else {
+            InputCompression::None
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            InputCompression::None => "uncompressed",
+            InputCompression::Gzip => "gzip",
+            InputCompression::Bgzf => "bgzf",
+            InputCompression::Zstd => "zstd",
+            InputCompression::Xz => "xz",
+        }
+    }
+}
+
+// 查看输入开头的字节，识别 gzip/bgzf/zstd/xz 并套上对应的解码器；文件名与内容不一致时以内容为准
+fn decompress_input(filename: &str, raw: Box<dyn Read + Send>) -> io::Result<Box<dyn Read + Send>> {
+    let mut reader = BufReader::new(raw);
+    let compression = InputCompression::detect(reader.fill_buf()?);
+    let expected = InputCompression::from_extension(filename);
+    if compression != InputCompression::None {
+        eprintln!(
+            "{}",
+            tr!("[info] {}: 检测到 {} 压缩", "[info] {}: detected {} compression", filename, compression.name())
+        );
+    }
+    // bgzf 本身就是合法的 gzip，.gz 文件名下的 bgzf 不算不一致
+    let consistent = compression == expected || matches!((compression, expected), (InputCompression::Bgzf, InputCompression::Gzip));
+    if !consistent && expected != InputCompression::None {
+        warn(
+            "compression_mismatch",
+            tr!(
+                "警告：{} 的扩展名表示 {} 压缩，但内容为 {}，按实际内容读取",
+                "warning: the extension of {} indicates {} compression but the content is {}; reading by content",
+                filename,
+                expected.name(),
+                compression.name(),
+            ),
+        );
+    }
+    match compression {
+        InputCompression::None => Ok(Box::new(reader)),
+        // bgzf 由多个 gzip 成员拼接而成，需要逐个成员解压
+        InputCompression::Gzip | InputCompression::Bgzf => Ok(Box::new(flate2::read::MultiGzDecoder::new(reader))),
+        InputCompression::Zstd => zstd_decoder(reader),
+        InputCompression::Xz => xz_decoder(reader),
+    }
+}
+
+#[cfg(feature = "zstd")]
+fn zstd_decoder(reader: BufReader<Box<dyn Read + Send>>) -> io::Result<Box<dyn Read + Send>> {
+    Ok(Box::new(zstd::stream::read::Decoder::with_buffer(reader)?))
+}
+
+#[cfg(not(feature = "zstd"))]
+fn zstd_decoder(_reader: BufReader<Box<dyn Read + Send>>) -> io::Result<Box<dyn Read + Send>> {
+    Err(io::Error::other("zstd-compressed input requires z10 to be built with the \"zstd\" feature"))
+}
+
+#[cfg(feature = "xz")]
+fn xz_decoder(reader: BufReader<Box<dyn Read + Send>>) -> io::Result<Box<dyn Read + Send>> {
+    Ok(Box::new(xz2::bufread::XzDecoder::new_multi_decoder(reader)))
+}
+
+#[cfg(not(feature = "xz"))]
+fn xz_decoder(_reader: BufReader<Box<dyn Read + Send>>) -> io::Result<Box<dyn Read + Send>> {
+    Err(io::Error::other("xz-compressed input requires z10 to be built with the \"xz\" feature"))
+}
+
+// contig 序列的来源。流水线只通过这个 trait 读取 contig，不直接依赖本地文件系统；
+// 新的后端（如对象存储）实现它并放在对应的 feature 之后，再在 open_sequence_source 中选择即可
+trait SequenceSource {
+    // 返回 contig 序列及每个 contig 的 N 数。wanted 为流水线需要的 contig，能按需读取的后端只取这些，
+    // 其余后端可以忽略它返回全部 contig
+    fn read_contigs(
+        &self,
+        wanted: Option<&HashSet<&str>>,
+        max_memory: Option<usize>,
+    ) -> io::Result<(Contigs, HashMap<String, usize>)>;
+}
+
+// 默认来源：本地 FASTA（也包括标准输入和已下载到本地的远程文件），整体顺序读取
+struct LocalFasta<'a> {
+    path: &'a str,
+}
+
+impl SequenceSource for LocalFasta<'_> {
+    fn read_contigs(
+        &self,
+        _wanted: Option<&HashSet<&str>>,
+        max_memory: Option<usize>,
+    ) -> io::Result<(Contigs, HashMap<String, usize>)> {
+        read_contigs(self.path, max_memory)
+    }
+}
+
+// 本地 FASTA 配合 .fai：按索引中的偏移只读取需要的 contig，其余 contig 不会载入内存
+struct IndexedLocalFasta<'a> {
+    path: &'a str,
+    fai_path: &'a str,
+}
+
+impl SequenceSource for IndexedLocalFasta<'_> {
+    fn read_contigs(
+        &self,
+        wanted: Option<&HashSet<&str>>,
+        max_memory: Option<usize>,
+    ) -> io::Result<(Contigs, HashMap<String, usize>)> {
+        if self.path == "-" || is_remote(self.fai_path) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                tr!(
+                    "--contigs-fai：本地索引需要本地的 FASTA 文件；远程 FASTA 须同时给出远程索引 URL",
+                    "--contigs-fai: a local index needs a local FASTA file; a remote FASTA needs a remote index URL as well",
+                ),
+            ));
+        }
+        let index = parse_fai(&std::fs::read_to_string(self.fai_path)?);
+        let mut file = File::open(self.path)?;
+        let mut header = [0u8; 16];
+        let header_length = file.read(&mut header)?;
+        let compression = InputCompression::detect(&header[..header_length]);
+        if compression != InputCompression::None {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                tr!(
+                    "--contigs-fai：{} 是 {} 压缩的，按偏移读取需要未压缩的 FASTA",
+                    "--contigs-fai: {} is {} compressed; reading by offset needs an uncompressed FASTA",
+                    self.path,
+                    compression.name(),
+                ),
+            ));
+        }
+
+        let mut contigs = HashMap::new();
+        let mut n_counts = HashMap::new();
+        let mut loaded_bytes = 0;
+        let wanted: Vec<&str> = match wanted {
+            Some(wanted) => wanted.iter().copied().collect(),
+            None => index.keys().map(String::as_str).collect(),
+        };
+        for contig_id in wanted {
+            let Some((first, last)) = index.get(contig_id).and_then(FaiEntry::byte_range) else {
+                continue; // 与整体读取时缺少 contig 的处理一致，后续按缺失 contig 报告
+            };
+            file.seek(io::SeekFrom::Start(first))?;
+            let mut bytes = Vec::with_capacity((last - first + 1) as usize);
+            (&mut file).take(last - first + 1).read_to_end(&mut bytes)?;
+            bytes.retain(|b| *b != b'\n' && *b != b'\r');
+            loaded_bytes += bytes.len();
+            if let Some(limit) = max_memory {
+                if loaded_bytes > limit {
+                    return Err(memory_limit_error(&tr!("读取 contigs", "reading contigs"), loaded_bytes, limit));
+                }
+            }
+            n_counts.insert(contig_id.to_string(), bytes.iter().filter(|&&b| b == b'N' || b == b'n').count());
+            contigs.insert(contig_id.to_string(), bytes);
+        }
+        Ok((contigs, n_counts))
+    }
+}
+
+// .fai 中一条 contig 的索引信息
+struct FaiEntry {
+    length: u64,
+    offset: u64,
+    line_bases: u64,
+    line_width: u64,
+}
+
+impl FaiEntry {
+    // 序列在 FASTA 中的字节范围（首尾均包含，含换行）；空序列为 None
+    fn byte_range(&self) -> Option<(u64, u64)> {
+        if self.length == 0 || self.line_bases == 0 {
+            return None;
+        }
+        let full_lines = (self.length - 1) / self.line_bases;
+        Some((self.offset, self.offset + full_lines * self.line_width + (self.length - 1) % self.line_bases))
+    }
+}
+
+// 解析 samtools faidx 的 .fai 文本：名称、长度、偏移、每行碱基数、每行字节数
+fn parse_fai(text: &str) -> HashMap<String, FaiEntry> {
+    let mut index: HashMap<String, FaiEntry> = HashMap::new();
+    for line in text.lines() {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let parsed: Option<Vec<u64>> = fields.get(1..5).map(|f| f.iter().filter_map(|v| v.parse().ok()).collect());
+        match parsed {
+            Some(values) if values.len() == 4 && values[1] > 0 => {
+                index.insert(
+                    fields[0].to_string(),
+                    FaiEntry { length: values[0], offset: values[1], line_bases: values[2], line_width: values[3] },
+                );
+            }
+            _ => warn("invalid_fai_row", tr!(".fai 行格式无效：{}", "invalid .fai row: {}", line)),
+        }
+    }
+    index
+}
+
+// 根据选项选择 contig 来源：给出 --contigs-fai 时只读取需要的 contig（远程 FASTA 按 .fai 发起范围请求，需要 remote feature），
+// 否则整体读取本地文件
+fn open_sequence_source<'a>(contigs_file: &'a str, options: &'a ReplaceOptions) -> io::Result<Box<dyn SequenceSource + 'a>> {
+    match &options.remote.contigs_fai {
+        Some(fai_url) if is_remote(contigs_file) => remote_sequence_source(contigs_file, fai_url, &options.remote),
+        Some(fai_path) => Ok(Box::new(IndexedLocalFasta { path: contigs_file, fai_path })),
+        None => Ok(Box::new(LocalFasta { path: contigs_file })),
+    }
+}
+
+/// Reads a FASTA file (optionally compressed, or `-` for stdin) into contig id -> sequence, along with the
+/// number of `N`/`n` bases in each contig. Loading more than `max_memory` bytes of sequence is an error.
+pub fn read_contigs(filename: &str, max_memory: Option<usize>) -> io::Result<(Contigs, HashMap<String, usize>)> {
+    let mut reader = HashingReader::new(open_input(filename)?);
+    let contigs = read_contigs_from(BufReader::new(&mut reader), max_memory)?;
+    reader.record_input(filename);
+    Ok(contigs)
+}
+
+// 读取 contigs，同时统计每个 contig 中 N 碱基的数量
+fn read_contigs_from<R: BufRead>(
+    reader: R,
+    max_memory: Option<usize>,
+) -> io::Result<(Contigs, HashMap<String, usize>)> {
+    let mut contigs = HashMap::new();
+    let mut n_counts = HashMap::new();
+    let mut current_id = String::new();
+    let mut current_sequence: Vec<u8> = Vec::new();
+    let mut current_n_count = 0;
+    let mut loaded_bytes = 0;
+
+    // 按字节读取：序列不经过 UTF-8 转换，每个碱基占一个字节
+    for line in reader.split(b'\n') {
+        let mut line = line?;
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        if let Some(header) = line.strip_prefix(b">") {
+            if !current_id.is_empty() && !current_sequence.is_empty() {
+                n_counts.insert(current_id.clone(), current_n_count);
+                contigs.insert(std::mem::take(&mut current_id), std::mem::take(&mut current_sequence));
+            }
+            // contig ID 为 '>' 之后第一个空白之前的部分，描述（如 length=5000 cov=30）不参与匹配
+            current_id = String::from_utf8_lossy(header).split_whitespace().next().unwrap_or_default().to_string();
+            current_sequence.clear();
+            current_n_count = 0;
+        } else {
+            current_n_count += line.iter().filter(|&&b| b == b'N' || b == b'n').count();
+            current_sequence.extend_from_slice(&line);
+            // 边读边检查，在真正耗尽内存之前就失败
+            loaded_bytes += line.len();
+            if let Some(limit) = max_memory {
+                if loaded_bytes > limit {
+                    return Err(memory_limit_error(&tr!("读取 contigs", "reading contigs"), loaded_bytes, limit));
+                }
+            }
+        }
+    }
+
+    if !current_id.is_empty() && !current_sequence.is_empty() {
+        n_counts.insert(current_id.clone(), current_n_count);
+        contigs.insert(current_id, current_sequence);
+    }
+
+    Ok((contigs, n_counts))
+}
+
+// diff 子命令的汇总计数
+#[derive(Debug, Default)]
+struct DiffCounts {
+    identical: usize,
+    different: usize,
+    only_first: usize,
+    only_second: usize,
+}
+
+// diff 子命令：逐个基因比较两个输出 FASTA，每个 gene_id 一行（按 ID 排序），
+// 列为 gene_id、status（identical / different / only_first / only_second）、两边的长度、差异数和差异位置。
+// 按字节比较（区分大小写）；长度不同时较长部分的每个位置都计为一个差异，位置只列出共同长度内的（1-based，逗号分隔）
+fn diff_fastas<W: Write>(first: &str, second: &str, writer: &mut W) -> io::Result<DiffCounts> {
+    let (first_records, _) = read_contigs(first, None)?;
+    let (second_records, _) = read_contigs(second, None)?;
+    let mut gene_ids: BTreeSet<&String> = first_records.keys().collect();
+    gene_ids.extend(second_records.keys());
+    let mut counts = DiffCounts::default();
+    writeln!(writer, "gene_id\tstatus\tfirst_length\tsecond_length\tdifferences\tpositions")?;
+    for gene_id in gene_ids {
+        let (first_sequence, second_sequence) = match (first_records.get(gene_id), second_records.get(gene_id)) {
+            (Some(first_sequence), Some(second_sequence)) => (first_sequence, second_sequence),
+            (Some(first_sequence), None) => {
+                counts.only_first += 1;
+                writeln!(writer, "{}\tonly_first\t{}\t.\t.\t.", gene_id, first_sequence.len())?;
+                continue;
+            }
+            (None, Some(second_sequence)) => {
+                counts.only_second += 1;
+                writeln!(writer, "{}\tonly_second\t.\t{}\t.\t.", gene_id, second_sequence.len())?;
+                continue;
+            }
+            (None, None) => continue,
+        };
+        let positions: Vec<String> = first_sequence
+            .iter()
+            .zip(second_sequence)
+            .enumerate()
+            .filter(|(_, (a, b))| a != b)
+            .map(|(index, _)| (index + 1).to_string())
+            .collect();
+        let differences = positions.len() + first_sequence.len().abs_diff(second_sequence.len());
+        let status = if differences == 0 {
+            counts.identical += 1;
+            "identical"
+        } else {
+            counts.different += 1;
+            "different"
+        };
+        let positions = if positions.is_empty() { ".".to_string() } else { positions.join(",") };
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            gene_id,
+            status,
+            first_sequence.len(),
+            second_sequence.len(),
+            differences,
+            positions
+        )?;
+    }
+    Ok(counts)
+}
+
+// 写出每个 contig 的 N 含量报告，并标记因超过 --max-n-fraction 而被排除的 contig
+fn write_n_content_report(
+    filename: &str,
+    contigs: &Contigs,
+    n_counts: &HashMap<String, usize>,
+    max_n_fraction: Option<f64>,
+) -> io::Result<()> {
+    let mut report_file = BufWriter::new(File::create(filename)?);
+    writeln!(report_file, "contig_id\tlength\tn_count\tn_percent\texcluded")?;
+    let mut contig_ids: Vec<&String> = contigs.keys().collect();
+    contig_ids.sort();
+    for contig_id in contig_ids {
+        let length = contigs[contig_id].len();
+        let n_count = n_counts.get(contig_id).copied().unwrap_or(0);
+        let n_fraction = n_fraction(n_count, length);
+        let excluded = max_n_fraction.is_some_and(|max| n_fraction > max);
+        writeln!(
+            report_file,
+            "{}\t{}\t{}\t{:.2}\t{}",
+            contig_id,
+            length,
+            n_count,
+            n_fraction * 100.0,
+            if excluded { "yes" } else { "no" }
+        )?;
+    }
+    report_file.flush()
+}
+
+fn n_fraction(n_count: usize, length: usize) -> f64 {
+    if length == 0 {
+        0.0
+    } else {
+        n_count as f64 / length as f64
+    }
+}
+
+fn read_mutations_from<R: BufRead>(mut reader: R, options: &ParseOptions) -> io::Result<Vec<Mutation>> {
+    if is_vcf(reader.fill_buf()?) {
+        return read_vcf_mutations(reader, options);
+    }
+    let mut mutations = vec![];
+
+    for line in reader.lines() {
+        let line = line?;
+        // 表格软件导出时带千分位的数字会加引号（"1,234"），此时按 CSV 引号规则拆分
+        let quoted_fields;
+        let parts: Vec<&str> = if line.contains('"') {
+            quoted_fields = split_quoted_csv_line(&line);
+            quoted_fields.iter().map(|field| field.as_str()).collect()
+        } else {
+            line.split(',').collect()
+        };
+        if (3..=6).contains(&parts.len()) {
+            let contig_id = parts[0].to_string();
+            let position = match parse_coordinate(parts[1], options) {
+                Ok(position) => Ok(position),
+                Err(CoordinateError::Invalid) => Err(()),
+                Err(error) => {
+                    Warning::new("invalid_mutation", tr!(
+                        "跳过突变 {}：{}",
+                        "skipped mutation {}: {}",
+                        line,
+                        error.message(parts[1]),
+                    )).contig(parts[0]).emit();
+                    Err(())
+                }
+            };
+            if let Ok(position) = position { // 位置信息在第3个部分
+                let alt = parts[2].trim();
+                if let Some(new_base) = alt.chars().next() { // 新碱基在第4个部分
+                    // 可选的第4列为突变ID，空值或 "." 视为缺失
+                    let mutation_id = parts
+                        .get(3)
+                        .map(|id| id.trim())
+                        .filter(|id| !id.is_empty() && *id != ".")
+                        .map(|id| id.to_string());
+                    // 可选的第5列为基因型，纯合参考（全为 0）的记录没有变异，跳过
+                    let Some(heterozygous) = parts.get(4).map_or(Some(false), |genotype| parse_genotype(genotype)) else {
+                        continue;
+                    };
+                    // 可选的第6列为期望的参考序列，空值或 "." 视为缺失。参考和替换序列等长时逐个碱基拆成替换，
+                    // 长度不同时为插入/缺失；多个碱基的替换序列必须给出参考序列
+                    let reference = parts.get(5).map(|field| field.trim()).filter(|field| !field.is_empty() && *field != ".");
+                    let alt_length = alt.chars().count();
+                    match reference {
+                        None if alt_length == 1 => mutations.push(Mutation {
+                            contig_id,
+                            position,
+                            new_base,
+                            mutation_id,
+                            heterozygous,
+                            expected_ref: None,
+                            indel: None,
+                        }),
+                        None => {
+                            Warning::new("invalid_mutation", tr!(
+                                "跳过突变 {}：多个碱基的替换序列需要第6列参考序列",
+                                "skipped mutation {}: a multi-base replacement needs the reference sequence in column 6",
+                                line,
+                            ))
+                                .contig(&contig_id)
+                                .position(position)
+                                .emit();
+                        }
+                        Some(reference) => push_ref_alt(&mut mutations, contig_id, position, reference, alt, mutation_id, heterozygous),
+                    }
+                }
+            }
+        }
+    }
+    Ok(mutations)
+}
+
+// 由参考和替换序列构造突变：等长时逐个碱基拆成替换（参考碱基作为期望的参考碱基核对），长度不同时为插入/缺失
+fn push_ref_alt(
+    mutations: &mut Vec<Mutation>,
+    contig_id: String,
+    position: usize,
+    reference: &str,
+    alt: &str,
+    mutation_id: Option<String>,
+    heterozygous: bool,
+) {
+    if reference.chars().count() == alt.chars().count() {
+        for (offset, (expected_ref, new_base)) in reference.chars().zip(alt.chars()).enumerate() {
+            mutations.push(Mutation {
+                contig_id: contig_id.clone(),
+                position: position + offset,
+                new_base,
+                mutation_id: mutation_id.clone(),
+                heterozygous,
+                expected_ref: Some(expected_ref),
+                indel: None,
+            });
+        }
+        return;
+    }
+    mutations.push(Mutation {
+        contig_id,
+        position,
+        new_base: alt.chars().next().unwrap_or('N'),
+        mutation_id,
+        heterozygous,
+        expected_ref: None,
+        indel: Some(Box::new(Indel { reference: reference.to_string(), alt: alt.to_string() })),
+    });
+}
+
+// VCF 输入按内容识别：第一行为 ##fileformat=VCF 或 #CHROM 表头
+fn is_vcf(header: &[u8]) -> bool {
+    header.starts_with(b"##fileformat=VCF") || header.starts_with(b"#CHROM")
+}
+
+// 读取 VCF 的 CHROM、POS、ID、REF、ALT 列，有 FORMAT 列时取第一个样本的 GT。REF 作为期望的参考序列核对，
+// 与 ALT 等长时逐个碱基拆成替换，否则为插入/缺失。ALT 为 "." 或 GT 为纯合参考的记录没有变异，跳过；
+// 符号等位基因（<DEL>、*、断点记法）无法应用，计数后跳过。多等位位点默认报错，
+// options.vcf_first_alt（--vcf-multiallelic first）时只取第一个 ALT
+fn read_vcf_mutations<R: BufRead>(reader: R, options: &ParseOptions) -> io::Result<Vec<Mutation>> {
+    let mut mutations = vec![];
+    let mut symbolic = 0;
+    for (line_number, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 5 {
+            warn("invalid_mutation", tr!(
+                "跳过 VCF 第 {} 行：少于 5 列",
+                "skipped VCF line {}: fewer than 5 columns",
+                line_number + 1,
+            ));
+            continue;
+        }
+        let (contig_id, reference, alts) = (fields[0], fields[3], fields[4]);
+        let position = match parse_coordinate(fields[1], options) {
+            Ok(position) => position,
+            Err(error) => {
+                Warning::new("invalid_mutation", tr!(
+                    "跳过 VCF 第 {} 行：{}",
+                    "skipped VCF line {}: {}",
+                    line_number + 1,
+                    error.message(fields[1]),
+                ))
+                    .contig(contig_id)
+                    .emit();
+                continue;
+            }
+        };
+        if alts == "." {
+            continue;
+        }
+        let alt = match alts.split_once(',') {
+            None => alts,
+            Some((first, _)) if options.vcf_first_alt => first,
+            Some(_) => {
+                return Err(GeneReplaceError::ParseError(tr!(
+                    "VCF 第 {} 行 {}:{} 是多等位位点（ALT {}），可用 --vcf-multiallelic first 只应用第一个 ALT",
+                    "VCF line {} {}:{} is multi-allelic (ALT {}); --vcf-multiallelic first applies only the first ALT",
+                    line_number + 1,
+                    contig_id,
+                    position,
+                    alts
+                ))
+                .into());
+            }
+        };
+        let is_sequence = |allele: &str| !allele.is_empty() && allele.bytes().all(|base| base.is_ascii_alphabetic());
+        if !is_sequence(reference) || !is_sequence(alt) {
+            symbolic += 1;
+            continue;
+        }
+        let genotype = match (fields.get(8), fields.get(9)) {
+            (Some(format), Some(sample)) => format.split(':').position(|key| key == "GT").and_then(|index| sample.split(':').nth(index)),
+            _ => None,
+        };
+        let Some(heterozygous) = genotype.map_or(Some(false), parse_genotype) else {
+            continue;
+        };
+        let mutation_id = Some(fields[2]).filter(|id| !id.is_empty() && *id != ".").map(str::to_string);
+        push_ref_alt(&mut mutations, contig_id.to_string(), position, reference, alt, mutation_id, heterozygous);
+    }
+    if symbolic > 0 {
+        warn("unsupported_vcf_allele", tr!(
+            "警告：跳过 {} 条符号等位基因（如 <DEL>、*）的 VCF 记录",
+            "warning: skipped {} VCF records with symbolic alleles (such as <DEL> or *)",
+            symbolic,
+        ));
+    }
+    Ok(mutations)
+}
+
+// 突变给出的期望参考序列：替换为第6列的碱基，插入/缺失为其参考序列
+fn expected_reference(mutation: &Mutation) -> Option<String> {
+    match &mutation.indel {
+        Some(indel) => Some(indel.reference.clone()),
+        None => mutation.expected_ref.map(String::from),
+    }
+}
+
+// 核对给出了期望参考序列（第6列）的突变：contig 上从该位置起的序列不同（不区分大小写）时不应用该突变。
+// contig 缺失或位置越界的突变留给后续的校验处理（插入/缺失的参考区间超出 contig 时视为不一致）。
+// 返回 (保留的突变, 不一致的突变及 contig 上实际的序列)
+fn check_reference_alleles(mutations: Vec<Mutation>, contigs: &Contigs) -> (Vec<Mutation>, Vec<(Mutation, String)>) {
+    let mut mismatches = vec![];
+    let kept = mutations
+        .into_iter()
+        .filter_map(|mutation| {
+            let Some(expected) = expected_reference(&mutation) else {
+                return Some(mutation);
+            };
+            let contig = contigs.get(&mutation.contig_id);
+            let Some(contig) = contig.filter(|contig| (1..=contig.len()).contains(&mutation.position)) else {
+                return Some(mutation);
+            };
+            let start = mutation.position - 1;
+            let found = &contig[start..(start + expected.len()).min(contig.len())];
+            if found.eq_ignore_ascii_case(expected.as_bytes()) {
+                Some(mutation)
+            } else {
+                mismatches.push((mutation, String::from_utf8_lossy(found).into_owned()));
+                None
+            }
+        })
+        .collect();
+    (kept, mismatches)
+}
+
+// 解析 VCF GT 风格的基因型（0/1、1|1、0/0/1/1 等，任意倍性）：同时含有参考等位基因 0 和其他等位基因时为杂合，
+// 全为 0 时返回 None（没有变异）；空值、"." 或缺失的等位基因视为纯合替换
+fn parse_genotype(genotype: &str) -> Option<bool> {
+    let alleles: Vec<&str> = genotype.trim().split(['/', '|']).map(str::trim).filter(|allele| !allele.is_empty() && *allele != ".").collect();
+    let reference = alleles.iter().filter(|allele| **allele == "0").count();
+    if !alleles.is_empty() && reference == alleles.len() {
+        return None;
+    }
+    Some(reference > 0)
+}
+
+// 两个碱基对应的 IUPAC 简并碱基；不是 ACGT 或相同时返回 None
+fn iupac_code(first: u8, second: u8) -> Option<u8> {
+    let mut pair = [first.to_ascii_uppercase(), second.to_ascii_uppercase()];
+    pair.sort_unstable();
+    match pair {
+        [b'A', b'G'] => Some(b'R'),
+        [b'C', b'T'] => Some(b'Y'),
+        [b'G', b'T'] => Some(b'K'),
+        [b'A', b'C'] => Some(b'M'),
+        [b'C', b'G'] => Some(b'S'),
+        [b'A', b'T'] => Some(b'W'),
+        _ => None,
+    }
+}
+
+// --het-as-ambiguity：把杂合突变的替换碱基改为参考碱基和替换碱基的 IUPAC 简并碱基（大小写跟随替换碱基），
+// 在应用突变之前完成，后续的应用和报告不需要区分。返回改写的突变数
+fn apply_het_ambiguity(mutations: &mut [Mutation], contigs: &Contigs) -> usize {
+    let mut converted = 0;
+    for mutation in mutations.iter_mut().filter(|mutation| mutation.heterozygous && mutation.indel.is_none() && mutation.new_base.is_ascii()) {
+        let reference = mutation
+            .position
+            .checked_sub(1)
+            .and_then(|index| contigs.get(&mutation.contig_id)?.get(index).copied());
+        if let Some(code) = reference.and_then(|reference| iupac_code(reference, mutation.new_base as u8)) {
+            mutation.new_base = if mutation.new_base.is_ascii_lowercase() { code.to_ascii_lowercase() } else { code } as char;
+            converted += 1;
+        }
+    }
+    converted
+}
+
+/// Reads a mutations CSV (optionally compressed, or `-` for stdin): contig, position, alt and the optional
+/// id, genotype and reference columns. A reference whose length differs from alt makes an indel.
+/// Uses the default [`ParseOptions`]; see [`read_mutations_with`].
+pub fn read_mutations(filename: &str) -> io::Result<Vec<Mutation>> {
+    read_mutations_with(filename, &ParseOptions::default())
+}
+
+/// Same as [`read_mutations`], parsing the file as described by `options`.
+pub fn read_mutations_with(filename: &str, options: &ParseOptions) -> io::Result<Vec<Mutation>> {
+    let mut reader = HashingReader::new(open_input(filename)?);
+    let mutations = read_mutations_from(BufReader::new(&mut reader), options)?;
+    reader.record_input(filename);
+    Ok(mutations)
+}
+
+// 读取多个突变文件：边解析边计算内容哈希，内容与已读入文件完全相同的文件整体跳过；
+// 文件内部以及文件之间的同位点重复仍由记录级去重处理（同一位点以第一次出现的为准）
+fn read_mutation_files(filenames: &[&str], options: &ParseOptions) -> io::Result<(Vec<Mutation>, Vec<MutationInputSummary>)> {
+    let mut mutations = vec![];
+    let mut summaries: Vec<MutationInputSummary> = vec![];
+    // 按 BLAKE3 摘要识别内容相同的文件
+    let mut seen_digests: HashMap<blake3::Hash, String> = HashMap::new();
+
+    for &filename in filenames {
+        let mut reader = HashingReader::new(open_input(filename)?);
+        let file_mutations = read_mutations_from(BufReader::new(&mut reader), options)?;
+        reader.record_input(filename);
+        let records = file_mutations.len();
+        let content_digest = reader.digest.finalize();
+        let duplicate_of = seen_digests.get(&content_digest).cloned();
+        match &duplicate_of {
+            Some(original) => warn("duplicate_input", tr!(
+                "跳过内容重复的突变文件：{}（与 {} 相同）",
+                "skipped duplicate mutations file: {} (same as {})",
+                filename,
+                original,
+            )),
+            None => {
+                seen_digests.insert(content_digest, filename.to_string());
+                mutations.extend(file_mutations);
+            }
+        }
+        summaries.push(MutationInputSummary {
+            path: filename.to_string(),
+            content_digest,
+            size: reader.bytes_read,
+            records,
+            duplicate_of,
+        });
+    }
+    Ok((mutations, summaries))
+}
+
+const MUTATION_INDEX_MAGIC: &[u8; 8] = b"Z10MIDX5";
+// 旧版索引（V1 没有基因型，V2 没有期望的参考碱基，V3 没有插入/缺失，V4 没有解析选项且使用 64 位 FNV 哈希），
+// 读到时重新解析突变文件
+const MUTATION_INDEX_OLD_MAGICS: [&[u8; 8]; 4] = [b"Z10MIDX1", b"Z10MIDX2", b"Z10MIDX3", b"Z10MIDX4"];
+
+// 索引头中记录的解析选项，与本次运行不同时索引作废
+fn parse_flags(options: &ParseOptions) -> u8 {
+    options.zero_based_genes as u8 | (options.lenient_numbers as u8) << 1 | (options.vcf_first_alt as u8) << 2
+}
+
+// 将解析后的突变及其来源文件的摘要写成二进制索引，格式为：
+// magic，解析选项（parse_flags），来源文件列表（路径、BLAKE3 摘要、大小、记录数、是否重复），
+// 突变列表（contig、位置、碱基、ID、是否杂合、期望的参考碱基（没有时为 0）、插入/缺失的参考和替换序列（没有时为空））
+fn save_mutation_index(
+    filename: &str,
+    mutations: &[Mutation],
+    inputs: &[MutationInputSummary],
+    options: &ParseOptions,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(filename)?);
+    writer.write_all(MUTATION_INDEX_MAGIC)?;
+    writer.write_all(&[parse_flags(options)])?;
+    writer.write_all(&(inputs.len() as u64).to_le_bytes())?;
+    for input in inputs {
+        write_index_str(&mut writer, &input.path)?;
+        writer.write_all(input.content_digest.as_bytes())?;
+        writer.write_all(&input.size.to_le_bytes())?;
+        writer.write_all(&(input.records as u64).to_le_bytes())?;
+        write_index_str(&mut writer, input.duplicate_of.as_deref().unwrap_or(""))?;
+    }
+    writer.write_all(&(mutations.len() as u64).to_le_bytes())?;
+    for mutation in mutations {
+        write_index_str(&mut writer, &mutation.contig_id)?;
+        writer.write_all(&(mutation.position as u64).to_le_bytes())?;
+        writer.write_all(&(mutation.new_base as u32).to_le_bytes())?;
+        write_index_str(&mut writer, mutation.mutation_id.as_deref().unwrap_or(""))?;
+        writer.write_all(&[mutation.heterozygous as u8])?;
+        writer.write_all(&mutation.expected_ref.map_or(0, |base| base as u32).to_le_bytes())?;
+        write_index_str(&mut writer, mutation.indel.as_ref().map_or("", |indel| indel.reference.as_str()))?;
+        write_index_str(&mut writer, mutation.indel.as_ref().map_or("", |indel| indel.alt.as_str()))?;
+    }
+    writer.flush()
+}
+
+// 读取突变索引；解析选项或当前突变文件的内容（BLAKE3 摘要和大小）与索引中记录的不一致时返回 None，由调用方重新解析
+fn load_mutation_index(
+    filename: &str,
+    mutations_files: &[&str],
+    options: &ParseOptions,
+) -> io::Result<Option<(Vec<Mutation>, Vec<MutationInputSummary>)>> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", filename, message));
+    let mut reader = BufReader::new(File::open(filename)?);
+
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if MUTATION_INDEX_OLD_MAGICS.contains(&&magic) {
+        warn("stale_index", tr!(
+            "突变索引 {} 是旧版格式，重新解析突变文件",
+            "mutation index {} uses an old format; re-parsing the mutations files",
+            filename,
+        ));
+        return Ok(None);
+    }
+    if &magic != MUTATION_INDEX_MAGIC {
+        return Err(invalid("not a z10 mutation index"));
+    }
+    let mut flags = [0u8; 1];
+    reader.read_exact(&mut flags)?;
+    if flags[0] != parse_flags(options) {
+        warn("stale_index", tr!(
+            "突变索引 {} 建立时的解析选项（--lenient-numbers、--vcf-multiallelic、--coord-base）与本次运行不同，重新解析突变文件",
+            "mutation index {} was built with different parsing options (--lenient-numbers, --vcf-multiallelic, --coord-base); re-parsing the mutations files",
+            filename,
+        ));
+        return Ok(None);
+    }
+
+    let input_count = read_index_u64(&mut reader)?;
+    let mut inputs = vec![];
+    for _ in 0..input_count {
+        let path = read_index_str(&mut reader)?;
+        let mut digest = [0u8; blake3::OUT_LEN];
+        reader.read_exact(&mut digest)?;
+        let size = read_index_u64(&mut reader)?;
+        let records = read_index_u64(&mut reader)? as usize;
+        let duplicate_of = Some(read_index_str(&mut reader)?).filter(|d| !d.is_empty());
+        inputs.push(MutationInputSummary { path, content_digest: blake3::Hash::from(digest), size, records, duplicate_of });
+    }
+
+    // 校验来源文件：数量和每个文件的内容摘要、大小都必须与建索引时一致
+    let stale = inputs.len() != mutations_files.len()
+        || mutations_files
+            .iter()
+            .zip(&inputs)
+            .map(|(&current, recorded)| {
+                hash_file(current).map(|(digest, size)| digest != recorded.content_digest || size != recorded.size)
+            })
+            .collect::<io::Result<Vec<bool>>>()?
+            .into_iter()
+            .any(|changed| changed);
+    if stale {
+        warn("stale_index", tr!(
+            "突变索引 {} 与当前突变文件不一致，重新解析突变文件",
+            "mutation index {} does not match the current mutations files; re-parsing them",
+            filename,
+        ));
+        return Ok(None);
+    }
+
+    let mutation_count = read_index_u64(&mut reader)?;
+    let mut mutations = Vec::with_capacity(mutation_count.min(1 << 24) as usize);
+    for _ in 0..mutation_count {
+        let contig_id = read_index_str(&mut reader)?;
+        let position = read_index_u64(&mut reader)? as usize;
+        let mut base_bytes = [0u8; 4];
+        reader.read_exact(&mut base_bytes)?;
+        let new_base = char::from_u32(u32::from_le_bytes(base_bytes)).ok_or_else(|| invalid("invalid base"))?;
+        let mutation_id = Some(read_index_str(&mut reader)?).filter(|id| !id.is_empty());
+        let mut heterozygous = [0u8; 1];
+        reader.read_exact(&mut heterozygous)?;
+        reader.read_exact(&mut base_bytes)?;
+        let expected_ref = match u32::from_le_bytes(base_bytes) {
+            0 => None,
+            code => Some(char::from_u32(code).ok_or_else(|| invalid("invalid reference base"))?),
+        };
+        let (reference, alt) = (read_index_str(&mut reader)?, read_index_str(&mut reader)?);
+        let indel = (!reference.is_empty()).then(|| Box::new(Indel { reference, alt }));
+        mutations.push(Mutation {
+            contig_id,
+            position,
+            new_base,
+            mutation_id,
+            heterozygous: heterozygous[0] != 0,
+            expected_ref,
+            indel,
+        });
+    }
+    Ok(Some((mutations, inputs)))
+}
+
+fn write_index_str<W: Write>(writer: &mut W, value: &str) -> io::Result<()> {
+    writer.write_all(&(value.len() as u64).to_le_bytes())?;
+    writer.write_all(value.as_bytes())
+}
+
+fn read_index_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_index_str<R: Read>(reader: &mut R) -> io::Result<String> {
+    let len = read_index_u64(reader)?;
+    let mut bytes = vec![];
+    reader.take(len).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != len {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated mutation index"));
+    }
+    String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+// 流式计算整个文件的 BLAKE3 摘要和大小（不解析）
+fn hash_file(filename: &str) -> io::Result<(blake3::Hash, u64)> {
+    let mut reader = HashingReader::new(open_input(filename)?);
+    io::copy(&mut reader, &mut io::sink())?;
+    reader.record_input(filename);
+    Ok((reader.digest.finalize(), reader.bytes_read))
+}
+
+// 基因位置按输入顺序返回，由调用方去重（dedup_genes）并按 contig 分组（group_genes_by_contig）
+/// Reads a gene positions CSV: contig, an optional gene_id, start, end and an optional strand column.
+/// Rows that cannot be parsed are skipped with a warning. Coordinates are read as 1-based and inclusive;
+/// use [`read_gene_positions_with`] for other conventions.
+pub fn read_gene_positions(filename: &str) -> io::Result<Vec<GeneInfo>> {
+    read_gene_positions_with(filename, &ParseOptions::default())
+}
+
+/// Same as [`read_gene_positions`], parsing the file as described by `options`.
+pub fn read_gene_positions_with(filename: &str, options: &ParseOptions) -> io::Result<Vec<GeneInfo>> {
+    read_gene_positions_checked(filename, options).map(|(genes, _)| genes)
+}
+
+// 同 read_gene_positions，另外返回区间无效（起点为 0 或起点大于终点）而被跳过的基因行，
+// 供 --dropped-genes 和 --strict 使用
+fn read_gene_positions_checked(filename: &str, options: &ParseOptions) -> io::Result<(Vec<GeneInfo>, Vec<GeneInfo>)> {
+    let mut reader = HashingReader::new(open_input(filename).map_err(GeneReplaceError::IoError)?);
+    let genes = read_gene_positions_from(&mut reader, options)?;
+    reader.record_input(filename);
+    Ok(genes)
+}
+
+fn read_gene_positions_from<R: Read>(reader: R, options: &ParseOptions) -> io::Result<(Vec<GeneInfo>, Vec<GeneInfo>)> {
+    let mut rdr = csv::ReaderBuilder::new().has_headers(false).flexible(true).delimiter(b',').from_reader(reader);
+
+    let mut genes = vec![];
+    let mut invalid_intervals = vec![];
+
+    for result in rdr.records() {
+        let record = result.map_err(GeneReplaceError::CsvError)?;
+        let record_data = record.iter().map(|field| field.trim()).collect::<Vec<_>>();
+        match parse_gene_record(&record_data, options) {
+            Some(GeneRecord::Valid(gene_info)) => genes.push(gene_info),
+            Some(GeneRecord::InvalidInterval(gene_info)) => invalid_intervals.push(gene_info),
+            None => {}
+        }
+    }
+    Ok((genes, invalid_intervals))
+}
+
+// gene_snv_replace 和各语言绑定使用的基因预处理：与命令行默认（--dedup-genes first）相同的去重，再按 contig 分组，
+// 使重复 gene_id 的处理在所有入口中一致
+fn group_unique_genes(genes: Vec<GeneInfo>) -> HashMap<String, Vec<GeneInfo>> {
+    group_genes_by_contig(dedup_genes(genes, DedupGenes::default()).0)
+}
+
+fn group_genes_by_contig(genes: Vec<GeneInfo>) -> HashMap<String, Vec<GeneInfo>> {
+    let mut gene_positions_map: HashMap<String, Vec<GeneInfo>> = HashMap::new();
+    for gene_info in genes {
+        gene_positions_map.entry(gene_info.contig_id.clone()).or_insert(vec![]).push(gene_info);
+    }
+    gene_positions_map
+}
+
+// 同一 gene_id 出现多次时的处理方式（--dedup-genes）
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum DedupGenes {
+    #[default]
+    First,   // 同一 contig 上保留输入中第一次出现的记录
+    Longest, // 同一 contig 上保留区间最长的记录，长度相同时保留先出现的
+    None,    // 全部保留，第 n 次出现的记录改名为 gene_id_n
+    Locus,   // 全部保留，重复 gene_id 的每条记录（包括第一条）改名为 gene_id_contig_start_end；位置也相同的记录只保留一条
+}
+
+impl DedupGenes {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "first" => Some(DedupGenes::First),
+            "longest" => Some(DedupGenes::Longest),
+            "none" => Some(DedupGenes::None),
+            "locus" => Some(DedupGenes::Locus),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            DedupGenes::First => "first",
+            DedupGenes::Longest => "longest",
+            DedupGenes::None => "none",
+            DedupGenes::Locus => "locus",
+        }
+    }
+}
+
+// 按策略处理重复的 gene_id，保持输入顺序；返回 (处理后的基因列表, 被合并（或改名）的重复记录数, 因出现在多个 contig 上而改名的记录数)。
+// first 和 longest 按 (contig, gene_id) 合并：同一 gene_id 在多个 contig 上时每个 contig 各保留一条，
+// 并像 locus 一样改名为 gene_id_contig_start_end，不会在输出中互相覆盖
+fn dedup_genes(genes: Vec<GeneInfo>, policy: DedupGenes) -> (Vec<GeneInfo>, usize, usize) {
+    let length = |gene_info: &GeneInfo| gene_info.end_position - gene_info.start_position;
+    let per_contig = matches!(policy, DedupGenes::First | DedupGenes::Longest);
+    let key = |gene_info: &GeneInfo| {
+        let contig_id = if per_contig { gene_info.contig_id.clone() } else { String::new() };
+        (contig_id, gene_info.gene_id.clone())
+    };
+    let mut kept: Vec<GeneInfo> = Vec::with_capacity(genes.len());
+    let mut index_by_id: HashMap<(String, String), usize> = HashMap::new();
+    let mut occurrences: HashMap<String, usize> = HashMap::new();
+    let locus_id =
+        |gene_info: &GeneInfo| format!("{}_{}_{}_{}", gene_info.gene_id, gene_info.contig_id, gene_info.start_position, gene_info.end_position);
+    let mut loci: HashSet<String> = HashSet::new();
+    let mut resolved = 0;
+    for mut gene_info in genes {
+        match (policy, index_by_id.get(&key(&gene_info))) {
+            (_, None) => {
+                index_by_id.insert(key(&gene_info), kept.len());
+                occurrences.insert(gene_info.gene_id.clone(), 1);
+                kept.push(gene_info);
+            }
+            (DedupGenes::First, Some(_)) => resolved += 1,
+            (DedupGenes::Longest, Some(&index)) => {
+                resolved += 1;
+                if length(&gene_info) > length(&kept[index]) {
+                    kept[index] = gene_info;
+                }
+            }
+            (DedupGenes::None, Some(_)) => {
+                resolved += 1;
+                let count = occurrences.entry(gene_info.gene_id.clone()).or_default();
+                *count += 1;
+                gene_info.source_id = Some(gene_info.gene_id.clone());
+                gene_info.gene_id = format!("{}_{}", gene_info.gene_id, count);
+                kept.push(gene_info);
+            }
+            (DedupGenes::Locus, Some(&index)) => {
+                resolved += 1;
+                // 第一条记录在出现重复时才改名，没有重复的 gene_id 保持不变
+                if kept[index].gene_id == gene_info.gene_id {
+                    let first_locus = locus_id(&kept[index]);
+                    loci.insert(first_locus.clone());
+                    kept[index].source_id = Some(std::mem::replace(&mut kept[index].gene_id, first_locus));
+                }
+                let locus = locus_id(&gene_info);
+                if loci.insert(locus.clone()) {
+                    gene_info.source_id = Some(std::mem::replace(&mut gene_info.gene_id, locus));
+                    kept.push(gene_info);
+                }
+            }
+        }
+    }
+    let mut split = 0;
+    if per_contig {
+        let mut contigs_per_id: HashMap<String, usize> = HashMap::new();
+        for gene_info in &kept {
+            *contigs_per_id.entry(gene_info.gene_id.clone()).or_default() += 1;
+        }
+        for gene_info in kept.iter_mut().filter(|gene_info| contigs_per_id[&gene_info.gene_id] > 1) {
+            let locus = locus_id(gene_info);
+            gene_info.source_id = Some(std::mem::replace(&mut gene_info.gene_id, locus));
+            split += 1;
+        }
+    }
+    (kept, resolved, split)
+}
+
+// 按 CSV 规则拆分一行：支持双引号包围的字段和 "" 转义
+fn split_quoted_csv_line(line: &str) -> Vec<String> {
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+enum CoordinateError {
+    Scientific,  // 科学计数法，可能已经丢失精度，任何模式下都拒绝
+    DigitGroups, // 带分隔符的数字，未开启 --lenient-numbers
+    Invalid,
+}
+
+impl CoordinateError {
+    fn message(&self, field: &str) -> String {
+        match self {
+            CoordinateError::Scientific => tr!(
+                "坐标 {:?} 是科学计数法，表格软件导出时可能已经四舍五入（如 1.23457e6 只保留 6 位有效数字），请从源数据重新导出为整数",
+                "coordinate {:?} is in scientific notation and may have been rounded by a spreadsheet export (1.23457e6 keeps only 6 significant digits); re-export it from the source data as an integer",
+                field
+            ),
+            CoordinateError::DigitGroups => tr!(
+                "坐标 {:?} 含有千分位分隔符，可使用 --lenient-numbers 接受",
+                "coordinate {:?} contains digit group separators; --lenient-numbers accepts it",
+                field,
+            ),
+            CoordinateError::Invalid => tr!("坐标 {:?} 不是非负整数", "coordinate {:?} is not a non-negative integer", field),
+        }
+    }
+}
+
+// 解析 position、start、end 等坐标字段；options.lenient_numbers（--lenient-numbers）时接受带千分位逗号或下划线的坐标
+// （如 "1,234,567"、1_234）
+fn parse_coordinate(field: &str, options: &ParseOptions) -> Result<usize, CoordinateError> {
+    let field = field.trim();
+    if let Ok(value) = field.parse::<usize>() {
+        return Ok(value);
+    }
+    if field.contains(['e', 'E']) && field.parse::<f64>().is_ok() {
+        return Err(CoordinateError::Scientific);
+    }
+    let digits: String = field.chars().filter(|&c| c != ',' && c != '_').collect();
+    if digits.len() == field.len() || digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(CoordinateError::Invalid);
+    }
+    if !options.lenient_numbers {
+        return Err(CoordinateError::DigitGroups);
+    }
+    let value = digits.parse::<usize>().map_err(|_| CoordinateError::Invalid)?;
+    tracing::debug!(original = field, value, "salvaged numeric field");
+    Ok(value)
+}
+
+// 解析出的基因行；起点为 0 或起点大于终点的行给出提示后单独返回，不参与处理
+enum GeneRecord {
+    Valid(GeneInfo),
+    InvalidInterval(GeneInfo),
+}
+
+// 解析一行基因位置记录（contig_id, gene_id, start, end[, strand]），CSV 和 SQLite 输入共用同一套校验和提示；
+// 三列记录（contig_id, start, end）没有基因ID，自动生成为 contig_start_end。
+// options.zero_based_genes（--coord-base 0）时起止为 0-based 半开区间（如 BED），解析后立即转为内部使用的 1-based 闭区间
+fn parse_gene_record(record_data: &[&str], options: &ParseOptions) -> Option<GeneRecord> {
+    let (gene_id_field, start_field, end_field, strand_field) = match *record_data {
+        [_, start, end] => (None, start, end, ""),
+        [_, gene_id, start, end] => (Some(gene_id), start, end, ""),
+        [_, gene_id, start, end, strand] => (Some(gene_id), start, end, strand),
+        _ => {
+            warn("invalid_gene_record", tr!("行格式无效：{:?}", "invalid row format: {:?}", record_data));
+            return None;
+        }
+    };
+    let contig_id = record_data[0].to_string();
+    let record_warning = |message: String| {
+        let warning = Warning::new("invalid_gene_record", message).contig(&contig_id);
+        match gene_id_field {
+            Some(gene_id) => warning.gene(gene_id),
+            None => warning,
+        }
+    };
+    let coordinate = |field: &str, name: &str| {
+        parse_coordinate(field, options).map_err(|error| match error {
+            CoordinateError::Invalid => record_warning(tr!("解析 {} 字段出错", "error parsing the {} field", name)).emit(),
+            error => record_warning(tr!("解析 {} 字段出错：{}", "error parsing the {} field: {}", name, error.message(field))).emit(),
+        })
+    };
+    if let Ok(start_position) = coordinate(start_field, "start_position") {
+        if let Ok(end_position) = coordinate(end_field, "end_position") {
+            // 0-based 半开区间 [start, end) 与 1-based 闭区间 [start + 1, end] 相同
+            let start_position = if options.zero_based_genes { start_position.saturating_add(1) } else { start_position };
+            let invalid_interval = if start_position == 0 {
+                record_warning(tr!(
+                    "基因区间 {}-{} 的起点为 0（坐标从 1 开始），已跳过：{:?}",
+                    "gene interval {}-{} starts at 0 (coordinates are 1-based); skipped: {:?}",
+                    start_position,
+                    end_position,
+                    record_data,
+                ))
+                .emit();
+                true
+            } else if start_position > end_position {
+                record_warning(tr!(
+                    "基因区间 {}-{} 为空或起点大于终点，已跳过：{:?}",
+                    "gene interval {}-{} is empty or starts after its end; skipped: {:?}",
+                    start_position,
+                    end_position,
+                    record_data,
+                ))
+                .emit();
+                true
+            } else {
+                false
+            };
+            let strand = match Strand::parse(strand_field) {
+                Some(strand) => strand,
+                None => {
+                    record_warning(tr!("解析 strand 字段出错：{:?}", "error parsing the strand field: {:?}", strand_field)).emit();
+                    return None;
+                }
+            };
+            let gene_id = match gene_id_field {
+                Some(gene_id) => gene_id.to_string(),
+                None => format!("{}_{}_{}", contig_id, start_position, end_position),
+            };
+            let gene_info = GeneInfo {
+                contig_id,
+                start_position,
+                end_position,
+                gene_id,
+                strand,
+                source_id: None,
+            };
+            return Some(if invalid_interval { GeneRecord::InvalidInterval(gene_info) } else { GeneRecord::Valid(gene_info) });
+        }
+    }
+    None
+}
+
+const DEFAULT_POSITIONS_QUERY: &str = "SELECT contig, gene_id, start, end FROM genes";
+
+// 从 SQLite 数据库读取基因位置（需要 sqlite feature），查询必须按顺序返回 contig、gene_id、start、end 四列（可加 strand），
+// 或不含 gene_id 的三列。返回值同 read_gene_positions_checked
+#[cfg(feature = "sqlite")]
+fn read_gene_positions_sqlite(filename: &str, query: &str, options: &ParseOptions) -> io::Result<(Vec<GeneInfo>, Vec<GeneInfo>)> {
+    use rusqlite::types::ValueRef;
+
+    let to_io = |e: rusqlite::Error| io::Error::other(format!("SQLite error: {}", e));
+    let conn = rusqlite::Connection::open_with_flags(filename, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY).map_err(to_io)?;
+    let mut statement = conn.prepare(query).map_err(to_io)?;
+    let column_count = statement.column_count();
+    if !(3..=5).contains(&column_count) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "--positions-query must return 4 columns (contig, gene_id, start, end), optionally a 5th (strand), or 3 (contig, start, end), got {}: {:?}",
+                statement.column_count(),
+                statement.column_names()
+            ),
+        ));
+    }
+
+    let mut genes = vec![];
+    let mut invalid_intervals = vec![];
+    let mut rows = statement.query([]).map_err(to_io)?;
+    while let Some(row) = rows.next().map_err(to_io)? {
+        let mut fields = Vec::with_capacity(column_count);
+        for index in 0..column_count {
+            let field = match row.get_ref(index).map_err(to_io)? {
+                ValueRef::Null => String::new(),
+                ValueRef::Integer(value) => value.to_string(),
+                ValueRef::Real(value) => value.to_string(),
+                ValueRef::Text(text) | ValueRef::Blob(text) => String::from_utf8_lossy(text).trim().to_string(),
+            };
+            fields.push(field);
+        }
+        let record_data: Vec<&str> = fields.iter().map(|field| field.as_str()).collect();
+        match parse_gene_record(&record_data, options) {
+            Some(GeneRecord::Valid(gene_info)) => genes.push(gene_info),
+            Some(GeneRecord::InvalidInterval(gene_info)) => invalid_intervals.push(gene_info),
+            None => {}
+        }
+    }
+    Ok((genes, invalid_intervals))
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn read_gene_positions_sqlite(_filename: &str, _query: &str, _options: &ParseOptions) -> io::Result<(Vec<GeneInfo>, Vec<GeneInfo>)> {
+    Err(io::Error::other("--positions-sqlite requires z10 to be built with the \"sqlite\" feature"))
+}
+
+// 汇总报告基因引用了但 FASTA 中没有的 contig（只报告一次，列出至多 20 个），以及 FASTA 中没有被任何基因引用的 contig
+fn report_missing_contigs(
+    gene_positions_map: &HashMap<String, Vec<GeneInfo>>,
+    contigs: &Contigs,
+    missing_contigs: &BTreeSet<String>,
+) {
+    const MAX_LISTED: usize = 20;
+
+    let listed = |contig_ids: &mut dyn Iterator<Item = &str>, total: usize| {
+        let mut text = contig_ids.take(MAX_LISTED).collect::<Vec<_>>().join(", ");
+        if total > MAX_LISTED {
+            text.push_str(", ...");
+        }
+        text
+    };
+    if !missing_contigs.is_empty() {
+        let genes: usize = missing_contigs.iter().map(|contig_id| gene_positions_map[contig_id].len()).sum();
+        let message = tr!(
+            "警告：{} 个 contig 被基因引用但不在 FASTA 中，其上的 {} 个基因不会输出：{}",
+            "warning: {} contigs are referenced by genes but missing from the FASTA; {} genes on them will not be output: {}",
+            missing_contigs.len(),
+            genes,
+            listed(&mut missing_contigs.iter().map(|c| c.as_str()), missing_contigs.len())
+        );
+        Warning::new("missing_contig", message).emit_summary(missing_contigs.iter().flat_map(|contig_id| {
+            gene_positions_map[contig_id].iter().map(move |gene_info| {
+                Warning::new("missing_contig", tr!(
+                    "基因 {} 所在的 contig {} 不在 FASTA 中",
+                    "contig {1} of gene {0} is not in the FASTA",
+                    gene_info.gene_id,
+                    contig_id,
+                ))
+                    .contig(contig_id)
+                    .gene(&gene_info.gene_id)
+            })
+        }));
+    }
+    let unreferenced: BTreeSet<&str> = contigs
+        .keys()
+        .filter(|contig_id| !gene_positions_map.contains_key(*contig_id))
+        .map(|c| c.as_str())
+        .collect();
+    if !unreferenced.is_empty() {
+        let message = tr!(
+            "警告：{} 个 contig 在 FASTA 中但没有被任何基因引用（ID 不一致？）：{}",
+            "warning: {} contigs are in the FASTA but referenced by no gene (ID mismatch?): {}",
+            unreferenced.len(),
+            listed(&mut unreferenced.iter().copied(), unreferenced.len())
+        );
+        Warning::new("unreferenced_contig", message).emit_summary(unreferenced.iter().map(|contig_id| {
+            Warning::new("unreferenced_contig", tr!(
+                "contig {} 没有被任何基因引用",
+                "contig {} is referenced by no gene",
+                contig_id,
+            )).contig(contig_id)
+        }));
+    }
+}
+
+// 写出未输出的基因：gene_id、contig、起止位置和原因（missing_contig、high_n_fraction、invalid_interval、out_of_range，
+// 或 liftover_failed，此时为换算前的坐标）
+fn write_dropped_genes(
+    filename: &str,
+    gene_positions_map: &HashMap<String, Vec<GeneInfo>>,
+    missing_contigs: &BTreeSet<String>,
+    excluded_contigs: &BTreeSet<String>,
+    unlifted_genes: &[GeneInfo],
+    invalid_interval_genes: &[GeneInfo],
+    out_of_range_genes: &[GeneInfo],
+) -> io::Result<()> {
+    let mut dropped_file = BufWriter::new(File::create(filename)?);
+    writeln!(dropped_file, "gene_id\tcontig\tstart\tend\treason")?;
+    let dropped = missing_contigs
+        .iter()
+        .map(|contig_id| (contig_id, "missing_contig"))
+        .chain(excluded_contigs.iter().map(|contig_id| (contig_id, "high_n_fraction")));
+    for (contig_id, reason) in dropped {
+        for gene_info in gene_positions_map.get(contig_id).into_iter().flatten() {
+            writeln!(
+                dropped_file,
+                "{}\t{}\t{}\t{}\t{}",
+                gene_info.gene_id, gene_info.contig_id, gene_info.start_position, gene_info.end_position, reason
+            )?;
+        }
+    }
+    let dropped_genes = unlifted_genes
+        .iter()
+        .map(|gene_info| (gene_info, "liftover_failed"))
+        .chain(invalid_interval_genes.iter().map(|gene_info| (gene_info, "invalid_interval")))
+        .chain(out_of_range_genes.iter().map(|gene_info| (gene_info, "out_of_range")));
+    for (gene_info, reason) in dropped_genes {
+        writeln!(
+            dropped_file,
+            "{}\t{}\t{}\t{}\t{}",
+            gene_info.gene_id, gene_info.contig_id, gene_info.start_position, gene_info.end_position, reason
+        )?;
+    }
+    dropped_file.flush()
+}
+
+// 基因区间是否落在长度为 contig_length 的 contig 内（1 <= start <= end <= 长度）；截取基因序列之前必须满足
+fn gene_fits_contig(gene_info: &GeneInfo, contig_length: usize) -> bool {
+    gene_info.start_position >= 1
+        && gene_info.start_position <= gene_info.end_position
+        && gene_info.end_position <= contig_length
+}
+
+// 读取 contig 坐标偏移文件，每行格式为 contig_id,offset（也接受制表符分隔）
+fn read_contig_offsets(filename: &str) -> io::Result<HashMap<String, i64>> {
+    let reader = BufReader::new(open_input(filename)?);
+
+    let mut offsets = HashMap::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let parts: Vec<&str> = line.split([',', '\t']).map(|part| part.trim()).collect();
+        if parts.len() == 2 {
+            if let Ok(offset) = parts[1].parse::<i64>() {
+                offsets.insert(parts[0].to_string(), offset);
+            } else {
+                let message = tr!("解析 contig 偏移量字段出错：{}", "error parsing the contig offset field: {}", line);
+                Warning::new("invalid_offset_record", message).contig(parts[0]).emit();
+            }
+        } else {
+            warn("invalid_offset_record", tr!("行格式无效：{}", "invalid row format: {}", line));
+        }
+    }
+    Ok(offsets)
+}
+
+// 比较基因文件和突变文件引用的 contig 集合：只有基因的 contig 输出将与参考序列相同，
+// 只有突变的 contig 上的突变不会被应用
+fn report_contig_set_differences<'a>(
+    gene_positions_map: &HashMap<String, Vec<GeneInfo>>,
+    mutations: impl IntoIterator<Item = &'a Mutation>,
+) {
+    const MAX_LISTED: usize = 20;
+
+    let gene_contigs: BTreeSet<&str> = gene_positions_map.keys().map(|c| c.as_str()).collect();
+    let mutation_contigs: BTreeSet<&str> = mutations.into_iter().map(|m| m.contig_id.as_str()).collect();
+
+    let genes_only: Vec<&str> = gene_contigs.difference(&mutation_contigs).copied().collect();
+    let mutations_only: Vec<&str> = mutation_contigs.difference(&gene_contigs).copied().collect();
+
+    let listed = |contigs: &[&str]| {
+        let mut text = contigs.iter().take(MAX_LISTED).copied().collect::<Vec<_>>().join(", ");
+        if contigs.len() > MAX_LISTED {
+            text.push_str(", ...");
+        }
+        text
+    };
+    if !genes_only.is_empty() {
+        let message = tr!(
+            "警告：{} 个 contig 上有基因但没有任何突变（这些基因将与参考序列相同）：{}",
+            "warning: {} contigs have genes but no mutations (these genes will match the reference): {}",
+            genes_only.len(),
+            listed(&genes_only)
+        );
+        Warning::new("contig_without_mutations", message).emit_summary(genes_only.iter().map(|contig_id| {
+            Warning::new("contig_without_mutations", tr!(
+                "contig {} 上有基因但没有任何突变",
+                "contig {} has genes but no mutations",
+                contig_id,
+            )).contig(contig_id)
+        }));
+    }
+    if !mutations_only.is_empty() {
+        let message = tr!(
+            "警告：{} 个 contig 上有突变但没有任何基因（这些突变不会被应用）：{}",
+            "warning: {} contigs have mutations but no genes (these mutations will not be applied): {}",
+            mutations_only.len(),
+            listed(&mutations_only)
+        );
+        Warning::new("contig_without_genes", message).emit_summary(mutations_only.iter().map(|contig_id| {
+            Warning::new("contig_without_genes", tr!(
+                "contig {} 上有突变但没有任何基因",
+                "contig {} has mutations but no genes",
+                contig_id,
+            )).contig(contig_id)
+        }));
+    }
+}
+
+// --check：顺序读取 FASTA，只统计每个 contig 的长度，不保留序列；contig ID 与 read_contigs 相同取标题的第一个词
+fn read_fasta_lengths(filename: &str) -> io::Result<HashMap<String, usize>> {
+    let mut reader = HashingReader::new(open_input(filename)?);
+    let mut lengths = HashMap::new();
+    let mut current_id = String::new();
+    let mut current_length = 0;
+    for line in BufReader::new(&mut reader).lines() {
+        let line = line?;
+        if let Some(header) = line.strip_prefix('>') {
+            if !current_id.is_empty() && current_length > 0 {
+                lengths.insert(std::mem::take(&mut current_id), current_length);
+            }
+            current_id = header.split_whitespace().next().unwrap_or_default().to_string();
+            current_length = 0;
+        } else {
+            current_length += line.len();
+        }
+    }
+    if !current_id.is_empty() && current_length > 0 {
+        lengths.insert(current_id, current_length);
+    }
+    reader.record_input(filename);
+    Ok(lengths)
+}
+
+// 读取 bedGraph 覆盖度轨道（chrom、0-based start、end、value），只保留覆盖度不低于 min_coverage 的区间，
+// 按 contig 分组并转换为合并后的 1-based 闭区间
+fn read_coverage_intervals(filename: &str, min_coverage: f64) -> io::Result<HashMap<String, Vec<(usize, usize)>>> {
+    let mut reader = HashingReader::new(open_input(filename)?);
+    let mut intervals: HashMap<String, Vec<(usize, usize)>> = HashMap::new();
+    for (line_number, line) in BufReader::new(&mut reader).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with('#') || line.starts_with("track") || line.starts_with("browser") {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let parsed = match fields[..] {
+            [contig, start, end, value, ..] => start
+                .parse::<usize>()
+                .ok()
+                .zip(end.parse::<usize>().ok())
+                .zip(value.parse::<f64>().ok())
+                .map(|((start, end), value)| (contig, start, end, value)),
+            _ => None,
+        };
+        let (contig, start, end, value) = parsed.ok_or_else(|| {
+            GeneReplaceError::ParseError(tr!(
+                "{} 第 {} 行不是 bedGraph 格式（chrom start end value）",
+                "{} line {} is not in bedGraph format (chrom start end value)",
+                filename,
+                line_number + 1,
+            ))
+        })?;
+        if value >= min_coverage && start < end {
+            intervals.entry(contig.to_string()).or_default().push((start + 1, end));
+        }
+    }
+    reader.record_input(filename);
+    // 排序并合并相邻或重叠的区间，得到互不重叠、终点递增的区间序列
+    for contig_intervals in intervals.values_mut() {
+        contig_intervals.sort_unstable();
+        let mut merged: Vec<(usize, usize)> = Vec::with_capacity(contig_intervals.len());
+        for &(start, end) in contig_intervals.iter() {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 + 1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        *contig_intervals = merged;
+    }
+    Ok(intervals)
+}
+
+// 将基因中覆盖度不足（不在任何保留区间内）的位置替换为 N，不论该位置是否应用了突变；返回被屏蔽的位置数
+fn mask_low_coverage(
+    mutated_genes: &mut HashMap<String, String>,
+    gene_summaries: &[GeneSummary],
+    coverage: &HashMap<String, Vec<(usize, usize)>>,
+) -> usize {
+    let mut masked = 0;
+    for summary in gene_summaries {
+        let gene = &summary.gene;
+        let Some(sequence) = mutated_genes.get_mut(&gene.gene_id) else {
+            continue;
+        };
+        let intervals = coverage.get(&gene.contig_id).map_or(&[][..], |intervals| intervals.as_slice());
+        let mut bases = std::mem::take(sequence).into_bytes();
+        // 区间互不重叠且有序：从第一个终点不早于基因起点的区间开始，随位置顺序推进
+        let mut next_interval = intervals.partition_point(|&(_, end)| end < gene.start_position);
+        for (offset, base) in bases.iter_mut().enumerate() {
+            let position = gene.start_position + offset;
+            while next_interval < intervals.len() && intervals[next_interval].1 < position {
+                next_interval += 1;
+            }
+            let covered = intervals.get(next_interval).is_some_and(|&(start, _)| start <= position);
+            if !covered {
+                *base = b'N';
+                masked += 1;
+            }
+        }
+        *sequence = String::from_utf8_lossy(&bases).into_owned();
+    }
+    masked
+}
+
+// 读取 contig 长度清单（contig<TAB>长度），只取前两列，因此 samtools faidx 的 .fai 和 genome 文件都可直接使用
+fn read_contig_lengths(filename: &str) -> io::Result<HashMap<String, usize>> {
+    let reader = BufReader::new(open_input(filename)?);
+    let mut contig_lengths = HashMap::new();
+    for (line_number, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.split('\t');
+        let contig = fields.next().unwrap_or_default();
+        let length = fields.next().and_then(|field| field.trim().parse::<usize>().ok()).ok_or_else(|| {
+            GeneReplaceError::ParseError(tr!(
+                "{} 第 {} 行不是 contig<TAB>长度 格式",
+                "{} line {} is not in contig<TAB>length format",
+                filename,
+                line_number + 1,
+            ))
+        })?;
+        contig_lengths.insert(contig.to_string(), length);
+    }
+    Ok(contig_lengths)
+}
+
+// 只根据 contig 长度检查基因区间和突变位置（contig 是否存在、是否越界），返回发现的问题数
+fn validate_coordinates(
+    gene_positions_map: &HashMap<String, Vec<GeneInfo>>,
+    mutations: &[Mutation],
+    contig_lengths: &HashMap<String, usize>,
+) -> usize {
+    const MAX_LISTED: usize = 20;
+
+    let mut problems = vec![];
+    for gene_info in gene_positions_map.values().flatten() {
+        match contig_lengths.get(&gene_info.contig_id) {
+            None => problems.push(
+                Warning::new("missing_contig", tr!(
+                    "基因 {} 所在的 contig {} 不存在",
+                    "contig {1} of gene {0} does not exist",
+                    gene_info.gene_id,
+                    gene_info.contig_id,
+                ))
+                    .contig(&gene_info.contig_id)
+                    .gene(&gene_info.gene_id),
+            ),
+            Some(&length) if gene_info.end_position > length => problems.push(
+                Warning::new(
+                    "gene_out_of_range",
+                    tr!(
+                        "基因 {} 的终止位置 {} 超出 contig {} 的长度 {}",
+                        "end position {1} of gene {0} is past the length {3} of contig {2}",
+                        gene_info.gene_id, gene_info.end_position, gene_info.contig_id, length
+                    ),
+                )
+                .contig(&gene_info.contig_id)
+                .gene(&gene_info.gene_id)
+                .position(gene_info.end_position),
+            ),
+            Some(_) => {}
+        }
+    }
+    for mutation in mutations {
+        match contig_lengths.get(&mutation.contig_id) {
+            None => problems.push(
+                Warning::new("missing_contig", tr!(
+                    "突变 {} 所在的 contig {} 不存在",
+                    "contig {1} of mutation {0} does not exist",
+                    mutation.id_or_dot(),
+                    mutation.contig_id,
+                ))
+                    .contig(&mutation.contig_id)
+                    .position(mutation.position),
+            ),
+            Some(&length) if mutation.position > length => problems.push(
+                Warning::new(
+                    "mutation_out_of_range",
+                    tr!(
+                        "突变 {} 的位置 {}:{} 超出 contig 长度 {}",
+                        "position {1}:{2} of mutation {0} is past the contig length {3}",
+                        mutation.id_or_dot(), mutation.contig_id, mutation.position, length
+                    ),
+                )
+                .contig(&mutation.contig_id)
+                .position(mutation.position),
+            ),
+            Some(_) => {}
+        }
+    }
+
+    // 标准错误只列出前 MAX_LISTED 个问题，--warnings-json 写出全部
+    let total = problems.len();
+    for (index, problem) in problems.into_iter().enumerate() {
+        if index < MAX_LISTED {
+            problem.emit();
+        } else {
+            problem.record();
+        }
+    }
+    if total > MAX_LISTED {
+        eprintln!("{}", tr!("……另有 {} 个问题未列出", "... {} more problems not listed", total - MAX_LISTED));
+    }
+    total
+}
+
+// 按 contig 偏移量调整突变位置，调整后超出 contig 范围的突变会被丢弃并给出警告。
+// contig_length 给出 contig 的长度，未知的 contig 只检查下界
+fn apply_contig_offsets(
+    mutations: Vec<Mutation>,
+    contig_offsets: &HashMap<String, i64>,
+    contig_length: impl Fn(&str) -> Option<usize>,
+) -> (Vec<Mutation>, Vec<Mutation>) {
+    let mut dropped = vec![];
+    let kept = mutations
+        .into_iter()
+        .filter_map(|mut mutation| {
+            let offset = match contig_offsets.get(&mutation.contig_id) {
+                Some(offset) => *offset,
+                None => return Some(mutation),
+            };
+            let adjusted = i64::try_from(mutation.position).ok().and_then(|position| position.checked_add(offset));
+            let contig_len = contig_length(&mutation.contig_id).map(|len| len as i64);
+            let Some(adjusted) = adjusted.filter(|&adjusted| adjusted >= 1 && contig_len.is_none_or(|len| adjusted <= len)) else {
+                let message =
+                    tr!(
+                        "偏移后的突变位置超出范围：{}:{} (offset {})",
+                        "mutation position out of range after offset: {}:{} (offset {})",
+                        mutation.contig_id,
+                        mutation.position,
+                        offset,
+                    );
+                Warning::new("offset_out_of_range", message).contig(&mutation.contig_id).position(mutation.position).emit();
+                COUNTERS.rejects_out_of_range.fetch_add(1, Ordering::Relaxed);
+                dropped.push(mutation);
+                return None;
+            };
+            mutation.position = adjusted as usize;
+            Some(mutation)
+        })
+        .collect();
+    (kept, dropped)
+}
+
+// --exclude-boundary：去掉恰好落在某个基因（only_strand 时只看该链上的基因）第一个或最后一个碱基上的突变，
+// 即使该位点同时在另一个重叠基因的内部。在应用之前过滤，两种 strategy 和所有报告看到的是同一组突变。
+// 基因区间是闭区间 [start, end]，不加这个选项时两端的突变都会应用。返回 (保留的突变, 去掉的突变)
+fn exclude_boundary_mutations(
+    mutations: Vec<Mutation>,
+    gene_positions_map: &HashMap<String, Vec<GeneInfo>>,
+    only_strand: Option<Strand>,
+) -> (Vec<Mutation>, Vec<Mutation>) {
+    let boundaries: HashSet<(&str, usize)> = gene_positions_map
+        .iter()
+        .flat_map(|(contig_id, gene_info_list)| {
+            gene_info_list
+                .iter()
+                .filter(|gene_info| only_strand.is_none_or(|strand| gene_info.strand == strand))
+                .flat_map(move |gene_info| [(contig_id.as_str(), gene_info.start_position), (contig_id.as_str(), gene_info.end_position)])
+        })
+        .collect();
+    mutations.into_iter().partition(|mutation| !boundaries.contains(&(mutation.contig_id.as_str(), mutation.position)))
+}
+
+// 读取 gene_id -> 分数 对应表（每行 gene_id,score，也接受制表符分隔，# 开头为注释）
+fn read_gene_scores(filename: &str) -> io::Result<HashMap<String, f64>> {
+    let mut scores = HashMap::new();
+    for (line_number, line) in BufReader::new(open_input(filename)?).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let score = line
+            .split_once([',', '\t'])
+            .and_then(|(gene_id, score)| Some((gene_id.trim(), score.trim().parse::<f64>().ok().filter(|score| !score.is_nan())?)));
+        match score {
+            Some((gene_id, score)) => {
+                scores.insert(gene_id.to_string(), score);
+            }
+            None => {
+                return Err(GeneReplaceError::ParseError(tr!(
+                    "{} 第 {} 行不是 gene_id,score 格式", "{} line {} is not in gene_id,score format",
+                    filename,
+                    line_number + 1
+                ))
+                .into());
+            }
+        }
+    }
+    Ok(scores)
+}
+
+// 按分数从高到低排列基因摘要；没有分数的基因排在最后，分数相同时按基因ID。
+// --variant-set 的记录 {gene}__{set} 在找不到自身分数时使用 {gene} 的分数
+fn order_by_score(gene_summaries: &[GeneSummary], scores: &HashMap<String, f64>) -> Vec<GeneSummary> {
+    let score_of = |gene_id: &str| {
+        scores
+            .get(gene_id)
+            .or_else(|| gene_id.rsplit_once("__").and_then(|(gene_id, _)| scores.get(gene_id)))
+            .copied()
+    };
+    let mut ordered = gene_summaries.to_vec();
+    ordered.sort_by(|a, b| {
+        let (a_score, b_score) = (score_of(&a.gene.gene_id), score_of(&b.gene.gene_id));
+        match (a_score, b_score) {
+            (Some(a_score), Some(b_score)) => b_score.total_cmp(&a_score),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => std::cmp::Ordering::Equal,
+        }
+        .then_with(|| a.gene.gene_id.cmp(&b.gene.gene_id))
+    });
+    ordered
+}
+
+// 比对得到的参考序列中表示空位的字符
+const GAP: u8 = b'-';
+
+// --gap-aware：把基因区间和突变位置从不含空位的坐标（只数碱基）换算为带空位序列中的位置。
+// 基因区间从第一个碱基到最后一个碱基，中间的空位保留在输出中；突变总是落在碱基上，不会改写空位。
+// 超出碱基数的坐标按超出量放到序列末尾之后，仍由后续的范围检查处理。返回含有空位的 contig 数
+fn apply_gap_aware<'a>(
+    gene_positions_map: &mut HashMap<String, Vec<GeneInfo>>,
+    mutations: impl IntoIterator<Item = &'a mut Mutation>,
+    contigs: &Contigs,
+) -> usize {
+    let mut base_positions: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (contig_id, sequence) in contigs {
+        if sequence.contains(&GAP) {
+            let positions = sequence.iter().enumerate().filter(|&(_, &base)| base != GAP).map(|(index, _)| index + 1).collect();
+            base_positions.insert(contig_id.as_str(), positions);
+        }
+    }
+    let to_gapped = |contig_id: &str, position: usize| -> usize {
+        match base_positions.get(contig_id) {
+            Some(positions) if position > 0 => positions
+                .get(position - 1)
+                .copied()
+                .unwrap_or_else(|| contigs[contig_id].len() + (position - positions.len())),
+            _ => position,
+        }
+    };
+    for (contig_id, gene_info_list) in gene_positions_map.iter_mut() {
+        for gene_info in gene_info_list {
+            gene_info.start_position = to_gapped(contig_id, gene_info.start_position);
+            gene_info.end_position = to_gapped(contig_id, gene_info.end_position);
+        }
+    }
+    for mutation in mutations {
+        mutation.position = to_gapped(&mutation.contig_id, mutation.position);
+    }
+    base_positions.len()
+}
+
+// UCSC chain 文件中的一条 chain，把 target（注释所在的组装）坐标映射到 query（参考组装）坐标
+#[derive(Debug)]
+struct Chain {
+    score: f64,
+    query_name: String,
+    query_size: usize,
+    query_minus: bool, // query 为反向链时映射后的坐标按正向链换算，基因的链随之翻转
+    // 无空位的比对块：(target 起点, query 起点, 长度)，0-based，按 target 起点递增
+    blocks: Vec<(usize, usize, usize)>,
+}
+
+impl Chain {
+    // 0-based target 位置映射到 query 正向链上的 0-based 位置；落在空位中时返回 None
+    fn lift_position(&self, position: usize) -> Option<usize> {
+        let index = self.blocks.partition_point(|&(target_start, _, _)| target_start <= position).checked_sub(1)?;
+        let (target_start, query_start, size) = self.blocks[index];
+        if position >= target_start + size {
+            return None;
+        }
+        let query_position = query_start + (position - target_start);
+        Some(if self.query_minus { self.query_size - 1 - query_position } else { query_position })
+    }
+}
+
+// --liftover：按 target contig 分组的 chain，每组按 score 从高到低
+#[derive(Debug, Default)]
+struct Liftover {
+    chains: HashMap<String, Vec<Chain>>,
+}
+
+impl Liftover {
+    fn read(filename: &str) -> io::Result<Self> {
+        let invalid = |line_number: usize, message: &str| {
+            io::Error::from(GeneReplaceError::ParseError(tr!(
+                "{} 第 {} 行：{}",
+                "{} line {}: {}",
+                filename,
+                line_number + 1,
+                message,
+            )))
+        };
+        let mut chains: HashMap<String, Vec<Chain>> = HashMap::new();
+        // 当前 chain 的 target 名及下一个块的 target/query 起点
+        let mut current: Option<(String, Chain, usize, usize)> = None;
+        for (line_number, line) in BufReader::new(open_input(filename)?).lines().enumerate() {
+            let line = line?;
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.first() == Some(&"chain") {
+                if fields.len() < 12 {
+                    return Err(invalid(line_number, &tr!("chain 行的列数不足", "chain line has too few columns")));
+                }
+                let number = |index: usize| fields[index].parse::<usize>().map_err(|_| invalid(line_number, &tr!(
+                    "chain 行的坐标不是整数",
+                    "chain line coordinate is not an integer",
+                )));
+                let chain = Chain {
+                    score: fields[1].parse().map_err(|_| invalid(line_number, &tr!(
+                        "chain 行的 score 不是数字",
+                        "chain line score is not a number",
+                    )))?,
+                    query_name: fields[7].to_string(),
+                    query_size: number(8)?,
+                    query_minus: fields[9] == "-",
+                    blocks: vec![],
+                };
+                if let Some((target_name, finished, _, _)) = current.replace((fields[2].to_string(), chain, number(5)?, number(10)?)) {
+                    chains.entry(target_name).or_default().push(finished);
+                }
+            } else if !fields.is_empty() {
+                let Some((_, chain, target_next, query_next)) = current.as_mut() else {
+                    return Err(invalid(line_number, &tr!(
+                        "比对块出现在第一个 chain 行之前",
+                        "alignment block before the first chain line",
+                    )));
+                };
+                let values: Vec<usize> = fields
+                    .iter()
+                    .map(|field| field.parse())
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| invalid(line_number, &tr!("比对块不是整数", "alignment block is not an integer")))?;
+                let (size, target_gap, query_gap) = match values[..] {
+                    [size] => (size, 0, 0),
+                    [size, target_gap, query_gap] => (size, target_gap, query_gap),
+                    _ => return Err(invalid(line_number, &tr!(
+                        "比对块应为 size [dt dq]",
+                        "alignment block should be size [dt dq]",
+                    ))),
+                };
+                chain.blocks.push((*target_next, *query_next, size));
+                *target_next += size + target_gap;
+                *query_next += size + query_gap;
+            }
+        }
+        if let Some((target_name, finished, _, _)) = current {
+            chains.entry(target_name).or_default().push(finished);
+        }
+        for target_chains in chains.values_mut() {
+            target_chains.sort_by(|a, b| b.score.total_cmp(&a.score));
+        }
+        Ok(Liftover { chains })
+    }
+
+    // 1-based 闭区间 [start, end] 换算到参考组装：两端落在同一条 chain 的比对块中才算成功（依 score 取第一条），
+    // 返回 (query contig, 起点, 终点, 是否反向)
+    fn lift_interval(&self, contig_id: &str, start: usize, end: usize) -> Option<(&str, usize, usize, bool)> {
+        self.chains.get(contig_id)?.iter().find_map(|chain| {
+            let first = chain.lift_position(start.checked_sub(1)?)?;
+            let last = chain.lift_position(end.checked_sub(1)?)?;
+            Some((chain.query_name.as_str(), first.min(last) + 1, first.max(last) + 1, chain.query_minus))
+        })
+    }
+}
+
+// 换算基因坐标；反向 chain 上的基因链翻转。返回 (换算成功的基因, 失败的基因)
+fn lift_genes(genes: Vec<GeneInfo>, liftover: &Liftover) -> (Vec<GeneInfo>, Vec<GeneInfo>) {
+    let mut lifted = vec![];
+    let mut unlifted = vec![];
+    for mut gene_info in genes {
+        match liftover.lift_interval(&gene_info.contig_id, gene_info.start_position, gene_info.end_position) {
+            Some((contig_id, start, end, reversed)) => {
+                gene_info.contig_id = contig_id.to_string();
+                gene_info.start_position = start;
+                gene_info.end_position = end;
+                if reversed {
+                    gene_info.strand = match gene_info.strand {
+                        Strand::Plus => Strand::Minus,
+                        Strand::Minus => Strand::Plus,
+                        Strand::Unknown => Strand::Unknown,
+                    };
+                }
+                lifted.push(gene_info);
+            }
+            None => unlifted.push(gene_info),
+        }
+    }
+    (lifted, unlifted)
+}
+
+// 报告无法换算的基因（它们不会输出）
+fn warn_unlifted_genes(unlifted: &[GeneInfo]) {
+    if unlifted.is_empty() {
+        return;
+    }
+    let listed: Vec<&str> = unlifted.iter().take(20).map(|gene_info| gene_info.gene_id.as_str()).collect();
+    let more = if unlifted.len() > listed.len() { ", ..." } else { "" };
+    let message = tr!(
+        "警告：{} 个基因无法通过 chain 换算坐标，不会输出：{}{}",
+        "warning: {} genes could not be lifted through the chain and will not be output: {}{}",
+        unlifted.len(),
+        listed.join(", "),
+        more,
+    );
+    Warning::new("gene_liftover_failed", message).emit_summary(unlifted.iter().map(|gene_info| {
+        Warning::new("gene_liftover_failed", tr!(
+            "基因 {} 无法通过 chain 换算坐标",
+            "gene {} could not be lifted through the chain",
+            gene_info.gene_id,
+        ))
+            .contig(&gene_info.contig_id)
+            .gene(&gene_info.gene_id)
+    }));
+}
+
+fn warn_unlifted_mutations(unlifted: usize) {
+    if unlifted > 0 {
+        warn("mutation_liftover_failed", tr!(
+            "警告：{} 个突变无法通过 chain 换算坐标，已跳过",
+            "warning: {} mutations could not be lifted through the chain and were skipped",
+            unlifted,
+        ));
+    }
+}
+
+// 换算突变位置；反向 chain 上的替换碱基取互补。返回 (换算成功的突变, 失败的突变数)
+fn lift_mutations(mutations: Vec<Mutation>, liftover: &Liftover) -> (Vec<Mutation>, Vec<Mutation>) {
+    let mut unlifted = vec![];
+    let lifted: Vec<Mutation> = mutations
+        .into_iter()
+        .filter_map(|mut mutation| {
+            // 插入/缺失的整个参考区间必须换算到同一条正向 chain 上且长度不变，否则视为换算失败
+            let span = mutation.indel.as_ref().map_or(1, |indel| indel.reference.len());
+            let lifted = liftover.lift_interval(&mutation.contig_id, mutation.position, mutation.position + span - 1);
+            let Some((contig_id, position, _, reversed)) =
+                lifted.filter(|&(_, position, end, reversed)| mutation.indel.is_none() || (!reversed && end + 1 - position == span))
+            else {
+                unlifted.push(mutation);
+                return None;
+            };
+            mutation.contig_id = contig_id.to_string();
+            mutation.position = position;
+            if reversed && mutation.new_base.is_ascii() {
+                mutation.new_base = complement_base(mutation.new_base as u8) as char;
+            }
+            if let Some(expected_ref) = mutation.expected_ref.filter(|base| reversed && base.is_ascii()) {
+                mutation.expected_ref = Some(complement_base(expected_ref as u8) as char);
+            }
+            Some(mutation)
+        })
+        .collect();
+    (lifted, unlifted)
+}
+
+fn is_remote(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+// 远程输入：小文件整体下载到临时目录（可校验 SHA-256），FASTA 配合 .fai 时按字节范围读取
+#[cfg(feature = "remote")]
+mod remote {
+    use super::{parse_fai, warn, Contigs, FaiEntry, RemoteOptions, Read, SequenceSource, Write};
+    use sha2::{Digest, Sha256};
+    use std::collections::{HashMap, HashSet};
+    use std::fs::File;
+    use std::io;
+    use std::path::PathBuf;
+
+    // 发送 GET 请求，失败时按指数退避重试；错误信息包含 URL 和 HTTP 状态码
+    fn get_with_retries(url: &str, range: Option<(u64, u64)>, options: &RemoteOptions) -> io::Result<ureq::Response> {
+        let mut backoff = options.retry_backoff;
+        let mut attempt = 0;
+        loop {
+            let mut request = ureq::get(url);
+            if let Some((start, end)) = range {
+                request = request.set("Range", &format!("bytes={}-{}", start, end));
+            }
+            let error = match request.call() {
+                Ok(response) => return Ok(response),
+                // 4xx 错误重试没有意义
+                Err(ureq::Error::Status(status, _)) if (400..500).contains(&status) => {
+                    return Err(io::Error::other(format!("GET {} failed: HTTP {}", url, status)));
+                }
+                Err(ureq::Error::Status(status, _)) => format!("HTTP {}", status),
+                Err(ureq::Error::Transport(transport)) => transport.to_string(),
+            };
+            if attempt >= options.retries {
+                return Err(io::Error::other(format!("GET {} failed after {} attempts: {}", url, attempt + 1, error)));
+            }
+            warn("http_retry", format!("GET {} failed ({}), retrying in {:?}", url, error, backoff));
+            std::thread::sleep(backoff);
+            backoff *= 2;
+            attempt += 1;
+        }
+    }
+
+    // 下载到临时文件并返回本地路径；给出期望的 SHA-256 时校验
+    pub fn download(url: &str, options: &RemoteOptions) -> io::Result<PathBuf> {
+        let file_name: String = url
+            .rsplit('/')
+            .next()
+            .unwrap_or("input")
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+        let path = std::env::temp_dir().join(format!("z10-{}-{}", std::process::id(), file_name));
+
+        let response = get_with_retries(url, None, options)?;
+        let mut reader = response.into_reader();
+        let mut file = File::create(&path)?;
+        let mut hasher = Sha256::new();
+        let mut buffer = [0u8; 64 * 1024];
+        loop {
+            let n = reader.read(&mut buffer)?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buffer[..n]);
+            file.write_all(&buffer[..n])?;
+        }
+        file.flush()?;
+
+        if let Some(expected) = options.expect_sha256.get(url) {
+            let actual: String = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+            if !actual.eq_ignore_ascii_case(expected) {
+                let _ = std::fs::remove_file(&path);
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("SHA-256 mismatch for {}: expected {}, got {}", url, expected, actual),
+                ));
+            }
+        }
+        Ok(path)
+    }
+
+    fn fetch_text(url: &str, options: &RemoteOptions) -> io::Result<String> {
+        let mut text = String::new();
+        get_with_retries(url, None, options)?.into_reader().read_to_string(&mut text)?;
+        Ok(text)
+    }
+
+    // 远程 FASTA 配合 .fai：只对需要的 contig 发起字节范围请求
+    pub struct IndexedRemoteFasta<'a> {
+        pub fasta_url: &'a str,
+        pub fai_url: &'a str,
+        pub options: &'a RemoteOptions,
+    }
+
+    impl SequenceSource for IndexedRemoteFasta<'_> {
+        // 内存上限按下载的字节数检查，与本地读取一致
+        fn read_contigs(
+            &self,
+            wanted: Option<&HashSet<&str>>,
+            max_memory: Option<usize>,
+        ) -> io::Result<(Contigs, HashMap<String, usize>)> {
+            let contigs = read_contigs_by_range(self.fasta_url, self.fai_url, wanted, self.options)?;
+            let loaded_bytes: usize = contigs.0.values().map(|sequence| sequence.len()).sum();
+            match max_memory {
+                Some(limit) if loaded_bytes > limit => Err(super::memory_limit_error(&tr!(
+                    "读取 contigs",
+                    "reading contigs",
+                ), loaded_bytes, limit)),
+                _ => Ok(contigs),
+            }
+        }
+    }
+
+    // 根据 .fai 只对需要的 contig（wanted 为 None 时为索引中的全部 contig）发起字节范围请求，返回值与 read_contigs 相同
+    fn read_contigs_by_range(
+        fasta_url: &str,
+        fai_url: &str,
+        wanted: Option<&HashSet<&str>>,
+        options: &RemoteOptions,
+    ) -> io::Result<(Contigs, HashMap<String, usize>)> {
+        let index = parse_fai(&fetch_text(fai_url, options)?);
+
+        let mut contigs = HashMap::new();
+        let mut n_counts = HashMap::new();
+        let wanted: Vec<&str> = match wanted {
+            Some(wanted) => wanted.iter().copied().collect(),
+            None => index.keys().map(String::as_str).collect(),
+        };
+        for contig_id in wanted {
+            let Some((first, last)) = index.get(contig_id).and_then(FaiEntry::byte_range) else {
+                continue; // 与本地 FASTA 缺少 contig 时的处理一致，后续按缺失 contig 报告
+            };
+            let mut bytes = vec![];
+            let response = get_with_retries(fasta_url, Some((first, last)), options)?;
+            if response.status() != 206 {
+                return Err(io::Error::other(format!(
+                    "GET {} did not honour the range request: HTTP {}",
+                    fasta_url,
+                    response.status()
+                )));
+            }
+            response.into_reader().read_to_end(&mut bytes)?;
+            bytes.retain(|b| *b != b'\n' && *b != b'\r');
+            let n_count = bytes.iter().filter(|&&b| b == b'N' || b == b'n').count();
+            contigs.insert(contig_id.to_string(), bytes);
+            n_counts.insert(contig_id.to_string(), n_count);
+        }
+        Ok((contigs, n_counts))
+    }
+}
+
+#[cfg(feature = "remote")]
+use remote::download;
+
+#[cfg(feature = "remote")]
+fn remote_sequence_source<'a>(
+    fasta_url: &'a str,
+    fai_url: &'a str,
+    options: &'a RemoteOptions,
+) -> io::Result<Box<dyn SequenceSource + 'a>> {
+    Ok(Box::new(remote::IndexedRemoteFasta { fasta_url, fai_url, options }))
+}
+
+#[cfg(not(feature = "remote"))]
+fn download(url: &str, _options: &RemoteOptions) -> io::Result<std::path::PathBuf> {
+    Err(io::Error::other(format!("{}: http(s) inputs require z10 to be built with the \"remote\" feature", url)))
+}
+
+#[cfg(not(feature = "remote"))]
+fn remote_sequence_source<'a>(
+    fasta_url: &'a str,
+    _fai_url: &'a str,
+    _options: &'a RemoteOptions,
+) -> io::Result<Box<dyn SequenceSource + 'a>> {
+    Err(io::Error::other(format!("{}: http(s) inputs require z10 to be built with the \"remote\" feature", fasta_url)))
+}
+
+// Python 绑定（需要 python feature，用 maturin 构建为扩展模块 z10）
+#[cfg(feature = "python")]
+mod python {
+    use super::{
+        dedup_genes, exclude_boundary_mutations, group_genes_by_contig, read_contigs, read_gene_positions_sqlite,
+        orient_minus_strand, read_gene_positions_with, replace_contigs, unapplied_mutations, Contigs, DedupGenes,
+        GeneInfo, Mutation, ParseOptions, RejectedMutation, ReplaceOptions, RunCounters, Strand, Strategy,
+        DEFAULT_POSITIONS_QUERY,
+    };
+    use pyo3::exceptions::{PyIOError, PyTypeError, PyValueError};
+    use pyo3::prelude::*;
+    use pyo3::types::PyDict;
+    use rayon::ThreadPoolBuilder;
+    use std::collections::{BTreeSet, HashMap};
+    use std::sync::atomic::Ordering;
+
+    // (contig, position, base) 或 (contig, position, base, id)
+    #[derive(FromPyObject)]
+    enum MutationTuple {
+        WithId(String, usize, char, Option<String>),
+        Plain(String, usize, char),
+    }
+
+    #[pyfunction]
+    fn load_contigs(path: &str) -> PyResult<HashMap<String, String>> {
+        let (contigs, _) = read_contigs(path, None).map_err(|e| PyIOError::new_err(e.to_string()))?;
+        // Python 一侧使用 str；合法的 UTF-8 直接转换，不复制
+        Ok(contigs
+            .into_iter()
+            .map(|(contig_id, sequence)| {
+                let sequence = String::from_utf8(sequence).unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned());
+                (contig_id, sequence)
+            })
+            .collect())
+    }
+
+    // (contig, gene_id, start, end) 或 (contig, gene_id, start, end, strand)
+    #[derive(FromPyObject)]
+    enum GeneTuple {
+        WithStrand(String, String, usize, usize, String),
+        Plain(String, String, usize, usize),
+    }
+
+    // load_gene_positions 返回的记录：(contig, gene_id, start, end, strand)
+    type GeneRecord = (String, String, usize, usize, &'static str);
+
+    // 返回 (contig, gene_id, start, end, strand) 列表，可直接传给 apply_snvs；coord_base 同 --coord-base
+    #[pyfunction]
+    #[pyo3(signature = (path, fmt = "csv", query = None, coord_base = 1))]
+    fn load_gene_positions(path: &str, fmt: &str, query: Option<&str>, coord_base: u8) -> PyResult<Vec<GeneRecord>> {
+        let parse_options = match coord_base {
+            0 | 1 => ParseOptions { zero_based_genes: coord_base == 0, ..ParseOptions::default() },
+            _ => return Err(PyValueError::new_err(format!("coord_base must be 0 or 1, got {}", coord_base))),
+        };
+        let genes = match fmt {
+            "csv" => read_gene_positions_with(path, &parse_options),
+            "sqlite" => read_gene_positions_sqlite(path, query.unwrap_or(DEFAULT_POSITIONS_QUERY), &parse_options).map(|(genes, _)| genes),
+            _ => return Err(PyValueError::new_err(format!("unknown positions format {:?}, expected \"csv\" or \"sqlite\"", fmt))),
+        }
+        .map_err(|e| PyIOError::new_err(e.to_string()))?;
+        Ok(genes
+            .into_iter()
+            .map(|gene_info| {
+                (gene_info.contig_id, gene_info.gene_id, gene_info.start_position, gene_info.end_position, gene_info.strand.as_str())
+            })
+            .collect())
+    }
+
+    // apply_snvs 的关键字参数，对应同名的命令行选项；其余 ReplaceOptions 字段涉及文件读写，这里不支持
+    const OPTION_NAMES: [&str; 5] = ["only_strand", "strategy", "dedup_genes", "exclude_boundary", "revcomp_minus"];
+
+    fn replace_options(options: Option<&Bound<'_, PyDict>>) -> PyResult<ReplaceOptions> {
+        let mut replace_options = ReplaceOptions::default();
+        for (key, value) in options.into_iter().flatten() {
+            let key: String = key.extract()?;
+            let invalid = |value: &Bound<'_, PyAny>| PyValueError::new_err(format!("invalid {}: {}", key, value));
+            match key.as_str() {
+                "only_strand" if value.is_none() => replace_options.only_strand = None,
+                "only_strand" => {
+                    let strand = Strand::parse(&value.extract::<String>()?).filter(|&strand| strand != Strand::Unknown);
+                    replace_options.only_strand = Some(strand.ok_or_else(|| invalid(&value))?);
+                }
+                "strategy" => replace_options.strategy = Strategy::parse(&value.extract::<String>()?).ok_or_else(|| invalid(&value))?,
+                "dedup_genes" => {
+                    replace_options.dedup_genes = DedupGenes::parse(&value.extract::<String>()?).ok_or_else(|| invalid(&value))?
+                }
+                "exclude_boundary" => replace_options.exclude_boundary = value.extract()?,
+                "revcomp_minus" => replace_options.forward_minus = !value.extract::<bool>()?,
+                _ => {
+                    return Err(PyTypeError::new_err(format!(
+                        "apply_snvs() got an unexpected keyword argument {:?}; supported: {}",
+                        key,
+                        OPTION_NAMES.join(", ")
+                    )))
+                }
+            }
+        }
+        Ok(replace_options)
+    }
+
+    // 返回 (gene_id -> 序列, 报告)；报告中 applied 为实际应用的突变，rejected 为未应用的突变及原因，
+    // counters 为本次调用的运行计数。关键字参数见 OPTION_NAMES。并行阶段释放 GIL
+    #[pyfunction]
+    #[pyo3(signature = (contigs, mutations, genes, threads = None, **options))]
+    fn apply_snvs<'py>(
+        py: Python<'py>,
+        contigs: HashMap<String, String>,
+        mutations: Vec<MutationTuple>,
+        genes: Vec<GeneTuple>,
+        threads: Option<usize>,
+        options: Option<&Bound<'py, PyDict>>,
+    ) -> PyResult<(HashMap<String, String>, Bound<'py, PyDict>)> {
+        let options = replace_options(options)?;
+        let contigs: Contigs =
+            contigs.into_iter().map(|(contig_id, sequence)| (contig_id, sequence.into_bytes())).collect();
+        let mutations: Vec<Mutation> = mutations
+            .into_iter()
+            .map(|mutation| match mutation {
+                MutationTuple::WithId(contig_id, position, new_base, mutation_id) => {
+                    Mutation { contig_id, position, new_base, mutation_id, heterozygous: false, expected_ref: None, indel: None }
+                }
+                MutationTuple::Plain(contig_id, position, new_base) => {
+                    Mutation { contig_id, position, new_base, mutation_id: None, heterozygous: false, expected_ref: None, indel: None }
+                }
+            })
+            .collect();
+        let mut gene_list = Vec::with_capacity(genes.len());
+        for gene in genes {
+            let (contig_id, gene_id, start_position, end_position, strand) = match gene {
+                GeneTuple::WithStrand(contig_id, gene_id, start, end, strand) => (contig_id, gene_id, start, end, strand),
+                GeneTuple::Plain(contig_id, gene_id, start, end) => (contig_id, gene_id, start, end, String::new()),
+            };
+            let strand = Strand::parse(&strand)
+                .ok_or_else(|| PyValueError::new_err(format!("invalid strand {:?} for gene {}", strand, gene_id)))?;
+            if start_position == 0 || start_position > end_position {
+                return Err(PyValueError::new_err(format!("invalid interval for gene {}: {}-{}", gene_id, start_position, end_position)));
+            }
+            if contigs.get(&contig_id).is_some_and(|sequence| end_position > sequence.len()) {
+                return Err(PyValueError::new_err(format!("gene {} ends past the end of contig {}", gene_id, contig_id)));
+            }
+            gene_list.push(GeneInfo { contig_id, start_position, end_position, gene_id, strand, source_id: None });
+        }
+        let gene_positions_map = group_genes_by_contig(dedup_genes(gene_list, options.dedup_genes).0);
+        let (mutations, boundary_mutations) = match options.exclude_boundary {
+            true => exclude_boundary_mutations(mutations, &gene_positions_map, options.only_strand),
+            false => (mutations, vec![]),
+        };
+
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads.unwrap_or_else(num_cpus::get))
+            .build()
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        let counters = RunCounters::new();
+        let replaced = py.allow_threads(|| {
+            replace_contigs(&contigs, &gene_positions_map, &mutations, options.only_strand, options.strategy, Some(&pool), &counters)
+        });
+        let mut replaced = replaced.map_err(|e| PyValueError::new_err(e.to_string()))?;
+        if !options.forward_minus {
+            replaced.mutated_genes = orient_minus_strand(&replaced.mutated_genes, &replaced.gene_summaries);
+        }
+
+        let report = PyDict::new(py);
+        let applied: Vec<(String, usize, String, String, Option<String>, String)> = replaced
+            .applied_mutations
+            .iter()
+            .map(|applied| {
+                let (ref_allele, alt_allele) = applied.alleles();
+                (
+                    applied.mutation.contig_id.clone(),
+                    applied.mutation.position,
+                    ref_allele,
+                    alt_allele,
+                    applied.mutation.mutation_id.clone(),
+                    applied.gene_id.clone(),
+                )
+            })
+            .collect();
+        let no_excluded_contigs = BTreeSet::new();
+        let unapplied = unapplied_mutations(
+            &mutations,
+            &replaced.applied_mutations,
+            &gene_positions_map,
+            &contigs,
+            &no_excluded_contigs,
+            options.only_strand,
+        );
+        let rejected: Vec<(String, usize, char, Option<String>, &str)> = boundary_mutations
+            .into_iter()
+            .map(|mutation| RejectedMutation { mutation, reason: "gene_boundary" })
+            .chain(unapplied)
+            .map(|rejected| {
+                let RejectedMutation { mutation, reason } = rejected;
+                (mutation.contig_id, mutation.position, mutation.new_base, mutation.mutation_id, reason)
+            })
+            .collect();
+        report.set_item("applied", applied)?;
+        report.set_item("rejected", rejected)?;
+        let gene_mutations: HashMap<&str, usize> = replaced
+            .gene_summaries
+            .iter()
+            .map(|summary| (summary.gene.gene_id.as_str(), summary.mutations_applied))
+            .collect();
+        report.set_item("gene_mutations", gene_mutations)?;
+        let counters: HashMap<&str, u64> =
+            counters.named().into_iter().map(|(name, counter)| (name, counter.load(Ordering::Relaxed))).collect();
+        report.set_item("counters", counters)?;
+        Ok((replaced.mutated_genes, report))
+    }
+
+    #[pymodule]
+    fn z10(module: &Bound<'_, PyModule>) -> PyResult<()> {
+        module.add_function(wrap_pyfunction!(load_contigs, module)?)?;
+        module.add_function(wrap_pyfunction!(load_gene_positions, module)?)?;
+        module.add_function(wrap_pyfunction!(apply_snvs, module)?)?;
+        Ok(())
+    }
+}
+
+// C ABI（需要 capi feature，构建为 cdylib，头文件由 cbindgen 根据下面的文档注释生成）
+#[cfg(feature = "capi")]
+mod capi {
+    use super::{
+        group_unique_genes, orient_minus_strand, read_contigs, read_gene_positions, read_mutation_files, replace_contigs,
+        report_contig_set_differences, skip_reference_mismatches, ParseOptions, RunCounters, Strategy,
+    };
+    use rayon::ThreadPoolBuilder;
+    use std::ffi::{c_char, CStr, CString};
+    use std::io;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    pub const RSNV_OK: i32 = 0;
+    pub const RSNV_ERR_INVALID_ARGUMENT: i32 = 1;
+    pub const RSNV_ERR_IO: i32 = 2;
+    pub const RSNV_ERR_PANIC: i32 = 3;
+
+    /// Result of a successful rsnv_run. Opaque to C; release with rsnv_free.
+    pub struct RsnvResult {
+        genes: Vec<(CString, CString)>,
+        summary_json: CString,
+    }
+
+    // 配置：{"contigs": 路径, "mutations": 路径或路径数组, "positions": 路径, "threads": 线程数（可选）,
+    // "revcomp_minus": 负链基因是否写为反向互补（可选，默认 true）}
+    fn run(config_json: &str) -> Result<RsnvResult, (i32, String)> {
+        let invalid = |message: String| (RSNV_ERR_INVALID_ARGUMENT, message);
+        fn io_error(path: &str) -> impl FnOnce(io::Error) -> (i32, String) + '_ {
+            move |e| (RSNV_ERR_IO, format!("{}: {}", path, e))
+        }
+        let config: serde_json::Value =
+            serde_json::from_str(config_json).map_err(|e| invalid(format!("invalid config JSON: {}", e)))?;
+        let path = |key: &str| {
+            config[key].as_str().ok_or_else(|| invalid(format!("config field \"{}\" must be a string path", key)))
+        };
+        let contigs_file = path("contigs")?;
+        let positions_file = path("positions")?;
+        let mutations_files: Vec<&str> = match &config["mutations"] {
+            serde_json::Value::String(path) => vec![path.as_str()],
+            serde_json::Value::Array(paths) => paths.iter().filter_map(|path| path.as_str()).collect(),
+            _ => return Err(invalid("config field \"mutations\" must be a path or an array of paths".to_string())),
+        };
+        let threads = config["threads"].as_u64().map_or_else(num_cpus::get, |threads| threads as usize);
+        let revcomp_minus = match &config["revcomp_minus"] {
+            serde_json::Value::Null => true,
+            value => value.as_bool().ok_or_else(|| invalid("config field \"revcomp_minus\" must be a boolean".to_string()))?,
+        };
+
+        let gene_positions_map = group_unique_genes(read_gene_positions(positions_file).map_err(io_error(positions_file))?);
+        let (contigs, _) = read_contigs(contigs_file, None).map_err(io_error(contigs_file))?;
+        let (mutations, _) = read_mutation_files(&mutations_files, &ParseOptions::default()).map_err(io_error("mutations"))?;
+        let mutations = skip_reference_mismatches(mutations, &contigs);
+        report_contig_set_differences(&gene_positions_map, &mutations);
+        let pool = ThreadPoolBuilder::new().num_threads(threads).build().map_err(|e| invalid(e.to_string()))?;
+        let counters = RunCounters::new();
+        let replaced = replace_contigs(&contigs, &gene_positions_map, &mutations, None, Strategy::default(), Some(&pool), &counters)
+            .map_err(|e| invalid(e.to_string()))?;
+
+        let summary = serde_json::json!({
+            "genes": replaced.gene_summaries.len(),
+            "mutations_applied": replaced.applied_mutations.len(),
+            "gene_mutations": replaced.gene_summaries.iter().map(|summary| summary.mutations_applied).sum::<usize>(),
+        });
+        let mutated_genes = match revcomp_minus {
+            true => orient_minus_strand(&replaced.mutated_genes, &replaced.gene_summaries),
+            false => replaced.mutated_genes,
+        };
+        let mut genes: Vec<(String, String)> = mutated_genes.into_iter().collect();
+        genes.sort();
+        let to_c = |text: String| CString::new(text).map_err(|e| invalid(format!("NUL byte in output: {}", e)));
+        Ok(RsnvResult {
+            genes: genes
+                .into_iter()
+                .map(|(id, sequence)| Ok((to_c(id)?, to_c(sequence)?)))
+                .collect::<Result<_, (i32, String)>>()?,
+            summary_json: to_c(summary.to_string())?,
+        })
+    }
+
+    thread_local! {
+        static LAST_ERROR: std::cell::RefCell<Option<CString>> = const { std::cell::RefCell::new(None) };
+    }
+
+    fn set_last_error(message: String) {
+        let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+        LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+    }
+
+    /// Runs the pipeline described by `config_json` (UTF-8, NUL-terminated):
+    /// {"contigs": path, "mutations": path or [paths], "positions": path, "threads": n, "revcomp_minus": bool}.
+    /// Minus-strand genes are returned reverse-complemented unless "revcomp_minus" is false.
+    /// On success returns RSNV_OK and stores a new handle in `*out_handle`; the caller owns it and
+    /// must release it with rsnv_free. On failure returns an RSNV_ERR_* code, leaves `*out_handle`
+    /// untouched, and rsnv_last_error() describes the error.
+    ///
+    /// # Safety
+    /// `config_json` must be a valid NUL-terminated string and `out_handle` a valid pointer.
+    #[no_mangle]
+    pub unsafe extern "C" fn rsnv_run(config_json: *const c_char, out_handle: *mut *mut RsnvResult) -> i32 {
+        if config_json.is_null() || out_handle.is_null() {
+            set_last_error("config_json and out_handle must not be NULL".to_string());
+            return RSNV_ERR_INVALID_ARGUMENT;
+        }
+        let config_json = match CStr::from_ptr(config_json).to_str() {
+            Ok(config_json) => config_json,
+            Err(e) => {
+                set_last_error(format!("config_json is not UTF-8: {}", e));
+                return RSNV_ERR_INVALID_ARGUMENT;
+            }
+        };
+        match catch_unwind(AssertUnwindSafe(|| run(config_json))) {
+            Ok(Ok(result)) => {
+                *out_handle = Box::into_raw(Box::new(result));
+                RSNV_OK
+            }
+            Ok(Err((code, message))) => {
+                set_last_error(message);
+                code
+            }
+            Err(_) => {
+                set_last_error("internal panic".to_string());
+                RSNV_ERR_PANIC
+            }
+        }
+    }
+
+    /// Message for the last failed call on this thread, or NULL. The string is owned by the
+    /// library and valid until the next failing call on the same thread.
+    #[no_mangle]
+    pub extern "C" fn rsnv_last_error() -> *const c_char {
+        LAST_ERROR.with(|last| last.borrow().as_ref().map_or(std::ptr::null(), |message| message.as_ptr()))
+    }
+
+    /// Number of output genes in `handle` (0 for NULL).
+    ///
+    /// # Safety
+    /// `handle` must be NULL or a handle returned by rsnv_run that has not been freed.
+    #[no_mangle]
+    pub unsafe extern "C" fn rsnv_result_gene_count(handle: *const RsnvResult) -> usize {
+        handle.as_ref().map_or(0, |result| result.genes.len())
+    }
+
+    /// Gene `index` (sorted by id). Stores pointers to the NUL-terminated UTF-8 id and sequence and
+    /// their lengths in bytes (without the NUL). The strings are owned by the handle and stay valid
+    /// until rsnv_free; callers must not modify or free them. Any out pointer may be NULL.
+    /// Returns RSNV_ERR_INVALID_ARGUMENT for a NULL handle or an index out of range.
+    ///
+    /// # Safety
+    /// `handle` must be a live handle; non-NULL out pointers must be valid for writes.
+    #[no_mangle]
+    pub unsafe extern "C" fn rsnv_result_gene(
+        handle: *const RsnvResult,
+        index: usize,
+        id_ptr: *mut *const c_char,
+        id_len: *mut usize,
+        seq_ptr: *mut *const c_char,
+        seq_len: *mut usize,
+    ) -> i32 {
+        let Some((id, sequence)) = handle.as_ref().and_then(|result| result.genes.get(index)) else {
+            set_last_error(format!("no gene at index {}", index));
+            return RSNV_ERR_INVALID_ARGUMENT;
+        };
+        if !id_ptr.is_null() {
+            *id_ptr = id.as_ptr();
+        }
+        if !id_len.is_null() {
+            *id_len = id.as_bytes().len();
+        }
+        if !seq_ptr.is_null() {
+            *seq_ptr = sequence.as_ptr();
+        }
+        if !seq_len.is_null() {
+            *seq_len = sequence.as_bytes().len();
+        }
+        RSNV_OK
+    }
+
+    /// Run summary as a NUL-terminated JSON object ({"genes", "mutations_applied", "gene_mutations"}),
+    /// owned by the handle and valid until rsnv_free. Returns NULL for a NULL handle.
+    ///
+    /// # Safety
+    /// `handle` must be NULL or a live handle.
+    #[no_mangle]
+    pub unsafe extern "C" fn rsnv_result_summary_json(handle: *const RsnvResult) -> *const c_char {
+        handle.as_ref().map_or(std::ptr::null(), |result| result.summary_json.as_ptr())
+    }
+
+    /// Releases a handle from rsnv_run and every string obtained from it. NULL is ignored.
+    ///
+    /// # Safety
+    /// `handle` must be NULL or a live handle; it must not be used afterwards.
+    #[no_mangle]
+    pub unsafe extern "C" fn rsnv_free(handle: *mut RsnvResult) {
+        if !handle.is_null() {
+            drop(Box::from_raw(handle));
+        }
+    }
+}
+
+// WebAssembly 封装（需要 wasm feature，目标 wasm32-unknown-unknown）：全部输入输出都是内存中的字符串，
+// 不使用文件和线程
+/// WebAssembly bindings (`wasm` feature) for `wasm32-unknown-unknown`.
+#[cfg(feature = "wasm")]
+pub mod wasm {
+    use super::{
+        group_unique_genes, masked_diff_sequences, orient_minus_strand, read_contigs_from, read_gene_positions_from,
+        read_mutations_from, replace_contigs, reverse_complement, skip_reference_mismatches, translate, OutputFormat,
+        ParseOptions, RunCounters, Strand, Strategy, STANDARD_CODE,
+    };
+    use wasm_bindgen::prelude::*;
+
+    // options_json：{"only_strand": "+"|"-", "format": "fasta"|"masked-diff", "translate": true|false, "coord_base": 0|1,
+    // "revcomp_minus": true|false}，均可省略；revcomp_minus 默认为 true，与命令行相同负链基因写为反向互补
+    fn run(contigs_fasta: &str, mutations_csv: &str, positions_csv: &str, options_json: &str) -> Result<String, String> {
+        let options: serde_json::Value = match options_json.trim() {
+            "" => serde_json::Value::Null,
+            options_json => serde_json::from_str(options_json).map_err(|e| format!("invalid options JSON: {}", e))?,
+        };
+        let only_strand = match options["only_strand"].as_str() {
+            Some(value) => Some(Strand::parse(value).ok_or_else(|| format!("invalid only_strand {:?}", value))?),
+            None => None,
+        };
+        let output_format = match options["format"].as_str() {
+            Some(value) => OutputFormat::parse(value).ok_or_else(|| format!("invalid format {:?}", value))?,
+            None => OutputFormat::Fasta,
+        };
+        let parse_options = match &options["coord_base"] {
+            serde_json::Value::Null => ParseOptions::default(),
+            value => match value.as_u64() {
+                Some(base @ (0 | 1)) => ParseOptions { zero_based_genes: base == 0, ..ParseOptions::default() },
+                _ => return Err(format!("invalid coord_base {}", value)),
+            },
+        };
+
+        let (contigs, _) = read_contigs_from(contigs_fasta.as_bytes(), None).map_err(|e| e.to_string())?;
+        let mutations = read_mutations_from(mutations_csv.as_bytes(), &parse_options).map_err(|e| e.to_string())?;
+        let mutations = skip_reference_mismatches(mutations, &contigs);
+        let (genes, _) = read_gene_positions_from(positions_csv.as_bytes(), &parse_options).map_err(|e| e.to_string())?;
+        let gene_positions_map = group_unique_genes(genes);
+        let counters = RunCounters::new();
+        let replaced = replace_contigs(&contigs, &gene_positions_map, &mutations, only_strand, Strategy::default(), None, &counters)
+            .map_err(|e| e.to_string())?;
+
+        let masked_diffs = match output_format {
+            OutputFormat::Fasta => None,
+            OutputFormat::MaskedDiff => Some(masked_diff_sequences(&replaced.gene_summaries, &replaced.applied_mutations)),
+            OutputFormat::Tsv | OutputFormat::Json | OutputFormat::Jsonl | OutputFormat::Fastq => return Err(format!("format {:?} is not supported here", output_format.name())),
+        };
+        let records = masked_diffs.as_ref().unwrap_or(&replaced.mutated_genes);
+        let oriented = match &options["revcomp_minus"] {
+            serde_json::Value::Null | serde_json::Value::Bool(true) => Some(orient_minus_strand(records, &replaced.gene_summaries)),
+            serde_json::Value::Bool(false) => None,
+            value => return Err(format!("invalid revcomp_minus {}", value)),
+        };
+        let records = oriented.as_ref().unwrap_or(records);
+        let mut output = String::new();
+        for summary in &replaced.gene_summaries {
+            let gene_id = &summary.gene.gene_id;
+            output.push_str(&format!(">{}\n{}\n", gene_id, records[gene_id]));
+            // 与命令行的 --translate 相同：负链基因先取反向互补
+            if options["translate"].as_bool().unwrap_or(false) {
+                let sequence = &replaced.mutated_genes[gene_id];
+                let protein = match summary.gene.strand {
+                    Strand::Minus => translate(&reverse_complement(sequence), 0, STANDARD_CODE),
+                    _ => translate(sequence, 0, STANDARD_CODE),
+                };
+                output.push_str(&format!(">{}_protein\n{}\n", gene_id, protein));
+            }
+        }
+        Ok(output)
+    }
+
+    /// Applies the mutations and returns the output FASTA sorted by gene id, or `{"error": "..."}`.
+    /// All inputs are in-memory strings in the command-line file formats; see the README for `options_json`.
+    #[wasm_bindgen]
+    pub fn apply_snvs(contigs_fasta: &str, mutations_csv: &str, positions_csv: &str, options_json: &str) -> String {
+        run(contigs_fasta, mutations_csv, positions_csv, options_json)
+            .unwrap_or_else(|error| serde_json::json!({ "error": error }).to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gene(contig_id: &str, gene_id: &str, start_position: usize, end_position: usize) -> GeneInfo {
+        GeneInfo {
+            contig_id: contig_id.to_string(),
+            start_position,
+            end_position,
+            gene_id: gene_id.to_string(),
+            strand: Strand::Unknown,
+            source_id: None,
+        }
+    }
+
+    fn snv(contig_id: &str, position: usize, new_base: char) -> Mutation {
+        Mutation {
+            contig_id: contig_id.to_string(),
+            position,
+            new_base,
+            mutation_id: None,
+            heterozygous: false,
+            expected_ref: None,
+            indel: None,
+        }
+    }
+
+    fn indel(contig_id: &str, position: usize, reference: &str, alt: &str) -> Mutation {
+        Mutation {
+            new_base: alt.chars().next().unwrap(),
+            indel: Some(Box::new(Indel { reference: reference.to_string(), alt: alt.to_string() })),
+            ..snv(contig_id, position, 'N')
+        }
+    }
+
+    fn contigs(records: &[(&str, &str)]) -> Contigs {
+        records.iter().map(|&(contig_id, sequence)| (contig_id.to_string(), sequence.as_bytes().to_vec())).collect()
+    }
+
+    fn gene_ids(genes: &[GeneInfo]) -> Vec<&str> {
+        genes.iter().map(|gene_info| gene_info.gene_id.as_str()).collect()
+    }
+
+    // 同一 gene_id 在两个 contig 上：first 和 longest 各保留一条并按位置改名，同一 contig 上的重复仍被合并
+    #[test]
+    fn dedup_keeps_same_id_on_two_contigs() {
+        let genes = vec![gene("c1", "g1", 1, 10), gene("c2", "g1", 2, 8), gene("c1", "g1", 3, 30)];
+        let (kept, resolved, split) = dedup_genes(genes.clone(), DedupGenes::First);
+        assert_eq!(gene_ids(&kept), ["g1_c1_1_10", "g1_c2_2_8"]);
+        assert_eq!((resolved, split), (1, 2));
+        assert!(kept.iter().all(|gene_info| gene_info.source_id.as_deref() == Some("g1")));
+
+        let (kept, resolved, split) = dedup_genes(genes, DedupGenes::Longest);
+        assert_eq!(gene_ids(&kept), ["g1_c1_3_30", "g1_c2_2_8"]);
+        assert_eq!((resolved, split), (1, 2));
+    }
+
+    #[test]
+    fn dedup_leaves_unique_ids_unchanged() {
+        let genes = vec![gene("c1", "g1", 1, 10), gene("c2", "g2", 2, 8)];
+        let (kept, resolved, split) = dedup_genes(genes, DedupGenes::First);
+        assert_eq!(gene_ids(&kept), ["g1", "g2"]);
+        assert_eq!((resolved, split), (0, 0));
+        assert!(kept.iter().all(|gene_info| gene_info.source_id.is_none()));
+    }
+
+    // gene_snv_replace 与命令行使用同样的去重：两个 contig 上的同名基因都输出，且与线程数无关
+    #[test]
+    fn gene_snv_replace_keeps_same_id_on_two_contigs() {
+        let contigs = contigs(&[("c1", "ACGTACGT"), ("c2", "TTTTGGGG")]);
+        let mutations = vec![snv("c1", 2, 'T'), snv("c2", 1, 'A')];
+        for num_threads in [0, 1, 2, 4, 8] {
+            let genes = vec![gene("c1", "g1", 1, 4), gene("c2", "g1", 1, 4), gene("c1", "g1", 5, 8)];
+            let mutated = gene_snv_replace(&contigs, &mutations, genes, num_threads).unwrap();
+            assert_eq!(mutated.len(), 2);
+            assert_eq!(mutated["g1_c1_1_4"], "ATGT");
+            assert_eq!(mutated["g1_c2_1_4"], "ATTT");
+        }
+    }
+
+    // 并发调用 gene_snv_replace 各自计数，结果互不影响，也不会安装或写入命令行的运行状态
+    #[test]
+    fn concurrent_gene_snv_replace_calls_do_not_share_state() {
+        let contigs = contigs(&[("c1", "ACGTACGT"), ("c2", "TTTTGGGG")]);
+        thread::scope(|scope| {
+            let calls: Vec<_> = (0..8)
+                .map(|call| {
+                    let contigs = &contigs;
+                    scope.spawn(move || {
+                        let base = ['A', 'C', 'G', 'T'][call % 4];
+                        let mutations = vec![snv("c1", 2, base), snv("c2", 5, base)];
+                        let genes = vec![gene("c1", "g1", 1, 4), gene("c2", "g2", 3, 8)];
+                        (base, gene_snv_replace(contigs, &mutations, genes, 2).unwrap())
+                    })
+                })
+                .collect();
+            for call in calls {
+                let (base, mutated) = call.join().unwrap();
+                assert_eq!(mutated["g1"], format!("A{}GT", base));
+                assert_eq!(mutated["g2"], format!("TT{}GGG", base));
+            }
+        });
+        assert!(CLI_RUN.lock().unwrap().is_none());
+    }
+
+    // 库的入口同样核对第6列的参考碱基：不一致的突变跳过，一致的（不区分大小写）照常应用
+    #[test]
+    fn gene_snv_replace_skips_reference_mismatches() {
+        let contigs = contigs(&[("c1", "ACGTACGT")]);
+        let with_ref = |position, new_base, expected_ref| Mutation { expected_ref: Some(expected_ref), ..snv("c1", position, new_base) };
+        let mutations = vec![with_ref(2, 'T', 'c'), with_ref(3, 'A', 'T'), snv("c1", 4, 'C')];
+        let mutated = gene_snv_replace(&contigs, &mutations, vec![gene("c1", "g1", 1, 8)], 1).unwrap();
+        assert_eq!(mutated["g1"], "ATGCACGT");
+    }
+
+    #[test]
+    fn gene_replace_error_exposes_its_source() {
+        let error = GeneReplaceError::IoError(io::Error::new(io::ErrorKind::NotFound, "missing.fa"));
+        let source = std::error::Error::source(&error).unwrap();
+        assert_eq!(source.to_string(), "missing.fa");
+        assert_eq!(io::Error::from(error).kind(), io::ErrorKind::NotFound);
+    }
+
+    // 去重后仍然重名的基因（这里是改名结果与另一个基因的 ID 相同）按 contig ID 顺序保留第一个，多次、多线程运行结果一致
+    #[test]
+    fn replace_contigs_picks_a_stable_winner_for_colliding_ids() {
+        let contigs = contigs(&[("c1", "ACGTACGT"), ("c2", "TTTTGGGG"), ("c3", "CCCCAAAA")]);
+        let mutations = vec![snv("c1", 2, 'T'), snv("c2", 1, 'A')];
+        for num_threads in [1, 2, 3, 8] {
+            for _ in 0..20 {
+                let genes = vec![gene("c3", "g1_c1_1_4", 1, 4), gene("c1", "g1", 1, 4), gene("c2", "g1", 1, 4)];
+                let mutated = gene_snv_replace(&contigs, &mutations, genes, num_threads).unwrap();
+                assert_eq!(mutated["g1_c1_1_4"], "ATGT");
+                assert_eq!(mutated["g1_c2_1_4"], "ATTT");
+                assert_eq!(mutated.len(), 2);
+            }
+        }
+    }
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir().join(format!("z10-{}-{}", std::process::id(), name)).to_string_lossy().into_owned()
+    }
+
+    // 在 c1（ACGT 重复 5 次）上应用突变，返回结果以及 applied VCF 的数据行和 BED 轨道的数据行
+    fn replace_and_report(
+        genes: Vec<GeneInfo>,
+        mutations: &[Mutation],
+        name: &str,
+    ) -> (ReplacedContigs, Vec<String>, Vec<String>) {
+        let contigs = contigs(&[("c1", "ACGTACGTACGTACGTACGT")]);
+        let replaced = replace_contigs(&contigs, &group_unique_genes(genes), mutations, None, Strategy::default(), None, &RunCounters::new()).unwrap();
+        let (vcf_path, bed_path) = (temp_path(&format!("{}.vcf", name)), temp_path(&format!("{}.bed", name)));
+        write_applied_vcf(&vcf_path, &replaced.applied_mutations, None, None).unwrap();
+        write_track_bed(&bed_path, &replaced.applied_mutations, None).unwrap();
+        let vcf = std::fs::read_to_string(&vcf_path).unwrap();
+        let bed = std::fs::read_to_string(&bed_path).unwrap();
+        let _ = (std::fs::remove_file(vcf_path), std::fs::remove_file(bed_path));
+        let vcf_rows = vcf.lines().filter(|line| !line.starts_with('#')).map(str::to_string).collect();
+        let bed_rows = bed.lines().skip(1).map(str::to_string).collect();
+        (replaced, vcf_rows, bed_rows)
+    }
+
+    #[test]
+    fn applied_insertion_is_reported_with_its_alleles() {
+        let mutations = [indel("c1", 5, "A", "AGG")];
+        let (replaced, vcf, bed) = replace_and_report(vec![gene("c1", "g1", 1, 20)], &mutations, "insertion");
+        assert_eq!(replaced.mutated_genes["g1"], "ACGTAGGCGTACGTACGTACGT");
+        assert_eq!(vcf, ["c1\t5\t.\tA\tAGG\t.\tPASS\t."]);
+        assert_eq!(bed, ["c1\t4\t7\tA>AGG"]);
+        assert_eq!(replaced.gene_summaries[0].mutations_applied, 1);
+    }
+
+    // 跨过 g2 起点的缺失只删除 g2 内的碱基；报告中只记录一次，归属第一个应用它的基因
+    #[test]
+    fn applied_deletion_is_reported_once() {
+        let genes = vec![gene("c1", "g1", 1, 20), gene("c1", "g2", 11, 20)];
+        let (replaced, vcf, bed) = replace_and_report(genes, &[indel("c1", 10, "CGT", "C")], "deletion");
+        assert_eq!(replaced.mutated_genes["g1"], "ACGTACGTACACGTACGT");
+        assert_eq!(replaced.mutated_genes["g2"], "ACGTACGT");
+        assert_eq!(vcf, ["c1\t10\t.\tCGT\tC\t.\tPASS\t."]);
+        assert_eq!(bed, ["c1\t9\t10\tCGT>C"]);
+        assert_eq!(replaced.applied_mutations[0].gene_id, "g1");
+    }
+
+    // 基因内的插入使序列变长；跨过基因起点的缺失只去掉基因内被删除的碱基，锚定碱基在基因外
+    #[test]
+    fn insertion_inside_a_gene_and_deletion_across_a_gene_start() {
+        let contigs = contigs(&[("c1", "ACGTACGTACGTACGTACGT")]);
+        let mutations = [indel("c1", 6, "C", "CTT"), indel("c1", 10, "CGTA", "C"), snv("c1", 15, 'T')];
+        let genes = vec![gene("c1", "g1", 3, 9), gene("c1", "g2", 12, 18)];
+        let mutated = gene_snv_replace(&contigs, &mutations, genes, 1).unwrap();
+        assert_eq!(mutated["g1"], "GTACTTGTA");
+        assert_eq!(mutated["g2"], "CTTAC");
+    }
+
+    // 基因首末碱基（start_position 和 end_position 本身）上的突变都会应用，紧邻基因两侧的不会；
+    // exclude_boundary_mutations 恰好去掉首末碱基上的突变，--only-strand 只看该链上的基因
+    #[test]
+    fn mutations_at_both_gene_boundaries() {
+        let contigs = contigs(&[("c1", "ACGTACGTACGT")]);
+        let mutations = vec![snv("c1", 4, 'G'), snv("c1", 5, 'T'), snv("c1", 8, 'A'), snv("c1", 9, 'T')];
+        let mutated = gene_snv_replace(&contigs, &mutations, vec![gene("c1", "g1", 5, 8)], 1).unwrap();
+        assert_eq!(mutated["g1"], "TCGA");
+
+        let minus = GeneInfo { strand: Strand::Minus, ..gene("c1", "g1", 5, 8) };
+        let gene_positions_map = HashMap::from([("c1".to_string(), vec![minus])]);
+        let (kept, dropped) = exclude_boundary_mutations(mutations.clone(), &gene_positions_map, None);
+        assert_eq!(kept.iter().map(|mutation| mutation.position).collect::<Vec<_>>(), [4, 9]);
+        assert_eq!(dropped.iter().map(|mutation| mutation.position).collect::<Vec<_>>(), [5, 8]);
+        let (kept, _) = exclude_boundary_mutations(mutations.clone(), &gene_positions_map, Some(Strand::Plus));
+        assert_eq!(kept.len(), 4);
+        let mutated = gene_snv_replace(&contigs, &kept[..1], vec![gene("c1", "g1", 5, 8)], 1).unwrap();
+        assert_eq!(mutated["g1"], "ACGT");
+    }
+
+    // 替换和插入/缺失混合：VCF 保留参考坐标，插入/缺失之后的位置写入 EPOS，BED 使用编辑后的坐标
+    #[test]
+    fn mixed_substitutions_and_indels_report_edited_positions() {
+        let mutations =
+            [snv("c1", 2, 'T'), indel("c1", 5, "A", "AGG"), indel("c1", 10, "CGT", "C"), snv("c1", 15, 'A'), snv("c1", 19, 'C')];
+        let (replaced, vcf, bed) = replace_and_report(vec![gene("c1", "g1", 1, 20)], &mutations, "mixed");
+        assert_eq!(replaced.mutated_genes["g1"], "ATGTAGGCGTACACATACCT");
+        assert_eq!(
+            vcf,
+            [
+                "c1\t2\t.\tC\tT\t.\tPASS\t.",
+                "c1\t5\t.\tA\tAGG\t.\tPASS\t.",
+                "c1\t10\t.\tCGT\tC\t.\tPASS\tEPOS=12",
+                "c1\t15\t.\tG\tA\t.\tPASS\t.",
+                "c1\t19\t.\tG\tC\t.\tPASS\t.",
+            ]
+        );
+        assert_eq!(bed, ["c1\t1\t2\tC>T", "c1\t4\t7\tA>AGG", "c1\t11\t12\tCGT>C", "c1\t14\t15\tG>A", "c1\t18\t19\tG>C"]);
+        assert_eq!(replaced.gene_summaries[0].mutations_applied, 5);
+        assert_eq!(replaced.gene_summaries[0].hamming, 3);
+    }
+
+    // g2 起点前插入 2 个碱基后整体右移；g3 的前两个碱基被删除，起点对应到删除点之后
+    #[test]
+    fn edited_coords_follow_applied_indels() {
+        let genes = vec![gene("c1", "g1", 1, 20), gene("c1", "g2", 6, 9), gene("c1", "g3", 11, 20)];
+        let mutations = [indel("c1", 5, "A", "AGG"), indel("c1", 10, "CGT", "C")];
+        let (replaced, _, _) = replace_and_report(genes, &mutations, "edited-coords");
+        let path = temp_path("edited-coords.tsv");
+        write_edited_coords(&path, &replaced.gene_summaries, &replaced.applied_mutations, None).unwrap();
+        let coords = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(
+            coords.lines().collect::<Vec<_>>(),
+            [
+                "gene_id\tcontig\tstart\tend\tedited_start\tedited_end\tstrand",
+                "g1\tc1\t1\t20\t1\t20\t.",
+                "g2\tc1\t6\t9\t8\t11\t.",
+                "g3\tc1\t11\t20\t13\t20\t.",
+            ]
+        );
+        let edited_contig = "ACGTAGGCGTACACGTACGT";
+        assert_eq!(&edited_contig[7..11], replaced.mutated_genes["g2"]);
+        assert_eq!(&edited_contig[12..20], replaced.mutated_genes["g3"]);
+    }
+
+    // 内容相同的突变文件按 BLAKE3 摘要识别，只读入一次；内容不同的都读入
+    #[test]
+    fn read_mutation_files_skips_identical_content_only() {
+        let paths = ["a.csv", "b.csv", "c.csv"].map(|name| temp_path(&format!("dup-{}", name)));
+        std::fs::write(&paths[0], "c1,2,T\n").unwrap();
+        std::fs::write(&paths[1], "c1,2,T\n").unwrap();
+        std::fs::write(&paths[2], "c1,3,T\n").unwrap();
+        let filenames: Vec<&str> = paths.iter().map(String::as_str).collect();
+        let (mutations, summaries) = read_mutation_files(&filenames, &ParseOptions::default()).unwrap();
+        for path in &paths {
+            let _ = std::fs::remove_file(path);
+        }
+        assert_eq!(mutations.iter().map(|mutation| mutation.position).collect::<Vec<_>>(), [2, 3]);
+        assert_eq!(summaries[1].duplicate_of.as_deref(), Some(paths[0].as_str()));
+        assert_eq!(summaries[2].duplicate_of, None);
+    }
+
+    // 坐标约定由参数决定：同一行按 1-based 和 0-based 读出不同的区间，默认的公开函数不受命令行设置影响
+    #[test]
+    fn gene_positions_follow_the_given_coordinate_base() {
+        let positions = "c1,g1,0,10\nc1,g2,5,10\n";
+        let (one_based, invalid) = read_gene_positions_from(positions.as_bytes(), &ParseOptions::default()).unwrap();
+        assert_eq!(gene_ids(&one_based), ["g2"]);
+        assert_eq!(gene_ids(&invalid), ["g1"]);
+        let zero_based = ParseOptions { zero_based_genes: true, ..ParseOptions::default() };
+        let (genes, invalid) = read_gene_positions_from(positions.as_bytes(), &zero_based).unwrap();
+        let intervals: Vec<(usize, usize)> = genes.iter().map(|gene_info| (gene_info.start_position, gene_info.end_position)).collect();
+        assert_eq!(intervals, [(1, 10), (6, 10)]);
+        assert!(invalid.is_empty());
+    }
+
+    // 带千分位的坐标只在 lenient_numbers 时接受，CSV 和 VCF 输入相同
+    #[test]
+    fn digit_groups_follow_lenient_numbers() {
+        let csv = "c1,\"1,234\",T\nc1,1_235,G\nc1,1236,A\n";
+        let vcf = "#CHROM\tPOS\tID\tREF\tALT\nc1\t1_234\t.\tA\tT\nc1\t1235\t.\tC\tG\n";
+        let lenient = ParseOptions { lenient_numbers: true, ..ParseOptions::default() };
+        let positions = |input: &str, options: &ParseOptions| -> Vec<usize> {
+            read_mutations_from(input.as_bytes(), options).unwrap().iter().map(|mutation| mutation.position).collect()
+        };
+        assert_eq!(positions(csv, &ParseOptions::default()), [1236]);
+        assert_eq!(positions(csv, &lenient), [1234, 1235, 1236]);
+        assert_eq!(positions(vcf, &ParseOptions::default()), [1235]);
+        assert_eq!(positions(vcf, &lenient), [1234, 1235]);
+    }
+
+    #[test]
+    fn multiallelic_vcf_follows_vcf_first_alt() {
+        let vcf = "#CHROM\tPOS\tID\tREF\tALT\nc1\t5\t.\tA\tC,G\n";
+        let error = read_mutations_from(vcf.as_bytes(), &ParseOptions::default()).unwrap_err();
+        assert!(error.to_string().contains("multi-allelic") || error.to_string().contains("多等位"), "{}", error);
+        let first_alt = ParseOptions { vcf_first_alt: true, ..ParseOptions::default() };
+        let mutations = read_mutations_from(vcf.as_bytes(), &first_alt).unwrap();
+        assert_eq!(mutations.iter().map(|mutation| mutation.new_base).collect::<Vec<_>>(), ['C']);
+    }
+
+    // 索引只在解析选项和来源文件内容都与建索引时相同的情况下使用
+    #[test]
+    fn mutation_index_is_rebuilt_on_any_mismatch() {
+        let (mutations_path, index_path) = (temp_path("index-mutations.csv"), temp_path("mutations.idx"));
+        std::fs::write(&mutations_path, "c1,2,T\nc1,3,A\n").unwrap();
+        let lenient = ParseOptions { lenient_numbers: true, ..ParseOptions::default() };
+        let (mutations, inputs) = read_mutation_files(&[mutations_path.as_str()], &lenient).unwrap();
+        save_mutation_index(&index_path, &mutations, &inputs, &lenient).unwrap();
+
+        let loaded = load_mutation_index(&index_path, &[mutations_path.as_str()], &lenient).unwrap();
+        assert_eq!(loaded.map(|(mutations, _)| mutations.len()), Some(2));
+        for options in [
+            ParseOptions::default(),
+            ParseOptions { vcf_first_alt: true, ..lenient },
+            ParseOptions { zero_based_genes: true, ..lenient },
+        ] {
+            assert!(load_mutation_index(&index_path, &[mutations_path.as_str()], &options).unwrap().is_none(), "{:?}", options);
+        }
+        std::fs::write(&mutations_path, "c1,2,T\nc1,3,C\n").unwrap();
+        assert!(load_mutation_index(&index_path, &[mutations_path.as_str()], &lenient).unwrap().is_none());
+
+        let _ = (std::fs::remove_file(mutations_path), std::fs::remove_file(index_path));
+    }
+
+    // 负链基因的 _nt 记录为反向互补，与 _aa 的翻译方向一致；正链基因不变
+    #[test]
+    fn paired_nt_aa_writes_minus_strand_genes_in_gene_orientation() {
+        let summary = |gene_id: &str, strand: Strand| GeneSummary {
+            gene: GeneInfo { strand, ..gene("c1", gene_id, 1, 6) },
+            length: 6,
+            mutations_applied: 0,
+            codons_affected: 0,
+            hamming: 0,
+        };
+        let mutated_genes: HashMap<String, String> =
+            [("plus", "ATGAAA"), ("minus", "TTTCAT")].iter().map(|&(id, sequence)| (id.to_string(), sequence.to_string())).collect();
+        let path = temp_path("paired.fa");
+        let summaries = [summary("plus", Strand::Plus), summary("minus", Strand::Minus)];
+        write_paired_nt_aa(&path, &mutated_genes, &summaries, STANDARD_CODE, InternalStopPolicy::TranslateThrough).unwrap();
+        let paired = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(paired, ">minus_nt\nATGAAA\n>minus_aa\nMK\n>plus_nt\nATGAAA\n>plus_aa\nMK\n");
+    }
+
+    // --check-output 逐条拦下每种违规：空 ID、ID 含空白、标题含换行、ID 重复、空序列、不允许的字符、折行不一致
+    #[test]
+    fn fasta_conformance_rejects_each_violation() {
+        let check = |header: &str, lines: &[&str]| FastaConformance::new(false).check(header, lines);
+        assert!(check("g1", &["ACGT", "AC"]).is_ok());
+        assert!(check("g1 contig=c1 strand=-", &["ACGTN-", "ryk"]).is_ok());
+        let violations: [(&str, &[&str]); 9] = [
+            ("", &["ACGT"]),
+            (" description", &["ACGT"]),
+            ("g\t1", &["ACGT"]),
+            ("g1 description\nwith a newline", &["ACGT"]),
+            ("g1", &[""]),
+            ("g1", &["AC*T"]),
+            ("g1", &["AC.T"]),
+            ("g1", &["ACGT", "ACG", "ACGT"]),
+            ("g1", &["ACG", "ACGT"]),
+        ];
+        for (header, lines) in violations {
+            assert!(check(header, lines).is_err(), "{:?} {:?}", header, lines);
+        }
+
+        let mut checker = FastaConformance::new(false);
+        assert!(checker.check("g1", &["ACGT"]).is_ok());
+        assert!(checker.check("g1 second copy", &["ACGT"]).is_err());
+        // masked-diff 输出允许 '.'
+        assert!(FastaConformance::new(true).check("g1", &["..T."]).is_ok());
+    }
+
+    // 检查失败时 write_output 中止，最终路径和临时文件都不留下；关闭检查时照常写出
+    #[test]
+    fn check_output_violation_aborts_before_the_output_is_published() {
+        let summary = |gene_id: &str| GeneSummary { gene: gene("c1", gene_id, 1, 4), length: 4, mutations_applied: 0, codons_affected: 0, hamming: 0 };
+        let settings = |check_output| OutputSettings { check_output, flush_every: None, mutated_quality: 20, wrap: 0 };
+        let records: HashMap<String, String> =
+            [("g1", "ACGT"), ("empty", "")].iter().map(|&(id, sequence)| (id.to_string(), sequence.to_string())).collect();
+        let path = temp_path("check-output.fa");
+        let temp_file = format!("{}.tmp-{}", path, std::process::id());
+        let cases = [vec![summary("g1"), summary("empty")], vec![summary("g1"), summary("g1")]];
+        for summaries in &cases {
+            let Err(error) = write_output(&path, OutputFormat::Fasta, &records, None, summaries, settings(true), None) else {
+                panic!("write_output accepted a violation");
+            };
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+            assert!(!std::path::Path::new(&path).exists() && !std::path::Path::new(&temp_file).exists());
+        }
+        write_output(&path, OutputFormat::Fasta, &records, None, &cases[0], settings(false), None).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), ">g1\nACGT\n>empty\n\n");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    // wasm 的 translate 与命令行的 --translate 一致：负链基因按反向互补翻译
+    // 负链基因的读码框从基因终点起算：4、5 在正链上同属第 2 个密码子，在负链上分属两个密码子
+    #[test]
+    fn codons_affected_follow_the_gene_strand() {
+        let plus = GeneInfo { strand: Strand::Plus, ..gene("c1", "g1", 1, 10) };
+        let minus = GeneInfo { strand: Strand::Minus, ..gene("c1", "g1", 1, 10) };
+        assert_eq!(count_codons_affected([4, 5].into_iter(), &plus), 1);
+        assert_eq!(count_codons_affected([4, 5].into_iter(), &minus), 2);
+        assert_eq!(count_codons_affected([5, 6, 7].into_iter(), &minus), 1);
+        assert_eq!(count_codons_affected([1, 10].into_iter(), &minus), 2);
+    }
+
+    #[cfg(feature = "wasm")]
+    #[test]
+    fn wasm_translates_minus_strand_genes_like_the_cli() {
+        let run = |options: &str| wasm::apply_snvs(">c1\nATGAAATTTCAT\n", "c1,6,G\n", "c1,plus,1,6,+\nc1,minus,7,12,-\n", options);
+        assert_eq!(run(r#"{"translate": true}"#), ">minus\nATGAAA\n>minus_protein\nMK\n>plus\nATGAAG\n>plus_protein\nMK\n");
+        let forward = run(r#"{"translate": true, "revcomp_minus": false}"#);
+        assert_eq!(forward, ">minus\nTTTCAT\n>minus_protein\nMK\n>plus\nATGAAG\n>plus_protein\nMK\n");
+    }
+
+    // wasm 与命令行一样跳过第6列参考碱基不一致的突变
+    #[cfg(feature = "wasm")]
+    #[test]
+    fn wasm_skips_reference_mismatches() {
+        let fasta = wasm::apply_snvs(">c1\nATGAAATTTCAT\n", "c1,2,C,,,T\nc1,6,G,,,C\n", "c1,g1,1,6\n", "{}");
+        assert_eq!(fasta, ">g1\nACGAAA\n");
+    }
+
+    // 已知的 ORF 翻译为预期的肽段；末尾不完整的密码子被丢弃，含简并碱基的密码子为 X，表 11 与标准表的差别只在起始密码子
+    #[test]
+    fn translate_known_orf() {
+        assert_eq!(translate("ATGGCCAAGTGGTAA", 0, STANDARD_CODE), "MAKW*");
+        assert_eq!(translate("ATGGCCAAGTG", 0, STANDARD_CODE), "MAK");
+        assert_eq!(translate("ATGNNNtgg", 0, STANDARD_CODE), "MXW");
+        assert_eq!(translate("ATGTGATAA", 0, genetic_code(11).unwrap()), "M**");
+        assert_eq!(translate("ATGTGATAA", 0, genetic_code(4).unwrap()), "MW*");
+        assert!(genetic_code(7).is_none());
+    }
+
+    // 起点为 0、起点大于终点或终点超出 contig 的基因被跳过，不会因越界截取而 panic，其余基因照常输出
+    #[test]
+    fn out_of_range_genes_are_skipped() {
+        let contigs = contigs(&[("c1", "ACGTACGTACGTACGTACGT")]);
+        let genes = vec![gene("c1", "zero", 0, 5), gene("c1", "past", 15, 25), gene("c1", "backwards", 9, 4), gene("c1", "ok", 1, 4)];
+        for strategy in [Strategy::Buffer, Strategy::MergeJoin] {
+            let replaced = replace_contigs(&contigs, &group_unique_genes(genes.clone()), &[snv("c1", 2, 'T')], None, strategy, None, &RunCounters::new()).unwrap();
+            assert_eq!(replaced.mutated_genes, HashMap::from([("ok".to_string(), "ATGT".to_string())]));
+            assert_eq!(replaced.gene_summaries.len(), 1);
+        }
+    }
+
+    // 互补表覆盖 IUPAC 简并碱基并保留大小写，未知字符原样保留
+    #[test]
+    fn reverse_complement_handles_iupac_and_case() {
+        assert_eq!(reverse_complement("ACGTacgt"), "acgtACGT");
+        assert_eq!(reverse_complement("RYSWKMBDHVN"), "NBDHVKMWSRY");
+        assert_eq!(reverse_complement("ryn-*é"), "é*-nry");
+    }
+
+    // 负链基因的输出等于正链切片（含突变）的反向互补；同一区间的正链和无链基因仍为正链切片
+    #[test]
+    fn minus_strand_genes_are_the_reverse_complement_of_the_forward_slice() {
+        let contigs = contigs(&[("c1", "GGAACGTTGCAnRYcTT")]);
+        let mutations = vec![snv("c1", 4, 'G'), snv("c1", 12, 'K')];
+        let stranded = |gene_id: &str, strand: Strand| GeneInfo { strand, ..gene("c1", gene_id, 3, 15) };
+        let genes = vec![stranded("plus", Strand::Plus), stranded("minus", Strand::Minus), stranded("unknown", Strand::Unknown)];
+        let mutated = gene_snv_replace(&contigs, &mutations, genes, 1).unwrap();
+        assert_eq!(mutated["plus"], "AGCGTTGCAKRYc");
+        assert_eq!(mutated["unknown"], mutated["plus"]);
+        assert_eq!(mutated["minus"], reverse_complement(&mutated["plus"]));
+        assert_eq!(mutated["minus"], "gRYMTGCAACGCT");
+    }
+
+    // --wrap：130 个碱基按 60 折行为 60、60、10，0 表示不折行；多字节字符不会在中间被截断
+    #[test]
+    fn wrap_lines_splits_at_the_given_width() {
+        let sequence = "ACGTACGTAC".repeat(13);
+        let lengths = |lines: Vec<&str>| lines.iter().map(|line| line.len()).collect::<Vec<_>>();
+        assert_eq!(lengths(wrap_lines(&sequence, 60)), [60, 60, 10]);
+        assert_eq!(lengths(wrap_lines(&sequence, 0)), [130]);
+        assert_eq!(wrap_lines("ACGTA", 5), ["ACGTA"]);
+        assert_eq!(wrap_lines("AçGTé", 2), ["Aç", "GT", "é"]);
+    }
+
+    // --strategy merge-join 与默认的 buffer 策略在随机输入上结果一致，包括数百个互相重叠的基因、重复位点和越界位点
+    #[test]
+    fn merge_join_matches_buffer_on_random_inputs() {
+        let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+        let mut next = |bound: usize| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % bound as u64) as usize
+        };
+        let mut total_applied = 0;
+        for round in 0..40 {
+            let length = 50 + next(400);
+            let sequence: String = (0..length).map(|_| b"ACGTN"[next(5)] as char).collect();
+            let contigs = contigs(&[("c1", &sequence), ("c2", "ACGTACGTAC")]);
+            // 最后几轮是病态情况：数百个基因都覆盖 contig 中部
+            let pathological = round >= 35;
+            let mut genes = vec![gene("c2", "short", 2, 9)];
+            for index in 0..(if pathological { 300 } else { 1 + next(30) }) {
+                let (start, end) = match pathological {
+                    true => (1 + next(length / 2), length / 2 + next(length / 2) + 1),
+                    false => {
+                        let start = 1 + next(length);
+                        (start, start + next(length + 1 - start))
+                    }
+                };
+                let strand = [Strand::Plus, Strand::Minus, Strand::Unknown][next(3)];
+                genes.push(GeneInfo { strand, ..gene("c1", &format!("g{}", index), start, end) });
+            }
+            let mutations: Vec<Mutation> = (0..next(200))
+                .map(|_| snv(["c1", "c1", "c1", "c2", "c3"][next(5)], next(length + 5), b"ACGT"[next(4)] as char))
+                .collect();
+            let gene_positions_map = group_unique_genes(genes);
+            for only_strand in [None, Some(Strand::Plus), Some(Strand::Minus)] {
+                let [buffer, merge_join] = [Strategy::Buffer, Strategy::MergeJoin].map(|strategy| {
+                    replace_contigs(&contigs, &gene_positions_map, &mutations, only_strand, strategy, None, &RunCounters::new()).unwrap()
+                });
+                assert_eq!(buffer.mutated_genes, merge_join.mutated_genes, "round {}", round);
+                let applied = |replaced: &ReplacedContigs| -> Vec<(String, usize, char, char, String)> {
+                    replaced
+                        .applied_mutations
+                        .iter()
+                        .map(|applied| {
+                            let mutation = &applied.mutation;
+                            (mutation.contig_id.clone(), mutation.position, mutation.new_base, applied.ref_base, applied.gene_id.clone())
+                        })
+                        .collect()
+                };
+                assert_eq!(applied(&buffer), applied(&merge_join), "round {}", round);
+                let summaries = |replaced: &ReplacedContigs| -> Vec<(String, usize, usize, usize)> {
+                    replaced
+                        .gene_summaries
+                        .iter()
+                        .map(|summary| (summary.gene.gene_id.clone(), summary.mutations_applied, summary.codons_affected, summary.hamming))
+                        .collect()
+                };
+                assert_eq!(summaries(&buffer), summaries(&merge_join), "round {}", round);
+                total_applied += buffer.applied_mutations.len();
+            }
+        }
+        assert!(total_applied > 0);
+    }
+
+    // 任意字节输入只返回结果或错误，不会 panic（fuzz/ 中 fuzz 目标的确定性版本）：随机字节与接近合法格式的行混合
+    #[test]
+    fn gene_snv_replace_bytes_never_panics_on_random_input() {
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = |bound: usize| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % bound as u64) as usize
+        };
+        let pieces: [&[u8]; 16] = [
+            b">", b">c1", b"\n", b",", b"c1", b"c2", b"0", b"1", b"18446744073709551616", b"-3", b"+", b"ACGTN", b"\xff\xfe",
+            "\u{00e9}".as_bytes(), b"\r\n", b"T",
+        ];
+        let mut outcomes = [0; 2];
+        for _ in 0..3000 {
+            let mut input = || -> Vec<u8> {
+                let mut bytes = vec![];
+                for _ in 0..next(40) {
+                    match next(4) {
+                        0 => bytes.push(next(256) as u8),
+                        _ => bytes.extend_from_slice(pieces[next(pieces.len())]),
+                    }
+                }
+                bytes
+            };
+            let (contigs, mutations, positions) = (input(), input(), input());
+            outcomes[gene_snv_replace_bytes(&contigs, &mutations, &positions).is_ok() as usize] += 1;
+        }
+        assert!(outcomes[0] > 0 && outcomes[1] > 0, "{:?}", outcomes);
+    }
+
+    // 100 MB 的单个 contig：每个 contig 只复制一份缓冲区，两种策略下最大单次分配都不超过 contig 长度
+    #[test]
+    fn largest_allocation_stays_within_one_contig_for_100_mb() {
+        const LENGTH: usize = 100 * 1024 * 1024;
+        let contigs = HashMap::from([("big".to_string(), b"ACGT".repeat(LENGTH / 4))]);
+        let genes = vec![gene("big", "head", 1, 1000), gene("big", "middle", LENGTH / 2, LENGTH / 2 + 999), gene("big", "whole", 1, LENGTH)];
+        let mutations = [snv("big", 1, 'T'), snv("big", LENGTH / 2 + 1, 'G'), snv("big", LENGTH, 'A')];
+        for strategy in [Strategy::Buffer, Strategy::MergeJoin] {
+            let replaced = replace_contigs(&contigs, &group_unique_genes(genes.clone()), &mutations, None, strategy, None, &RunCounters::new()).unwrap();
+            assert_eq!(&replaced.mutated_genes["head"][..4], "TCGT");
+            assert_eq!(&replaced.mutated_genes["middle"][..4], "TGCG");
+            assert_eq!(replaced.mutated_genes["whole"].len(), LENGTH);
+            assert!(replaced.mutated_genes["whole"].ends_with("ACGA"));
+            assert_eq!(replaced.gene_summaries.iter().map(|summary| summary.mutations_applied).sum::<usize>(), 5);
+        }
+        assert!(largest_allocation() <= LENGTH, "largest allocation {} > {}", largest_allocation(), LENGTH);
+    }
+
+    // 大 contig 上的大量突变：读入后每个碱基占一个字节，所有突变在同一份缓冲区上原地修改。
+    // 每个突变都复制一次 contig 时 100k 个突变需要复制约 800 GB，不可能在时限内完成
+    #[test]
+    fn many_mutations_on_a_large_contig_are_applied_in_place() {
+        const LENGTH: usize = 8 * 1024 * 1024;
+        const MUTATIONS: usize = 100_000;
+        let sequence = b"ACGTTGCA".repeat(LENGTH / 8);
+        let mut fasta = b">big length=8M\r\n".to_vec();
+        for line in sequence.chunks(60) {
+            fasta.extend_from_slice(line);
+            fasta.extend_from_slice(b"\r\n");
+        }
+        let (contigs, n_counts) = read_contigs_from(&fasta[..], None).unwrap();
+        assert_eq!(contigs["big"], sequence);
+        assert_eq!(n_counts["big"], 0);
+
+        let started = Instant::now();
+        let mutations: Vec<Mutation> = (0..MUTATIONS).map(|index| snv("big", 1 + index * (LENGTH / MUTATIONS), 'n')).collect();
+        let genes = vec![gene("big", "whole", 1, LENGTH), gene("big", "tail", LENGTH - 999, LENGTH)];
+        let mutated = gene_snv_replace(&contigs, &mutations, genes, 2).unwrap();
+        let elapsed = started.elapsed();
+        eprintln!("{} mutations on a {} byte contig: {:.3}s", MUTATIONS, LENGTH, elapsed.as_secs_f64());
+
+        let mut expected = sequence.clone();
+        for mutation in &mutations {
+            expected[mutation.position - 1] = b'n';
+        }
+        assert_eq!(mutated["whole"].as_bytes(), &expected[..]);
+        assert_eq!(mutated["tail"].as_bytes(), &expected[LENGTH - 1000..]);
+        assert!(elapsed < Duration::from_secs(60), "{:?}", elapsed);
+    }
+
+    // 按 (contig, 位置) 索引突变：100 kb contig 上 10k 个随机突变单线程也应远低于一秒，
+    // 结果与逐个突变直接修改参考序列一致
+    #[test]
+    fn ten_thousand_mutations_on_a_100kb_contig_finish_well_under_a_second() {
+        const LENGTH: usize = 100_000;
+        let sequence = b"ACGT".repeat(LENGTH / 4);
+        let contigs = HashMap::from([("c1".to_string(), sequence.clone())]);
+        let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+        let mut next = |bound: usize| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % bound as u64) as usize
+        };
+        let mut expected = sequence;
+        let mut mutations = vec![];
+        let mut seen = HashSet::new();
+        for _ in 0..10_000 {
+            let position = 1 + next(LENGTH);
+            let new_base = ['a', 'c', 'g', 't'][next(4)];
+            // 同一位置的多个突变只应用最先读到的一个
+            if seen.insert(position) {
+                expected[position - 1] = new_base as u8;
+            }
+            mutations.push(snv("c1", position, new_base));
+        }
+        let genes = (0..100).map(|index| gene("c1", &format!("g{}", index), 1 + index * 1000, (index + 1) * 1000)).collect();
+
+        let started = Instant::now();
+        let mutated = gene_snv_replace(&contigs, &mutations, genes, 1).unwrap();
+        let elapsed = started.elapsed();
+        for index in 0..100 {
+            assert_eq!(mutated[&format!("g{}", index)].as_bytes(), &expected[index * 1000..(index + 1) * 1000]);
+        }
+        assert!(elapsed < Duration::from_millis(500), "{:?}", elapsed);
+    }
+
+    #[test]
+    fn parse_byte_size_accepts_documented_suffixes() {
+        assert_eq!(parse_byte_size("1024").unwrap(), 1024);
+        assert_eq!(parse_byte_size("100B").unwrap(), 100);
+        assert_eq!(parse_byte_size("4G").unwrap(), 4 << 30);
+        assert_eq!(parse_byte_size("4GB").unwrap(), 4 << 30);
+        assert_eq!(parse_byte_size("4gib").unwrap(), 4 << 30);
+        assert_eq!(parse_byte_size(" 512 m ").unwrap(), 512 << 20);
+        assert_eq!(parse_byte_size("1.5G").unwrap(), 3 << 29);
+        assert_eq!(parse_byte_size("2T").unwrap(), 2 << 40);
+    }
+
+    #[test]
+    fn parse_byte_size_rejects_bad_values() {
+        for value in ["", "abc", "G", "4X", "4GBB", "-1G", "1e9", "1.5.2G", "99999999999T"] {
+            let error = parse_byte_size(value).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidInput, "{:?}", value);
+        }
+    }
+}
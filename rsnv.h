@@ -0,0 +1,78 @@
+#ifndef RSNV_H
+#define RSNV_H
+
+/* Generated with cbindgen --config cbindgen.toml --output rsnv.h; do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define RSNV_OK 0
+
+#define RSNV_ERR_INVALID_ARGUMENT 1
+
+#define RSNV_ERR_IO 2
+
+#define RSNV_ERR_PANIC 3
+
+/**
+ * Result of a successful rsnv_run. Opaque to C; release with rsnv_free.
+ */
+typedef struct RsnvResult RsnvResult;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Runs the pipeline described by `config_json` (UTF-8, NUL-terminated):
+ * {"contigs": path, "mutations": path or [paths], "positions": path, "threads": n, "revcomp_minus": bool}.
+ * Minus-strand genes are returned reverse-complemented unless "revcomp_minus" is false.
+ * On success returns RSNV_OK and stores a new handle in `*out_handle`; the caller owns it and
+ * must release it with rsnv_free. On failure returns an RSNV_ERR_* code, leaves `*out_handle`
+ * untouched, and rsnv_last_error() describes the error.
+ */
+int32_t rsnv_run(const char *config_json, RsnvResult **out_handle);
+
+/**
+ * Message for the last failed call on this thread, or NULL. The string is owned by the
+ * library and valid until the next failing call on the same thread.
+ */
+const char *rsnv_last_error(void);
+
+/**
+ * Number of output genes in `handle` (0 for NULL).
+ */
+uintptr_t rsnv_result_gene_count(const RsnvResult *handle);
+
+/**
+ * Gene `index` (sorted by id). Stores pointers to the NUL-terminated UTF-8 id and sequence and
+ * their lengths in bytes (without the NUL). The strings are owned by the handle and stay valid
+ * until rsnv_free; callers must not modify or free them. Any out pointer may be NULL.
+ * Returns RSNV_ERR_INVALID_ARGUMENT for a NULL handle or an index out of range.
+ */
+int32_t rsnv_result_gene(const RsnvResult *handle,
+                         uintptr_t index,
+                         const char **id_ptr,
+                         uintptr_t *id_len,
+                         const char **seq_ptr,
+                         uintptr_t *seq_len);
+
+/**
+ * Run summary as a NUL-terminated JSON object ({"genes", "mutations_applied", "gene_mutations"}),
+ * owned by the handle and valid until rsnv_free. Returns NULL for a NULL handle.
+ */
+const char *rsnv_result_summary_json(const RsnvResult *handle);
+
+/**
+ * Releases a handle from rsnv_run and every string obtained from it. NULL is ignored.
+ */
+void rsnv_free(RsnvResult *handle);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* RSNV_H */
@@ -0,0 +1,127 @@
+// C ABI 的集成测试：用 dlopen 加载构建出的 cdylib（需要 capi feature），按 rsnv.h 的声明调用
+#![cfg(all(feature = "capi", target_os = "linux"))]
+
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::fs;
+use std::path::PathBuf;
+
+const RTLD_NOW: c_int = 2;
+
+extern "C" {
+    fn dlopen(filename: *const c_char, flags: c_int) -> *mut c_void;
+    fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+    fn dlerror() -> *const c_char;
+}
+
+type RunFn = unsafe extern "C" fn(*const c_char, *mut *mut c_void) -> i32;
+type LastErrorFn = unsafe extern "C" fn() -> *const c_char;
+type GeneCountFn = unsafe extern "C" fn(*const c_void) -> usize;
+type GeneFn = unsafe extern "C" fn(*const c_void, usize, *mut *const c_char, *mut usize, *mut *const c_char, *mut usize) -> i32;
+type SummaryFn = unsafe extern "C" fn(*const c_void) -> *const c_char;
+type FreeFn = unsafe extern "C" fn(*mut c_void);
+
+const RSNV_OK: i32 = 0;
+const RSNV_ERR_IO: i32 = 2;
+
+struct Library {
+    run: RunFn,
+    last_error: LastErrorFn,
+    gene_count: GeneCountFn,
+    gene: GeneFn,
+    summary_json: SummaryFn,
+    free: FreeFn,
+}
+
+// 本次构建的 cdylib 与测试可执行文件同在 target/<profile>/deps 下（target/<profile>/libz10.so 只在 cargo build 时更新）
+fn load_library() -> Library {
+    let path = std::env::current_exe().unwrap().with_file_name("libz10.so");
+    let path = CString::new(path.to_str().unwrap()).unwrap();
+    unsafe {
+        let handle = dlopen(path.as_ptr(), RTLD_NOW);
+        assert!(!handle.is_null(), "dlopen failed: {:?}", CStr::from_ptr(dlerror()));
+        let symbol = |name: &str| {
+            let name = CString::new(name).unwrap();
+            let symbol = dlsym(handle, name.as_ptr());
+            assert!(!symbol.is_null(), "missing symbol {:?}", name);
+            symbol
+        };
+        Library {
+            run: std::mem::transmute::<*mut c_void, RunFn>(symbol("rsnv_run")),
+            last_error: std::mem::transmute::<*mut c_void, LastErrorFn>(symbol("rsnv_last_error")),
+            gene_count: std::mem::transmute::<*mut c_void, GeneCountFn>(symbol("rsnv_result_gene_count")),
+            gene: std::mem::transmute::<*mut c_void, GeneFn>(symbol("rsnv_result_gene")),
+            summary_json: std::mem::transmute::<*mut c_void, SummaryFn>(symbol("rsnv_result_summary_json")),
+            free: std::mem::transmute::<*mut c_void, FreeFn>(symbol("rsnv_free")),
+        }
+    }
+}
+
+fn write_inputs(name: &str) -> (PathBuf, String) {
+    let dir = std::env::temp_dir().join(format!("z10-capi-{}-{}", std::process::id(), name));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("contigs.fa"), ">c1\nACGTACGTACGTACGTACGTACGTACGTAC\n>c2\nTTTTGGGGCCCC\n").unwrap();
+    fs::write(dir.join("mutations.csv"), "c1,2,T\nc1,5,G\nc2,3,A\n").unwrap();
+    fs::write(dir.join("positions.csv"), "c1,g1,1,10,+\nc1,g2,5,25,-\nc2,g3,2,8\n").unwrap();
+    let path = |name: &str| serde_json::Value::from(dir.join(name).to_str().unwrap());
+    let config = serde_json::json!({
+        "contigs": path("contigs.fa"),
+        "mutations": [path("mutations.csv")],
+        "positions": path("positions.csv"),
+        "threads": 2,
+    });
+    (dir, config.to_string())
+}
+
+#[test]
+fn run_returns_genes_and_summary() {
+    let library = load_library();
+    let (_dir, config) = write_inputs("ok");
+    let config = CString::new(config).unwrap();
+    let mut handle: *mut c_void = std::ptr::null_mut();
+    unsafe {
+        assert_eq!((library.run)(config.as_ptr(), &mut handle), RSNV_OK);
+        assert!(!handle.is_null());
+        let mut genes = vec![];
+        for index in 0..(library.gene_count)(handle) {
+            let (mut id, mut id_len, mut sequence, mut sequence_len) = (std::ptr::null(), 0, std::ptr::null(), 0);
+            assert_eq!((library.gene)(handle, index, &mut id, &mut id_len, &mut sequence, &mut sequence_len), RSNV_OK);
+            let id = CStr::from_ptr(id).to_str().unwrap().to_string();
+            let sequence = CStr::from_ptr(sequence).to_str().unwrap().to_string();
+            assert_eq!((id.len(), sequence.len()), (id_len, sequence_len));
+            genes.push((id, sequence));
+        }
+        let expected = [("g1", "ATGTGCGTAC"), ("g2", "TACGTACGTACGTACGTACGC"), ("g3", "TATGGGG")];
+        let expected: Vec<(String, String)> = expected.iter().map(|(id, sequence)| (id.to_string(), sequence.to_string())).collect();
+        assert_eq!(genes, expected);
+        let summary: serde_json::Value =
+            serde_json::from_str(CStr::from_ptr((library.summary_json)(handle)).to_str().unwrap()).unwrap();
+        assert_eq!(summary["genes"], 3);
+        assert_eq!(summary["mutations_applied"], 3);
+        let mut id = std::ptr::null();
+        let null = std::ptr::null_mut();
+        assert_ne!((library.gene)(handle, 3, &mut id, null, null.cast(), null), RSNV_OK);
+        (library.free)(handle);
+        (library.free)(std::ptr::null_mut());
+    }
+}
+
+#[test]
+fn run_reports_errors() {
+    let library = load_library();
+    let (dir, config) = write_inputs("error");
+    fs::remove_file(dir.join("contigs.fa")).unwrap();
+    let config = CString::new(config).unwrap();
+    let mut handle: *mut c_void = std::ptr::null_mut();
+    unsafe {
+        assert_eq!((library.run)(config.as_ptr(), &mut handle), RSNV_ERR_IO);
+        assert!(handle.is_null());
+        let message = CStr::from_ptr((library.last_error)()).to_str().unwrap();
+        assert!(message.contains("contigs.fa"), "{}", message);
+
+        let invalid = CString::new("{not json").unwrap();
+        assert_ne!((library.run)(invalid.as_ptr(), &mut handle), RSNV_OK);
+        assert!(handle.is_null());
+        assert_eq!((library.gene_count)(std::ptr::null()), 0);
+        assert!((library.summary_json)(std::ptr::null()).is_null());
+    }
+}
@@ -0,0 +1,1052 @@
+// 命令行的端到端测试：在临时目录中写入小的输入文件，运行编译好的 z10 并检查退出状态和输出
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const CONTIGS: &str = ">c1\nACGTACGTACGTACGTACGTACGTACGTAC\n>c2\nTTTTGGGGCCCC\n";
+const MUTATIONS: &str = "c1,2,T\nc1,5,G\nc2,3,A\n";
+const POSITIONS: &str = "c1,g1,1,10,+\nc1,g2,5,25,-\nc2,g3,2,8\n";
+
+// 每个测试一个独立的临时目录
+fn temp_dir(name: &str) -> PathBuf {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let dir = std::env::temp_dir().join(format!("z10-cli-{}-{}-{}", std::process::id(), name, NEXT.fetch_add(1, Ordering::Relaxed)));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn write_inputs(dir: &Path, contigs: &str, mutations: &str, positions: &str) -> [PathBuf; 3] {
+    let files = [dir.join("contigs.fa"), dir.join("mutations.csv"), dir.join("positions.csv")];
+    for (file, content) in files.iter().zip([contigs, mutations, positions]) {
+        fs::write(file, content).unwrap();
+    }
+    files
+}
+
+fn run(dir: &Path, inputs: &[PathBuf; 3], args: &[&str]) -> Output {
+    let output_file = dir.join("out.fa");
+    run_without_output(dir, inputs, &[&["-o", output_file.to_str().unwrap()], args].concat())
+}
+
+// 不加 -o，用于 --check 等不写输出的模式
+fn run_without_output(dir: &Path, inputs: &[PathBuf; 3], args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_z10"))
+        .args(inputs)
+        .args(["--lang", "en"])
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn rejects_bad_numeric_options() {
+    let dir = temp_dir("numeric");
+    let inputs = write_inputs(&dir, CONTIGS, MUTATIONS, POSITIONS);
+    let cases: &[(&[&str], &str)] = &[
+        (&["--max-n-fraction", "abc"], "--max-n-fraction"),
+        (&["--max-n-fraction", "150%"], "--max-n-fraction"),
+        (&["--max-n-fraction", "1.5"], "--max-n-fraction"),
+        (&["--flush-every", "ten"], "--flush-every"),
+        (&["--flush-every", "0"], "--flush-every"),
+        (&["--coverage", "cov.bedgraph", "--min-coverage", "-1"], "--min-coverage"),
+        (&["--min-coverage", "x"], "--min-coverage"),
+        (&["--mutated-quality", "94"], "--mutated-quality"),
+        (&["--mutated-quality", "high"], "--mutated-quality"),
+        (&["--max-memory", "abc"], "--max-memory"),
+    ];
+    for (args, flag) in cases {
+        let output = run(&dir, &inputs, args);
+        assert_eq!(output.status.code(), Some(1), "{:?}", args);
+        let message = stderr(&output);
+        assert!(message.starts_with("error: ") && message.contains(flag), "{:?}: {}", args, message);
+        assert!(!dir.join("out.fa").exists(), "{:?}", args);
+    }
+}
+
+#[test]
+fn accepts_percentage_n_fraction() {
+    let dir = temp_dir("percent");
+    let inputs = write_inputs(&dir, CONTIGS, MUTATIONS, POSITIONS);
+    let output = run(&dir, &inputs, &["--max-n-fraction", "5%", "--mutated-quality", "93", "--max-memory", "1.5G"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(dir.join("out.fa").exists());
+}
+
+// --check 只报告校验结果，不提及任何输出文件，也不创建它们
+#[test]
+fn check_prints_no_output_paths() {
+    let dir = temp_dir("check");
+    let inputs = write_inputs(&dir, CONTIGS, MUTATIONS, POSITIONS);
+    let output = run_without_output(&dir, &inputs, &["--check"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "coordinate validation passed: 3 genes, 3 mutations, 2 contigs\n");
+    assert!(!dir.join("output.fasta").exists());
+}
+
+// --check 先按 --contig-offset 调整突变位置再校验
+#[test]
+fn check_applies_contig_offset() {
+    let dir = temp_dir("check-offset");
+    let inputs = write_inputs(&dir, CONTIGS, "c1,2,T\nc2,13,A\n", POSITIONS);
+    fs::write(dir.join("offsets.csv"), "c2,-2\n").unwrap();
+    fs::write(dir.join("bad-offsets.csv"), "c1,+100\n").unwrap();
+
+    let output = run_without_output(&dir, &inputs, &["--check"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("1 problems"), "{}", stderr(&output));
+
+    let output = run_without_output(&dir, &inputs, &["--check", "--contig-offset", "offsets.csv"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = run_without_output(&dir, &inputs, &["--check", "--contig-offset", "bad-offsets.csv"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("2 problems"), "{}", stderr(&output));
+}
+
+// --check 先按 --liftover 换算基因坐标再校验
+#[test]
+fn check_applies_liftover() {
+    let dir = temp_dir("check-liftover");
+    let inputs = write_inputs(&dir, CONTIGS, MUTATIONS, "old1,g1,1,10,+\nold1,g2,16,30,-\n");
+    fs::write(dir.join("old-to-new.chain"), "chain 100 old1 30 + 0 30 c1 30 + 0 25 1\n10\t5\t0\n15\n").unwrap();
+
+    let output = run_without_output(&dir, &inputs, &["--check"]);
+    assert_eq!(output.status.code(), Some(1));
+
+    let output = run_without_output(&dir, &inputs, &["--check", "--liftover", "old-to-new.chain"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "coordinate validation passed: 2 genes, 3 mutations, 2 contigs\n");
+}
+
+// 突变ID（突变文件第4列或 VCF 的 ID 列）原样出现在 applied VCF 和校验信息中，缺失时为 "."
+#[test]
+fn mutation_ids_reach_the_reports() {
+    let dir = temp_dir("mutation-id");
+    let inputs = write_inputs(&dir, CONTIGS, "c1,2,T,rs1\nc1,5,G\nc2,3,A,.\n", POSITIONS);
+    fs::write(
+        dir.join("extra.vcf"),
+        "##fileformat=VCFv4.2\n#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\nc2\t5\trs9\tG\tC\t.\tPASS\t.\n",
+    )
+    .unwrap();
+
+    let output = run(&dir, &inputs, &["--vcf", "extra.vcf", "--applied-vcf", "applied.vcf"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let applied = fs::read_to_string(dir.join("applied.vcf")).unwrap();
+    let ids: Vec<(&str, &str)> = applied
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .map(|line| {
+            let fields: Vec<&str> = line.split('\t').collect();
+            (fields[1], fields[2])
+        })
+        .collect();
+    assert_eq!(ids, [("2", "rs1"), ("5", "."), ("3", "."), ("5", "rs9")]);
+
+    let inputs = write_inputs(&dir, CONTIGS, "c1,99,T,rs42\n", POSITIONS);
+    let output = run_without_output(&dir, &inputs, &["--check"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("mutation rs42"), "{}", stderr(&output));
+}
+
+// 主输出默认每行 60 个碱基，--wrap 0 不折行
+#[test]
+fn output_is_wrapped_at_sixty_by_default() {
+    let dir = temp_dir("wrap");
+    let inputs = write_inputs(&dir, &format!(">c1\n{}\n", "ACGTACGTAC".repeat(13)), "c1,1,T\n", "c1,g1,1,130,+\n");
+    let line_lengths = || fs::read_to_string(dir.join("out.fa")).unwrap().lines().map(str::len).collect::<Vec<_>>();
+
+    let output = run(&dir, &inputs, &[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(line_lengths()[1..], [60, 60, 10]);
+
+    let output = run(&dir, &inputs, &["--wrap", "0"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(line_lengths()[1..], [130]);
+}
+
+// 负链基因默认输出为正链切片（含突变）的反向互补，正链基因不变；--no-revcomp-minus 时按正链切片输出
+#[test]
+fn minus_strand_genes_are_reverse_complemented_by_default() {
+    let dir = temp_dir("revcomp");
+    let inputs = write_inputs(&dir, ">c1\nAACGTTGCAnRYc\n", "c1,2,G\n", "c1,plus,1,13,+\nc1,minus,1,13,-\n");
+    let records = || {
+        let fasta = fs::read_to_string(dir.join("out.fa")).unwrap();
+        fasta.lines().map(str::to_string).collect::<Vec<_>>()
+    };
+
+    let output = run(&dir, &inputs, &["--sort-by", "gene"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(records(), [">minus", "gRYnTGCAACGCT", ">plus", "AGCGTTGCAnRYc"]);
+
+    let output = run(&dir, &inputs, &["--revcomp-minus", "--sort-by", "gene"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(records(), [">minus", "gRYnTGCAACGCT", ">plus", "AGCGTTGCAnRYc"]);
+
+    let output = run(&dir, &inputs, &["--no-revcomp-minus", "--sort-by", "gene"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(records(), [">minus", "AGCGTTGCAnRYc", ">plus", "AGCGTTGCAnRYc"]);
+}
+
+// 同一 contig 上的缺失、插入和替换：基因序列按参考坐标截取后应用编辑，下游基因在编辑后 contig 上的坐标随之平移
+#[test]
+fn indels_and_substitutions_on_one_contig() {
+    let dir = temp_dir("indels");
+    let mutations = "c1,3,G,del,,GTA\nc1,8,TCCC,ins,,T\nc1,10,A\n";
+    let inputs = write_inputs(&dir, CONTIGS, mutations, "c1,g1,1,12,+\nc1,g2,15,20,+\n");
+
+    let output = run(&dir, &inputs, &["--sort-by", "gene", "--edited-coords", "coords.tsv", "--applied-vcf", "applied.vcf"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let fasta = fs::read_to_string(dir.join("out.fa")).unwrap();
+    assert_eq!(fasta.lines().collect::<Vec<_>>(), [">g1", "ACGCGTCCCAAGT", ">g2", "GTACGT"]);
+
+    let coords = fs::read_to_string(dir.join("coords.tsv")).unwrap();
+    let rows: Vec<&str> = coords.lines().filter(|line| !line.starts_with(';')).skip(1).collect();
+    assert_eq!(rows, ["g1\tc1\t1\t12\t1\t13\t+", "g2\tc1\t15\t20\t16\t21\t+"]);
+
+    let applied = fs::read_to_string(dir.join("applied.vcf")).unwrap();
+    let alleles: Vec<String> = applied
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .map(|line| line.split('\t').skip(1).take(4).collect::<Vec<_>>().join(" "))
+        .collect();
+    assert_eq!(alleles, ["3 del GTA G", "8 ins T TCCC", "10 . C A"]);
+}
+
+// 基因首末碱基上的突变默认应用；--exclude-boundary 时不应用并给出数量，基因内部的突变不受影响
+#[test]
+fn exclude_boundary_drops_mutations_on_the_first_and_last_base() {
+    let dir = temp_dir("boundary");
+    let inputs = write_inputs(&dir, CONTIGS, "c1,1,T\nc1,2,T\nc1,10,A\nc1,11,T\n", "c1,g1,1,10,+\n");
+
+    let output = run(&dir, &inputs, &[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(fs::read_to_string(dir.join("out.fa")).unwrap(), ">g1\nTTGTACGTAA\n");
+
+    let output = run(&dir, &inputs, &["--exclude-boundary"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(fs::read_to_string(dir.join("out.fa")).unwrap(), ">g1\nATGTACGTAC\n");
+    assert!(stdout(&output).contains("--exclude-boundary: 2 mutations on the first or last base of a gene were not applied"), "{}", stdout(&output));
+}
+
+// 同样的输入运行两次，每种 --sort-by 下的输出都逐字节相同；默认按 contig ID 和起点排序
+#[test]
+fn repeated_runs_give_byte_identical_output() {
+    let dir = temp_dir("deterministic");
+    let contigs: String = (1..=5).map(|contig| format!(">c{}\n{}\n", contig, "ACGTTGCA".repeat(40))).collect();
+    let mutations: String = (1..=5).flat_map(|contig| (1..=320).step_by(7).map(move |position| format!("c{},{},N\n", contig, position))).collect();
+    // 位置文件中的顺序与 contig 和起点的顺序都不同
+    let positions: String = (0..60)
+        .map(|row| (row * 37) % 60)
+        .map(|index| format!("c{},gene{},{},{},+\n", 5 - index % 5, index, 1 + index / 5 * 25, 30 + index / 5 * 25))
+        .collect();
+    let inputs = write_inputs(&dir, &contigs, &mutations, &positions);
+
+    for sort_by in ["position", "gene", "input"] {
+        let outputs: Vec<Vec<u8>> = (0..2)
+            .map(|_| {
+                let output = run(&dir, &inputs, &["-t", "4", "--sort-by", sort_by]);
+                assert!(output.status.success(), "{}", stderr(&output));
+                fs::read(dir.join("out.fa")).unwrap()
+            })
+            .collect();
+        assert_eq!(outputs[0], outputs[1], "--sort-by {}", sort_by);
+    }
+
+    let output = run(&dir, &inputs, &["-t", "4"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let fasta = fs::read_to_string(dir.join("out.fa")).unwrap();
+    let ids: Vec<usize> = fasta.lines().filter_map(|line| line.strip_prefix(">gene")).map(|id| id.parse().unwrap()).collect();
+    let mut sorted = ids.clone();
+    sorted.sort_by_key(|&index| (5 - index % 5, index / 5));
+    assert_eq!(ids.len(), 60);
+    assert_eq!(ids, sorted);
+}
+
+// --summary 的计数只包含落在基因范围内且通过参考碱基核对的突变；重叠区内的突变计入两个基因，
+// 参考不一致、基因之外和超出 contig 末端的突变都不计
+#[test]
+fn summary_counts_match_a_hand_built_input() {
+    let dir = temp_dir("summary");
+    let mutations = "c1,2,T\nc1,9,G,m1,,A\nc1,12,A,m2,,C\nc1,14,T\nc1,25,G\nc2,3,A\nc2,40,A\n";
+    let positions = "c1,g2,8,15\nc1,g1,1,10\nc2,g4,10,12\nc2,g3,2,8\n";
+    let inputs = write_inputs(&dir, CONTIGS, mutations, positions);
+    let output = run(&dir, &inputs, &["--summary", "summary.tsv"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let summary = fs::read_to_string(dir.join("summary.tsv")).unwrap();
+    let rows: Vec<&str> = summary.lines().filter(|line| !line.starts_with(';')).collect();
+    assert_eq!(
+        rows,
+        [
+            "gene_id\tcontig_id\tstart\tend\tlength\tnum_mutations_applied",
+            "g1\tc1\t1\t10\t10\t2",
+            "g2\tc1\t8\t15\t8\t2",
+            "g3\tc2\t2\t8\t7\t1",
+            "g4\tc2\t10\t12\t3\t0",
+        ]
+    );
+}
+
+// FASTA 标题中 ID 之后的描述（空格或制表符分隔）不影响按 contig ID 查找基因和突变
+#[test]
+fn descriptive_fasta_headers_are_looked_up_by_id() {
+    let dir = temp_dir("descriptions");
+    let inputs = write_inputs(&dir, CONTIGS, MUTATIONS, POSITIONS);
+    assert!(run(&dir, &inputs, &[]).status.success());
+    let expected = fs::read_to_string(dir.join("out.fa")).unwrap();
+
+    let described = CONTIGS.replace(">c1\n", ">c1 length=30 cov=30\n").replace(">c2\n", ">c2\tplasmid copy=2\n");
+    let inputs = write_inputs(&dir, &described, MUTATIONS, POSITIONS);
+    let output = run(&dir, &inputs, &["--summary", "summary.tsv"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("in gene windows 3 (applied 3)") && stderr(&output).contains("missing contig 0"), "{}", stderr(&output));
+    assert_eq!(fs::read_to_string(dir.join("out.fa")).unwrap(), expected);
+    let summary = fs::read_to_string(dir.join("summary.tsv")).unwrap();
+    let contig_ids: Vec<&str> = summary.lines().filter(|line| !line.starts_with(';')).skip(1).map(|line| line.split('\t').nth(1).unwrap()).collect();
+    assert_eq!(contig_ids, ["c1", "c1", "c2"]);
+}
+
+// --vcf 读取小的 VCF：跳过 # 开头的行和 ALT 为 "." 的记录，多等位位点默认报错、--vcf-multiallelic first 时取第一个 ALT，
+// 插入按 REF/ALT 应用，符号等位基因计数后跳过
+#[test]
+fn small_vcf_is_read_as_a_mutation_source() {
+    let dir = temp_dir("vcf");
+    let inputs = write_inputs(&dir, CONTIGS, "c2,3,A\n", "c1,g1,1,10\nc2,g3,2,8\n");
+    let vcf = [
+        "##fileformat=VCFv4.2",
+        "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO",
+        "c1\t2\tv1\tC\tT\t.\tPASS\t.",
+        "c1\t4\tv2\tT\t.\t.\tPASS\t.",
+        "c1\t5\tv3\tA\tG,C\t.\tPASS\t.",
+        "c1\t7\tv4\tG\tGAA\t.\tPASS\t.",
+        "c1\t9\tv5\tA\t<DEL>\t.\tPASS\t.",
+    ]
+    .join("\n");
+    fs::write(dir.join("calls.vcf"), vcf + "\n").unwrap();
+
+    let output = run(&dir, &inputs, &["--vcf", "calls.vcf"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("VCF line 5 c1:5 is multi-allelic (ALT G,C)"), "{}", stderr(&output));
+
+    let output = run(&dir, &inputs, &["--vcf", "calls.vcf", "--vcf-multiallelic", "first"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("warning: skipped 1 VCF records with symbolic alleles"), "{}", stderr(&output));
+    let records = fasta_records(&fs::read_to_string(dir.join("out.fa")).unwrap());
+    assert_eq!(records["g1"], ">g1\nATGTGCGAATAC\n");
+    assert_eq!(records["g3"], ">g3\nTATGGGG\n");
+}
+
+// --lang en/zh 选择消息语言，两种语言使用相同的参数；未给出 --lang 时按 LC_ALL、LC_MESSAGES、LANG 选择
+#[test]
+fn lang_selects_the_message_language() {
+    let dir = temp_dir("lang");
+    let inputs = write_inputs(&dir, CONTIGS, "c1,5,G,m2,,T\n", "c1,g1,1,10\n");
+    let english = "reference base mismatch for mutation m2: c1:5 expected T, found A";
+    let chinese = "突变 m2 的参考碱基不一致：c1:5 期望 T，实际为 A";
+    let run_with = |lang: Option<&str>, locale: &str| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_z10"));
+        command.args(&inputs).args(["-o", "out.fa"]).current_dir(&dir);
+        command.env_remove("LC_ALL").env_remove("LC_MESSAGES").env("LANG", locale);
+        if let Some(lang) = lang {
+            command.args(["--lang", lang]);
+        }
+        let output = command.output().unwrap();
+        assert!(output.status.success(), "{}", stderr(&output));
+        stderr(&output)
+    };
+
+    for (lang, locale, expected, unexpected) in [
+        (Some("en"), "zh_CN.UTF-8", english, chinese),
+        (Some("zh"), "en_US.UTF-8", chinese, english),
+        (None, "en_US.UTF-8", english, chinese),
+        (None, "zh_CN.UTF-8", chinese, english),
+        (None, "C", chinese, english),
+    ] {
+        let messages = run_with(lang, locale);
+        assert!(messages.contains(expected) && !messages.contains(unexpected), "--lang {:?} LANG={}: {}", lang, locale, messages);
+    }
+}
+
+// 坐标无效的基因逐个警告后跳过，同一输入中的有效基因照常输出
+#[test]
+fn invalid_gene_intervals_are_skipped() {
+    let dir = temp_dir("gene-range");
+    let positions = "c1,zero,0,5\nc1,past,20,40\nc1,backwards,9,4\nc1,g1,1,10\n";
+    let inputs = write_inputs(&dir, CONTIGS, MUTATIONS, positions);
+    let output = run(&dir, &inputs, &[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let message = stderr(&output);
+    for expected in ["starts at 0", "starts after its end", "interval 20-40 of gene past is outside contig c1 (1-30)"] {
+        assert!(message.contains(expected), "{}: {}", expected, message);
+    }
+    assert_eq!(fs::read_to_string(dir.join("out.fa")).unwrap(), ">g1\nATGTGCGTAC\n");
+}
+
+// 缺失的 contig 汇总为一行，基因数与被跳过的基因一致；--verbose 时逐个列出
+#[test]
+fn missing_contigs_are_summarized() {
+    let dir = temp_dir("missing-contig");
+    let inputs = write_inputs(&dir, CONTIGS, MUTATIONS, "cX,a,1,5\ncX,b,2,6\ncY,c,1,3\nc1,g1,1,10\n");
+    let summary = "warning: 2 contigs are referenced by genes but missing from the FASTA; 3 genes on them will not be output: cX, cY\n";
+    let per_gene = |message: &str| message.lines().filter(|line| line.ends_with("is not in the FASTA")).count();
+
+    let output = run(&dir, &inputs, &[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let message = stderr(&output);
+    assert_eq!(message.matches(summary).count(), 1, "{}", message);
+    assert_eq!(per_gene(&message), 0, "{}", message);
+    assert_eq!(fs::read_to_string(dir.join("out.fa")).unwrap(), ">g1\nATGTGCGTAC\n");
+
+    let output = run(&dir, &inputs, &["--verbose"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let message = stderr(&output);
+    assert_eq!(message.matches(summary).count(), 1, "{}", message);
+    assert_eq!(per_gene(&message), 3, "{}", message);
+}
+
+// --translate：按基因的链翻译突变后的序列，内部终止密码子和不完整的末尾密码子在摘要中报告
+#[test]
+fn translate_writes_the_mutated_peptides() {
+    let dir = temp_dir("translate");
+    let contigs = ">c1\nATGAAATAGTGGTAA\n>c2\nTTAAAACAT\n";
+    let inputs = write_inputs(&dir, contigs, "c2,5,G\n", "c1,orf,1,15,+\nc1,partial,1,8,+\nc2,rev,1,9,-\n");
+    let proteins = || fs::read_to_string(dir.join("proteins.fa")).unwrap();
+
+    let output = run(&dir, &inputs, &["--translate", "proteins.fa", "--internal-stop", "mark", "--codon-table", "11"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(proteins(), ">orf internal_stop\nMK*W*\n>partial\nMK\n>rev\nMS*\n");
+    assert!(stdout(&output).contains("(1 genes with internal stop codons)"), "{}", stdout(&output));
+    assert!(stderr(&output).contains("1 genes have a length that is not a multiple of 3"), "{}", stderr(&output));
+
+    // 突变把内部的 TAG 改为 TAC 后不再有内部终止密码子
+    fs::write(&inputs[1], "c1,9,C\nc2,5,G\n").unwrap();
+    let output = run(&dir, &inputs, &["--translate", "proteins.fa", "--internal-stop", "mark"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(proteins(), ">orf\nMKYW*\n>partial\nMK\n>rev\nMS*\n");
+}
+
+// 标准输入（"-"）只能用于一个输入：用于两个输入时在读取前报错、不写输出；只用于一个输入时照常读取
+#[test]
+fn stdin_can_be_used_for_one_input_only() {
+    let dir = temp_dir("stdin");
+    let [contigs, mutations, positions] = write_inputs(&dir, CONTIGS, MUTATIONS, POSITIONS);
+    let run_with_stdin = |args: [&Path; 3]| {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_z10"))
+            .args(args)
+            .args(["--lang", "en", "-o", "out.fa"])
+            .current_dir(&dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        // 报错退出时不会读取标准输入，写入失败可以忽略
+        let _ = child.stdin.take().unwrap().write_all(MUTATIONS.as_bytes());
+        child.wait_with_output().unwrap()
+    };
+    let stdin = Path::new("-");
+
+    let output = run_with_stdin([stdin, stdin, &positions]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stderr(&output), "error: standard input (\"-\") can be used for only one input file\n");
+    assert!(!dir.join("out.fa").exists());
+
+    // 从标准输入读取突变的结果与直接读取文件相同
+    let output = run(&dir, &[contigs.clone(), mutations, positions.clone()], &[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let expected = fs::read_to_string(dir.join("out.fa")).unwrap();
+    fs::remove_file(dir.join("out.fa")).unwrap();
+    let output = run_with_stdin([&contigs, stdin, &positions]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(fs::read_to_string(dir.join("out.fa")).unwrap(), expected);
+}
+
+// --check：基因超出 contig 末端时列出该基因并以非零状态退出，不写输出
+#[test]
+fn check_reports_out_of_range_gene() {
+    let dir = temp_dir("check-range");
+    let inputs = write_inputs(&dir, CONTIGS, MUTATIONS, "c1,g1,1,10,+\nc2,long,5,40,-\n");
+    let output = run_without_output(&dir, &inputs, &["--check"]);
+    assert_eq!(output.status.code(), Some(1));
+    let message = stderr(&output);
+    assert!(message.contains("end position 40 of gene long is past the length 12 of contig c2"), "{}", message);
+    assert!(message.contains("found 1 problems"), "{}", message);
+    assert_eq!(stdout(&output), "");
+    assert!(!dir.join("out.fa").exists() && !dir.join("output.fasta").exists());
+}
+
+// 起点大于终点的警告与起点为 0 的警告一样输出换算为 1-based 之后的坐标
+#[test]
+fn invalid_interval_warnings_use_converted_coordinates() {
+    let dir = temp_dir("coord-base-warning");
+    let inputs = write_inputs(&dir, CONTIGS, MUTATIONS, "c1,backwards,9,4\nc1,g1,0,10\n");
+    let output = run(&dir, &inputs, &["--coord-base", "0"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("gene interval 10-4 is empty or starts after its end"), "{}", stderr(&output));
+    assert_eq!(fs::read_to_string(dir.join("out.fa")).unwrap(), ">g1\nATGTGCGTAC\n");
+}
+
+// 第6列给出的参考碱基与 contig 不一致时警告并跳过该突变；--strict 时是错误，不写输出
+#[test]
+fn reference_mismatches_are_skipped_or_rejected_with_strict() {
+    let dir = temp_dir("ref-mismatch");
+    let inputs = write_inputs(&dir, CONTIGS, "c1,2,T,m1,,c\nc1,5,G,m2,,T\n", "c1,g1,1,10\n");
+    let output = run(&dir, &inputs, &[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let message = stderr(&output);
+    assert!(message.contains("reference base mismatch for mutation m2: c1:5 expected T, found A"), "{}", message);
+    assert!(!message.contains("m1"), "{}", message);
+    assert!(stdout(&output).contains("reference check: 1 mutations do not match the contig base and were skipped"));
+    assert_eq!(fs::read_to_string(dir.join("out.fa")).unwrap(), ">g1\nATGTACGTAC\n");
+
+    fs::remove_file(dir.join("out.fa")).unwrap();
+    let output = run(&dir, &inputs, &["--strict"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("--strict: reference base mismatch for mutation m2"), "{}", stderr(&output));
+    assert!(!dir.join("out.fa").exists());
+}
+
+// 来源信息：json/jsonl 主输出、FASTA 旁的 .provenance.json、--stats 和 ; 注释中的内容一致，
+// 配置为生效的值（含默认值），输入的大小和 BLAKE3 与文件一致
+#[test]
+fn provenance_blocks_match_the_run() {
+    let dir = temp_dir("provenance");
+    let inputs = write_inputs(&dir, CONTIGS, MUTATIONS, POSITIONS);
+    let args = [
+        "--format", "json,fasta", "--fasta-out", "genes.fa", "--jsonl-out", "out.jsonl", "--provenance-sidecar",
+        "--stats", "stats.json", "--id-map", "ids.tsv", "-t", "2", "--timings",
+    ];
+    let output = run(&dir, &inputs, &args);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let read_json = |name: &str| -> serde_json::Value { serde_json::from_str(&fs::read_to_string(dir.join(name)).unwrap()).unwrap() };
+
+    // -o 写的是 json，FASTA 在 genes.fa，旁注文件跟随 FASTA
+    assert!(!dir.join("out.fa.provenance.json").exists());
+    let provenance = read_json("genes.fa.provenance.json");
+    assert_eq!(read_json("out.fa")["provenance"], provenance);
+    assert_eq!(read_json("stats.json")["provenance"], provenance);
+    let jsonl = fs::read_to_string(dir.join("out.jsonl")).unwrap();
+    let first_line: serde_json::Value = serde_json::from_str(jsonl.lines().next().unwrap()).unwrap();
+    assert_eq!(first_line, serde_json::json!({ "provenance": provenance }));
+    assert_eq!(jsonl.lines().count(), 4);
+
+    let configuration = &provenance["configuration"];
+    assert_eq!(configuration["threads"], "2");
+    assert_eq!(configuration["wrap"], "60");
+    assert_eq!(configuration["strategy"], "buffer");
+    assert_eq!(configuration["revcomp-minus"], "true");
+    assert_eq!(configuration["id-map"], "ids.tsv");
+    assert_eq!(configuration["positions"], inputs[2].to_str().unwrap());
+    // 与运行摘要比较：输出路径和线程数
+    let outputs = configuration["outputs"].as_str().unwrap();
+    let summary = stdout(&output);
+    for (format, path) in outputs.split(',').map(|output| output.split_once('=').unwrap()) {
+        assert!(summary.contains(&format!("mutated gene sequences ({}): {}", format, path)), "{}: {}", outputs, summary);
+    }
+    assert!(stderr(&output).contains("(2 work items, 2 threads)"), "{}", stderr(&output));
+
+    let recorded = provenance["inputs"].as_array().unwrap();
+    assert_eq!(recorded.len(), 3);
+    for input in &inputs {
+        let content = fs::read(input).unwrap();
+        let entry = recorded.iter().find(|entry| entry["path"] == input.to_str().unwrap()).unwrap();
+        assert_eq!(entry["size"], content.len());
+        assert_eq!(entry["blake3"], blake3::hash(&content).to_hex().as_str());
+    }
+    assert!(provenance["started_at"].as_u64().unwrap() <= provenance["finished_at"].as_u64().unwrap());
+
+    let id_map = fs::read_to_string(dir.join("ids.tsv")).unwrap();
+    assert!(id_map.contains("; config.threads=2\n"), "{}", id_map);
+    assert!(id_map.contains(&format!("; finished_at={}\n", provenance["finished_at"])), "{}", id_map);
+
+    let output = run(&dir, &inputs, &["--format", "json", "--no-provenance"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(read_json("out.fa").get("provenance").is_none());
+}
+
+// --sqlite 的 mutations 表同时列出没有应用的突变及原因：与 genes 表按 gene_id 连接，各类数目与 --stats 一致
+#[cfg(feature = "sqlite")]
+#[test]
+fn sqlite_mutations_table_lists_rejected_mutations_with_reasons() {
+    let dir = temp_dir("sqlite-rejected");
+    let mutations = "c1,2,T\nc1,5,G\nc1,9,T,m9,,G\nc1,28,A\nc1,40,A\nc2,3,A\nc2,3,C\nc3,1,A\n";
+    let inputs = write_inputs(&dir, CONTIGS, mutations, POSITIONS);
+    let output = run(&dir, &inputs, &["--sqlite", "run.db", "--stats", "stats.json"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let stats: serde_json::Value = serde_json::from_str(&fs::read_to_string(dir.join("stats.json")).unwrap()).unwrap();
+
+    let conn = rusqlite::Connection::open(dir.join("run.db")).unwrap();
+    let count = |sql: &str| -> u64 { conn.query_row(sql, [], |row| row.get::<_, i64>(0)).unwrap() as u64 };
+    let applied = count("SELECT count(*) FROM mutations m JOIN genes g ON m.gene_id = g.id WHERE m.status = 'applied'");
+    assert_eq!(applied, stats["applied"].as_u64().unwrap());
+    assert_eq!(count("SELECT count(*) FROM mutations WHERE status = 'applied' AND reason IS NOT NULL"), 0);
+    let rejected = count("SELECT count(*) FROM mutations m LEFT JOIN genes g ON m.gene_id = g.id WHERE m.status = 'rejected' AND g.id IS NULL AND m.reason IS NOT NULL");
+    assert_eq!(rejected + applied, stats["mutations_read"].as_u64().unwrap());
+    let reason = |reason: &str| count(&format!("SELECT count(*) FROM mutations WHERE status = 'rejected' AND reason = '{}'", reason));
+    assert_eq!(reason("out_of_range"), stats["out_of_range"].as_u64().unwrap());
+    assert_eq!(
+        reason("outside_genes"),
+        stats["intergenic"].as_u64().unwrap() + stats["contig_without_genes"].as_u64().unwrap()
+    );
+    assert_eq!(reason("ref_mismatch"), stats["filtered"].as_u64().unwrap());
+    assert_eq!(reason("duplicate_position"), 1);
+
+    let (contig, position, ref_allele, alt): (String, i64, Option<String>, String) = conn
+        .query_row("SELECT contig, position, ref, alt FROM mutations WHERE id = 'm9'", [], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })
+        .unwrap();
+    assert_eq!((contig.as_str(), position, ref_allele.as_deref(), alt.as_str()), ("c1", 9, Some("G"), "T"));
+}
+
+// --report-parquet 的列固定不变（下游按列名和类型读取）；没有应用的突变以 rejected 行写出，带原因
+#[cfg(feature = "arrow")]
+#[test]
+fn report_parquet_schema_is_pinned() {
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::Int32Type;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    let dir = temp_dir("parquet");
+    let inputs = write_inputs(&dir, CONTIGS, "c1,2,T,m1\nc1,28,A\nc1,40,A\n", POSITIONS);
+    let output = run(&dir, &inputs, &["--report-parquet", "report.parquet"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let reader = ParquetRecordBatchReaderBuilder::try_new(fs::File::open(dir.join("report.parquet")).unwrap()).unwrap();
+    let fields: Vec<String> = reader
+        .schema()
+        .fields()
+        .iter()
+        .map(|field| format!("{} {} {}", field.name(), field.data_type(), if field.is_nullable() { "null" } else { "not null" }))
+        .collect();
+    assert_eq!(
+        fields,
+        [
+            "gene_id Utf8 null",
+            "contig Dictionary(Int32, Utf8) not null",
+            "position UInt64 not null",
+            "ref Dictionary(Int32, Utf8) null",
+            "alt Dictionary(Int32, Utf8) not null",
+            "status Dictionary(Int32, Utf8) not null",
+            "reason Dictionary(Int32, Utf8) null",
+            "gene_position UInt64 null",
+            "id Utf8 null",
+            "edited_position UInt64 null",
+        ]
+    );
+
+    let batches: Vec<_> = reader.build().unwrap().map(Result::unwrap).collect();
+    let column = |name: &str| -> Vec<Option<String>> {
+        batches
+            .iter()
+            .flat_map(|batch| {
+                let array = batch.column_by_name(name).unwrap().as_dictionary::<Int32Type>();
+                let values = array.values().as_string::<i32>();
+                array.keys().iter().map(|key| key.map(|key| values.value(key as usize).to_string())).collect::<Vec<_>>()
+            })
+            .collect()
+    };
+    let strings = |values: &[Option<&str>]| -> Vec<Option<String>> { values.iter().map(|value| value.map(String::from)).collect() };
+    assert_eq!(column("status"), strings(&[Some("applied"), Some("rejected"), Some("rejected")]));
+    assert_eq!(column("reason"), strings(&[None, Some("outside_genes"), Some("out_of_range")]));
+    assert_eq!(column("ref"), strings(&[Some("C"), None, None]));
+}
+
+// 主 FASTA 中的记录，按基因ID索引（包括 > 行和折行后的序列行）
+fn fasta_records(text: &str) -> std::collections::HashMap<String, String> {
+    text.split_inclusive('\n')
+        .fold(Vec::<String>::new(), |mut records, line| {
+            match records.last_mut() {
+                Some(record) if !line.starts_with('>') => record.push_str(line),
+                _ => records.push(line.to_string()),
+            }
+            records
+        })
+        .into_iter()
+        .map(|record| (record[1..record.find('\n').unwrap()].to_string(), record))
+        .collect()
+}
+
+// --split-output-tar 的记录与主 FASTA 一致：按 --wrap 折行，负链基因取反向互补
+#[test]
+fn split_tar_entries_match_the_main_fasta() {
+    let dir = temp_dir("split-tar");
+    let inputs = write_inputs(&dir, CONTIGS, MUTATIONS, POSITIONS);
+    let args = ["--wrap", "4", "--split-output-tar", "genes.tar"];
+    let output = run(&dir, &inputs, &args);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let main = fasta_records(&fs::read_to_string(dir.join("out.fa")).unwrap());
+    assert_eq!(main["g2"], ">g2\nTACG\nTACG\nTACG\nTACG\nTACG\nC\n");
+
+    let mut archive = tar::Archive::new(fs::File::open(dir.join("genes.tar")).unwrap());
+    let mut entries = vec![];
+    for entry in archive.entries().unwrap() {
+        let mut entry = entry.unwrap();
+        let name = entry.path().unwrap().display().to_string();
+        let mut content = String::new();
+        std::io::Read::read_to_string(&mut entry, &mut content).unwrap();
+        entries.push((name, content));
+    }
+    let names: Vec<&str> = entries.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(names, ["g1.fasta", "g2.fasta", "g3.fasta"]);
+    for (name, content) in &entries {
+        assert_eq!(content, &main[name.trim_end_matches(".fasta")], "{}", name);
+    }
+}
+
+// --group-by 的记录与主 FASTA 一致：按 --wrap 折行，负链基因取反向互补
+#[test]
+fn grouped_outputs_match_the_main_fasta() {
+    let dir = temp_dir("group-by");
+    let inputs = write_inputs(&dir, CONTIGS, MUTATIONS, POSITIONS);
+    fs::write(dir.join("groups.csv"), "g1,kinase\ng2,kinase\n").unwrap();
+    let output = run(&dir, &inputs, &["--wrap", "4", "--group-by", "groups.csv", "--group-dir", "grouped"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let main = fasta_records(&fs::read_to_string(dir.join("out.fa")).unwrap());
+    let grouped = |name: &str| fs::read_to_string(dir.join("grouped").join(name)).unwrap();
+    assert_eq!(grouped("kinase.fasta"), main["g1"].clone() + &main["g2"]);
+    assert!(grouped("kinase.fasta").contains(">g2\nTACG\nTACG\n"));
+    assert_eq!(grouped("ungrouped.fasta"), main["g3"]);
+}
+
+// Prometheus 文本格式中的一个样本：(指标名, 标签, 值)，标签值已去掉转义
+type Sample = (String, Vec<(String, String)>, f64);
+
+// 按 Prometheus 文本格式的基本语法解析：# HELP/# TYPE 注释、指标名和标签名的字符集、带转义的标签值和数值
+fn parse_exposition(text: &str) -> Vec<Sample> {
+    let is_name = |name: &str, colon: bool| {
+        let mut chars = name.chars();
+        chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || (colon && c == ':'))
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || (colon && c == ':'))
+    };
+    let mut types: std::collections::HashMap<String, String> = std::collections::HashMap::new();
+    let mut samples = vec![];
+    assert!(text.ends_with('\n'), "{:?}", text);
+    for line in text.lines() {
+        if let Some(comment) = line.strip_prefix("# ") {
+            let mut fields = comment.splitn(3, ' ');
+            let (keyword, name, rest) = (fields.next().unwrap(), fields.next().unwrap(), fields.next().unwrap_or(""));
+            assert!(is_name(name, true), "{}", line);
+            match keyword {
+                "HELP" => assert!(!rest.is_empty(), "{}", line),
+                "TYPE" => {
+                    assert!(["counter", "gauge", "histogram", "summary", "untyped"].contains(&rest), "{}", line);
+                    assert!(types.insert(name.to_string(), rest.to_string()).is_none(), "TYPE repeated: {}", line);
+                }
+                _ => panic!("unknown comment: {}", line),
+            }
+            continue;
+        }
+        let name_end = line.find(['{', ' ']).unwrap_or_else(|| panic!("no value: {}", line));
+        let name = &line[..name_end];
+        assert!(is_name(name, true), "{}", line);
+        assert!(types.contains_key(name), "sample before its TYPE: {}", line);
+        if types[name] == "counter" {
+            assert!(name.ends_with("_total"), "{}", line);
+        }
+        let mut rest = &line[name_end..];
+        let mut labels = vec![];
+        if let Some(mut inner) = rest.strip_prefix('{') {
+            loop {
+                let (label, after) = inner.split_once("=\"").unwrap_or_else(|| panic!("bad label: {}", line));
+                assert!(is_name(label, false) && !label.starts_with("__"), "{}", line);
+                let mut value = String::new();
+                let mut chars = after.char_indices();
+                let end = loop {
+                    match chars.next() {
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, '\\')) => value.push('\\'),
+                            Some((_, '"')) => value.push('"'),
+                            Some((_, 'n')) => value.push('\n'),
+                            other => panic!("bad escape {:?}: {}", other, line),
+                        },
+                        Some((index, '"')) => break index,
+                        Some((_, c)) => value.push(c),
+                        None => panic!("unterminated label value: {}", line),
+                    }
+                };
+                labels.push((label.to_string(), value));
+                inner = &after[end + 1..];
+                if let Some(after) = inner.strip_prefix(',') {
+                    inner = after;
+                } else {
+                    rest = inner.strip_prefix('}').unwrap_or_else(|| panic!("bad label list: {}", line));
+                    break;
+                }
+            }
+        }
+        let value = rest.strip_prefix(' ').unwrap_or_else(|| panic!("no value: {}", line));
+        let value: f64 = value.parse().unwrap_or_else(|_| panic!("bad value: {}", line));
+        samples.push((name.to_string(), labels, value));
+    }
+    samples
+}
+
+// --metrics-file 的内容符合 Prometheus 文本格式，结束时写出的最终值与运行结果一致
+#[test]
+fn metrics_file_follows_the_exposition_format() {
+    let dir = temp_dir("metrics");
+    let positions = format!("{}c9,g9,1,5\n", POSITIONS);
+    let inputs = write_inputs(&dir, CONTIGS, "c1,2,T\nc1,5,G\nc2,3,A\nc9,2,A\n", &positions);
+    let job = "run \"7\" \\ nightly";
+    let label = format!("job={}", job);
+    let output = run(&dir, &inputs, &["--metrics-file", "z10.prom", "--metrics-label", &label, "--metrics-label", "team=genomics"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(!dir.join("z10.prom.tmp").exists());
+    let samples = parse_exposition(&fs::read_to_string(dir.join("z10.prom")).unwrap());
+    let value = |name: &str, labels: &[(&str, &str)]| {
+        samples
+            .iter()
+            .find(|(sample, sample_labels, _)| {
+                sample == name && labels.iter().all(|(key, value)| sample_labels.iter().any(|(k, v)| k == key && v == value))
+            })
+            .unwrap_or_else(|| panic!("{} {:?} missing", name, labels))
+            .2
+    };
+    assert_eq!(value("run_info", &[("version", env!("CARGO_PKG_VERSION")), ("job", job), ("team", "genomics")]), 1.0);
+    assert_eq!(value("genes_processed_total", &[]), 3.0);
+    assert_eq!(value("mutations_applied_total", &[]), 3.0);
+    assert_eq!(value("rejects_total", &[("reason", "missing_contig")]), 1.0);
+    assert_eq!(value("rejects_total", &[("reason", "out_of_range")]), 0.0);
+    assert_eq!(value("bytes_written_total", &[]), fs::metadata(dir.join("out.fa")).unwrap().len() as f64);
+}
+
+// 桩服务器收到的请求：(路径, Range 头)
+#[cfg(feature = "remote")]
+type StubRequests = std::sync::Arc<std::sync::Mutex<Vec<(String, Option<String>)>>>;
+
+// 本地的 HTTP 桩服务器：按路径返回 files 中的内容，支持单个 bytes=start-end 范围请求（206），
+// 其他路径返回 404。返回服务器地址和收到的请求
+#[cfg(feature = "remote")]
+fn start_stub_server(files: Vec<(&'static str, String)>) -> (String, StubRequests) {
+    use std::io::{BufRead, BufReader};
+    use std::sync::{Arc, Mutex};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = format!("http://{}", listener.local_addr().unwrap());
+    let requests: StubRequests = Arc::new(Mutex::new(vec![]));
+    let recorded = requests.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let path = request_line.split(' ').nth(1).unwrap_or_default().to_string();
+            let mut range = None;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header.trim_end().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = header.split_once(':') {
+                    if name.eq_ignore_ascii_case("range") {
+                        range = Some(value.trim().to_string());
+                    }
+                }
+            }
+            recorded.lock().unwrap().push((path.clone(), range.clone()));
+            let (status, body, extra) = match files.iter().find(|(name, _)| *name == path) {
+                None => ("404 Not Found", String::new(), String::new()),
+                Some((_, content)) => match range.as_deref().and_then(|range| range.strip_prefix("bytes=")) {
+                    Some(bounds) => {
+                        let (start, end) = bounds.split_once('-').unwrap();
+                        let (start, end): (usize, usize) = (start.parse().unwrap(), end.parse().unwrap());
+                        let content_range = format!("Content-Range: bytes {}-{}/{}\r\n", start, end, content.len());
+                        ("206 Partial Content", content[start..=end].to_string(), content_range)
+                    }
+                    None => ("200 OK", content.clone(), String::new()),
+                },
+            };
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                extra,
+                body
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+        }
+    });
+    (address, requests)
+}
+
+// 远程 FASTA 配合 .fai 时只按字节范围下载需要的 contig，远程突变文件整体下载；404 的错误信息包含 URL 和状态码
+#[cfg(feature = "remote")]
+#[test]
+fn remote_fasta_is_read_by_range() {
+    let dir = temp_dir("remote");
+    let fai = "c1\t30\t4\t30\t31\nc2\t12\t39\t12\t13\n".to_string();
+    let (address, requests) =
+        start_stub_server(vec![("/contigs.fa", CONTIGS.to_string()), ("/contigs.fa.fai", fai), ("/mutations.csv", MUTATIONS.to_string())]);
+    let local = write_inputs(&dir, CONTIGS, MUTATIONS, "c1,g1,1,10,+\n");
+    let fasta_url = format!("{}/contigs.fa", address);
+    let inputs = [PathBuf::from(&fasta_url), PathBuf::from(format!("{}/mutations.csv", address)), local[2].clone()];
+    let fai_url = format!("{}.fai", fasta_url);
+    let output = run(&dir, &inputs, &["--contigs-fai", &fai_url]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(fs::read_to_string(dir.join("out.fa")).unwrap(), ">g1\nATGTGCGTAC\n");
+    let fasta_requests: Vec<_> =
+        requests.lock().unwrap().iter().filter(|(path, _)| path == "/contigs.fa").map(|(_, range)| range.clone()).collect();
+    assert_eq!(fasta_requests, [Some("bytes=4-33".to_string())]);
+
+    let inputs = [PathBuf::from(&fasta_url), PathBuf::from(format!("{}/missing.csv", address)), local[2].clone()];
+    let output = run(&dir, &inputs, &["--contigs-fai", &fai_url]);
+    assert_eq!(output.status.code(), Some(1));
+    let message = stderr(&output);
+    assert!(message.contains(&format!("GET {}/missing.csv failed: HTTP 404", address)), "{}", message);
+}
+
+// --events 的消费者：逐行解析事件，用 gene_done 重建各基因的突变数，与 run_finished 的汇总和 --summary 一致
+#[test]
+fn events_reconstruct_the_run_summary() {
+    let dir = temp_dir("events");
+    let positions = format!("{}c9,g9,1,5\n", POSITIONS);
+    let inputs = write_inputs(&dir, CONTIGS, MUTATIONS, &positions);
+    let output = run(&dir, &inputs, &["--events", "events.jsonl", "--summary", "summary.tsv", "-t", "4"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let events: Vec<serde_json::Value> = fs::read_to_string(dir.join("events.jsonl"))
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap_or_else(|e| panic!("{}: {}", e, line)))
+        .collect();
+    for event in &events {
+        assert_eq!(event["v"], 1, "{}", event);
+        assert!(event["ts"].is_u64(), "{}", event);
+    }
+    let kind = |event: &serde_json::Value| event["event"].as_str().unwrap().to_string();
+    assert_eq!(kind(&events[0]), "run_started");
+    assert_eq!(kind(events.last().unwrap()), "run_finished");
+    assert_eq!(events[0]["config"]["threads"], "4");
+
+    let of_kind = |name: &'static str| events.iter().filter(move |event| kind(event) == name);
+    let mut loaded: Vec<&str> = of_kind("contig_loaded").map(|event| event["contig"].as_str().unwrap()).collect();
+    loaded.sort();
+    assert_eq!(loaded, ["c1", "c2"]);
+    let stages: Vec<&str> = of_kind("stage_finished").map(|event| event["stage"].as_str().unwrap()).collect();
+    for stage in ["read_contigs", "read_mutations", "replace", "write_output"] {
+        assert!(stages.contains(&stage), "{:?}", stages);
+    }
+    assert!(of_kind("warning").any(|event| event["type"] == "missing_contig" && event["message"].as_str().unwrap().ends_with(": c9")));
+
+    let gene_mutations: std::collections::HashMap<String, u64> = of_kind("gene_done")
+        .map(|event| (event["gene_id"].as_str().unwrap().to_string(), event["n_mutations"].as_u64().unwrap()))
+        .collect();
+    let summary = &events.last().unwrap()["summary"];
+    assert_eq!(gene_mutations.len() as u64, summary["genes"].as_u64().unwrap());
+    assert_eq!(gene_mutations.values().sum::<u64>(), summary["gene_mutations"].as_u64().unwrap());
+    assert_eq!(summary["mutations_applied"], 3);
+    let table = fs::read_to_string(dir.join("summary.tsv")).unwrap();
+    let rows: Vec<Vec<&str>> =
+        table.lines().filter(|line| !line.starts_with(';')).skip(1).map(|line| line.split('\t').collect()).collect();
+    assert_eq!(rows.len(), gene_mutations.len());
+    for row in rows {
+        assert_eq!(gene_mutations[row[0]].to_string(), row[5], "{:?}", row);
+    }
+}
+
+// 按指定格式压缩测试输入；未启用对应 feature 时只写入魔数和无效数据，用来检查报错
+fn compress_as(kind: &str, bytes: &[u8]) -> Vec<u8> {
+    match kind {
+        "uncompressed" => bytes.to_vec(),
+        "gzip" | "bgzf" => {
+            let builder = flate2::GzBuilder::new();
+            // bgzf：FEXTRA 中第一个子字段为 BC
+            let builder = if kind == "bgzf" { builder.extra(vec![b'B', b'C', 2, 0, 0, 0]) } else { builder };
+            let mut encoder = builder.write(vec![], flate2::Compression::default());
+            encoder.write_all(bytes).unwrap();
+            encoder.finish().unwrap()
+        }
+        #[cfg(feature = "zstd")]
+        "zstd" => zstd::encode_all(bytes, 0).unwrap(),
+        #[cfg(not(feature = "zstd"))]
+        "zstd" => [&[0x28, 0xb5, 0x2f, 0xfd][..], b"not zstd"].concat(),
+        #[cfg(feature = "xz")]
+        "xz" => {
+            let mut encoder = xz2::write::XzEncoder::new(vec![], 6);
+            encoder.write_all(bytes).unwrap();
+            encoder.finish().unwrap()
+        }
+        #[cfg(not(feature = "xz"))]
+        "xz" => [&[0xfd, b'7', b'z', b'X', b'Z', 0x00][..], b"not xz"].concat(),
+        _ => unreachable!(),
+    }
+}
+
+// 读入 .fa.gz 等 gzip 输入，-o 以 .gz 结尾时写出 gzip 压缩的 FASTA，解压后与未压缩的运行结果相同
+#[test]
+fn gzip_inputs_round_trip_to_a_gzip_output() {
+    let dir = temp_dir("gzip");
+    let inputs = write_inputs(&dir, CONTIGS, MUTATIONS, POSITIONS);
+    assert!(run(&dir, &inputs, &[]).status.success());
+    let expected = fs::read(dir.join("out.fa")).unwrap();
+
+    let gzipped = inputs.clone().map(|file| {
+        let gzipped = file.with_extension(format!("{}.gz", file.extension().unwrap().to_str().unwrap()));
+        fs::write(&gzipped, compress_as("gzip", &fs::read(&file).unwrap())).unwrap();
+        gzipped
+    });
+    let output = run_without_output(&dir, &gzipped, &["-o", "out.fa.gz"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let compressed = fs::read(dir.join("out.fa.gz")).unwrap();
+    assert_eq!(compressed[..2], [0x1f, 0x8b]);
+    let mut decompressed = vec![];
+    flate2::read::MultiGzDecoder::new(&compressed[..]).read_to_end(&mut decompressed).unwrap();
+    assert_eq!(decompressed, expected);
+}
+
+#[test]
+fn compression_is_detected_by_content_for_every_extension() {
+    let baseline_dir = temp_dir("compression-baseline");
+    let inputs = write_inputs(&baseline_dir, CONTIGS, MUTATIONS, POSITIONS);
+    assert!(run(&baseline_dir, &inputs, &[]).status.success());
+    let expected = fs::read_to_string(baseline_dir.join("out.fa")).unwrap();
+
+    let extensions = [("", "uncompressed"), (".gz", "gzip"), (".bgz", "bgzf"), (".zst", "zstd"), (".xz", "xz")];
+    for kind in ["uncompressed", "gzip", "bgzf", "zstd", "xz"] {
+        for (extension, implied) in extensions {
+            let case = format!("{} content named *{}", kind, extension);
+            let dir = temp_dir("compression");
+            let names = ["contigs.fa", "mutations.csv", "positions.csv"];
+            let inputs = [CONTIGS, MUTATIONS, POSITIONS].map(|content| content.as_bytes());
+            let files: Vec<PathBuf> = names.iter().map(|name| dir.join(format!("{}{}", name, extension))).collect();
+            for (file, content) in files.iter().zip(inputs) {
+                fs::write(file, compress_as(kind, content)).unwrap();
+            }
+            let output = run(&dir, &[files[0].clone(), files[1].clone(), files[2].clone()], &[]);
+            let log = stderr(&output);
+
+            let supported = (kind != "zstd" || cfg!(feature = "zstd")) && (kind != "xz" || cfg!(feature = "xz"));
+            if !supported {
+                assert!(!output.status.success(), "{}", case);
+                assert!(log.contains(&format!("built with the \"{}\" feature", kind)), "{}: {}", case, log);
+                continue;
+            }
+            assert!(output.status.success(), "{}: {}", case, log);
+            assert_eq!(fs::read_to_string(dir.join("out.fa")).unwrap(), expected, "{}", case);
+
+            let detected = log.lines().filter(|line| line.contains(&format!("detected {} compression", kind))).count();
+            assert_eq!(detected, if kind == "uncompressed" { 0 } else { 3 }, "{}: {}", case, log);
+            let mismatch = implied != "uncompressed" && implied != kind && !(kind == "bgzf" && implied == "gzip");
+            let warnings = log.lines().filter(|line| line.contains("warning: the extension of")).count();
+            assert_eq!(warnings, if mismatch { 3 } else { 0 }, "{}: {}", case, log);
+            if mismatch {
+                let message = format!("indicates {} compression but the content is {}", implied, kind);
+                assert!(log.contains(&message), "{}: {}", case, log);
+            }
+        }
+    }
+}
@@ -0,0 +1,44 @@
+# Python 绑定的冒烟测试：用 maturin develop 安装 python feature 构建的模块后运行 pytest
+import z10
+
+
+def write_inputs(directory):
+    contigs = directory / "contigs.fa"
+    contigs.write_text(">c1\nACGTACGTACGTACGTACGTACGTACGTAC\n>c2\nTTTTGGGGCCCC\n")
+    positions = directory / "positions.csv"
+    positions.write_text("c1,g1,1,10\nc2,g3,2,8\n")
+    return str(contigs), str(positions)
+
+
+def test_round_trip(tmp_path):
+    contigs_file, positions_file = write_inputs(tmp_path)
+    contigs = z10.load_contigs(contigs_file)
+    assert contigs == {"c1": "ACGTACGTACGTACGTACGTACGTACGTAC", "c2": "TTTTGGGGCCCC"}
+    genes = z10.load_gene_positions(positions_file)
+    assert sorted(genes) == [("c1", "g1", 1, 10, "."), ("c2", "g3", 2, 8, ".")]
+
+    mutations = [("c1", 2, "T", "m1"), ("c1", 5, "G"), ("c2", 3, "A"), ("c1", 28, "A")]
+    sequences, report = z10.apply_snvs(contigs, mutations, genes, threads=2)
+    assert sequences == {"g1": "ATGTGCGTAC", "g3": "TATGGGG"}
+    assert sorted(report["applied"]) == [
+        ("c1", 2, "C", "T", "m1", "g1"),
+        ("c1", 5, "A", "G", None, "g1"),
+        ("c2", 3, "T", "A", None, "g3"),
+    ]
+    assert report["rejected"] == [("c1", 28, "A", None, "outside_genes")]
+    assert report["gene_mutations"] == {"g1": 2, "g3": 1}
+    assert report["counters"]["mutations_applied"] == 3
+
+
+def test_minus_strand_and_unknown_option(tmp_path):
+    contigs = {"c1": "ACGTACGTAC"}
+    sequences, _ = z10.apply_snvs(contigs, [("c1", 1, "T")], [("c1", "g1", 1, 4, "-")])
+    assert sequences == {"g1": "ACGA"}
+    sequences, _ = z10.apply_snvs(contigs, [("c1", 1, "T")], [("c1", "g1", 1, 4, "-")], revcomp_minus=False)
+    assert sequences == {"g1": "TCGT"}
+    try:
+        z10.apply_snvs(contigs, [], [], no_such_option=True)
+    except TypeError:
+        pass
+    else:
+        raise AssertionError("unknown keyword accepted")
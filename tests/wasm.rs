@@ -0,0 +1,22 @@
+// wasm 封装的端到端测试：与网页一样只传字符串，检查 FASTA 输出、翻译和错误 JSON。
+// 在本机按 wasm feature 运行（cargo test --features wasm）
+#![cfg(feature = "wasm")]
+
+use z10::wasm::apply_snvs;
+
+const CONTIGS: &str = ">c1\nACGTACGTACGTACGTACGTACGTACGTAC\n>c2\nTTTTGGGGCCCC\n";
+const MUTATIONS: &str = "c1,2,T\nc1,5,G\nc2,3,A\n";
+const POSITIONS: &str = "c1,g1,1,10,+\nc1,g2,5,25,-\nc2,g3,2,8\n";
+
+#[test]
+fn apply_snvs_end_to_end() {
+    let output = apply_snvs(CONTIGS, MUTATIONS, POSITIONS, "");
+    assert_eq!(output, ">g1\nATGTGCGTAC\n>g2\nTACGTACGTACGTACGTACGC\n>g3\nTATGGGG\n");
+
+    let output = apply_snvs(CONTIGS, MUTATIONS, "c1,g1,1,9,+\n", r#"{"translate": true}"#);
+    assert_eq!(output, ">g1\nATGTGCGTA\n>g1_protein\nMCV\n");
+
+    let output = apply_snvs(CONTIGS, MUTATIONS, POSITIONS, r#"{"only_strand": "x"}"#);
+    let error: serde_json::Value = serde_json::from_str(&output).unwrap();
+    assert!(error["error"].as_str().unwrap().contains("only_strand"), "{}", output);
+}